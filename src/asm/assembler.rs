@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::asm::{AsmError, AsmErrorKind, Program, Segment};
+use crate::asm::expr::{eval, quoted, Scope};
+use crate::disasm::{decode_bytes, Operand};
+
+const DIRECTIVES: [&str; 6] = ["ORG", "EQU", "DB", "DW", "DS", "END"];
+
+/// One source line split into its fields
+struct Line {
+    number: usize,
+    label: Option<String>,
+    /// Upper case mnemonic or directive
+    op: Option<String>,
+    operands: Vec<String>,
+}
+
+/// Names and `$` while evaluating a line
+struct Here<'a> {
+    symbols: &'a BTreeMap<String, i32>,
+    addr: i32,
+}
+
+impl<'a> Scope for Here<'a> {
+    fn symbol(&self, name: &str) -> Option<i32> {
+        self.symbols.get(name).copied()
+    }
+
+    fn here(&self) -> i32 {
+        self.addr
+    }
+}
+
+/// Register operands and immediate size of each mnemonic, taken from the disassembler
+/// so that both directions share one opcode table
+struct OpTable {
+    opcodes: HashMap<(&'static str, Vec<&'static str>), u8>,
+    /// Number of register operands and instruction length
+    shapes: HashMap<&'static str, (usize, u16)>,
+}
+
+impl OpTable {
+    fn new() -> Self {
+        let mut table = Self { opcodes: HashMap::new(), shapes: HashMap::new() };
+        for op_code in 0..=255u8 {
+            let instruction = decode_bytes(&[op_code, 0, 0]).unwrap();
+            if instruction.undocumented {
+                continue;
+            }
+            let regs: Vec<&'static str> = instruction.operands.iter().filter_map(|operand| match operand {
+                Operand::Register(name) => Some(*name),
+                _ => None,
+            }).collect();
+            // RST 的操作数是表达式
+            let regs_len = if instruction.mnemonic == "RST" { 0 } else { regs.len() };
+            table.shapes.insert(instruction.mnemonic, (regs_len, instruction.len));
+            table.opcodes.insert((instruction.mnemonic, regs), op_code);
+        }
+        table
+    }
+
+    fn len(&self, mnemonic: &str) -> Option<u16> {
+        self.shapes.get(mnemonic).map(|shape| shape.1)
+    }
+}
+
+/// Assemble Intel 8080 source: labels, ORG, DB/DW/DS, EQU, END, expressions and every instruction
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let table = OpTable::new();
+    let lines = source.lines().enumerate()
+        .map(|(i, text)| split_line(&table, i + 1, text))
+        .collect::<Result<Vec<Line>, AsmError>>()?;
+    let symbols = define_symbols(&lines, &table)?;
+    emit(&lines, &table, symbols)
+}
+
+fn error(line: &Line, kind: AsmErrorKind) -> AsmError {
+    AsmError { line: line.number, kind }
+}
+
+fn split_line(table: &OpTable, number: usize, text: &str) -> Result<Line, AsmError> {
+    let fail = |msg: &str| AsmError { line: number, kind: AsmErrorKind::Syntax(String::from(msg)) };
+    let code = strip_comment(text).map_err(|kind| AsmError { line: number, kind })?;
+    let mut rest = code.trim_start();
+    let mut label = None;
+    let first = rest.split_whitespace().next().unwrap_or("");
+    let second = rest.split_whitespace().nth(1).unwrap_or("").to_ascii_uppercase();
+    let known = |word: &str| {
+        let upper = word.to_ascii_uppercase();
+        DIRECTIVES.contains(&upper.as_str()) || table.shapes.contains_key(upper.as_str())
+    };
+    // 冒号结尾、后面跟 EQU、或者顶格写且不是助记符的都是标号
+    if first.ends_with(':') || second == "EQU"
+        || (!first.is_empty() && !code.starts_with(char::is_whitespace) && !known(first)) {
+        let name = first.trim_end_matches(':');
+        if name.is_empty() || !name.starts_with(|c: char| c.is_ascii_alphabetic() || "_?@".contains(c)) {
+            return Err(fail("bad label"));
+        }
+        label = Some(name.to_ascii_uppercase());
+        rest = rest[first.len()..].trim_start();
+    }
+    let op_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let op = &rest[..op_end];
+    let operands = split_operands(&rest[op_end..]).map_err(|kind| AsmError { line: number, kind })?;
+    if op.is_empty() && !operands.is_empty() {
+        return Err(fail("operands without an instruction"));
+    }
+    Ok(Line {
+        number,
+        label,
+        op: if op.is_empty() { None } else { Some(op.to_ascii_uppercase()) },
+        operands,
+    })
+}
+
+/// The line up to a `;` outside quotes
+fn strip_comment(text: &str) -> Result<String, AsmErrorKind> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            ';' => break,
+            '\'' | '"' => i = quoted(&chars, i)?.1,
+            _ => i += 1,
+        }
+    }
+    Ok(chars[..i].iter().collect())
+}
+
+/// Split at commas outside quotes and parentheses
+fn split_operands(text: &str) -> Result<Vec<String>, AsmErrorKind> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut operands = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\'' | '"' => {
+                i = quoted(&chars, i)?.1;
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(chars[start..i].iter().collect::<String>());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    operands.push(chars[start..].iter().collect::<String>());
+    let operands: Vec<String> = operands.iter().map(|s| String::from(s.trim())).collect();
+    if operands.len() == 1 && operands[0].is_empty() {
+        return Ok(Vec::new());
+    }
+    if operands.iter().any(|s| s.is_empty()) {
+        return Err(AsmErrorKind::Syntax(String::from("empty operand")));
+    }
+    Ok(operands)
+}
+
+/// The whole operand is one quoted string
+fn string_operand(operand: &str) -> Option<String> {
+    let chars: Vec<char> = operand.chars().collect();
+    if !matches!(chars.first(), Some('\'') | Some('"')) {
+        return None;
+    }
+    match quoted(&chars, 0) {
+        Ok((s, end)) if end == chars.len() => Some(s),
+        _ => None,
+    }
+}
+
+/// DB strings longer than two characters are spelled out, anything else is one byte
+fn db_len(operands: &[String]) -> i32 {
+    operands.iter().map(|operand| match string_operand(operand) {
+        Some(s) if s.len() != 1 => s.len() as i32,
+        _ => 1,
+    }).sum()
+}
+
+fn one_operand(line: &Line) -> Result<&str, AsmError> {
+    match line.operands.as_slice() {
+        [operand] => Ok(operand),
+        _ => Err(error(line, AsmErrorKind::Syntax(format!("{} takes one operand", line.op.as_deref().unwrap_or(""))))),
+    }
+}
+
+/// Pass 1: the address of every label and the value of every EQU
+fn define_symbols(lines: &[Line], table: &OpTable) -> Result<BTreeMap<String, i32>, AsmError> {
+    let mut symbols = BTreeMap::new();
+    let mut pending = Vec::new();
+    let mut addr = 0i32;
+    for line in lines {
+        let op = line.op.as_deref().unwrap_or("");
+        let value = |expr: &str, symbols: &BTreeMap<String, i32>| {
+            eval(expr, &Here { symbols, addr }).map_err(|kind| error(line, kind))
+        };
+        if op == "EQU" {
+            let name = line.label.clone().ok_or_else(|| error(line, AsmErrorKind::Syntax(String::from("EQU needs a name"))))?;
+            let expr = one_operand(line)?;
+            if symbols.contains_key(&name) {
+                return Err(error(line, AsmErrorKind::Redefined(name)));
+            }
+            match eval(expr, &Here { symbols: &symbols, addr }) {
+                Ok(v) => {
+                    symbols.insert(name, v);
+                }
+                // 向前引用的 EQU 等标号都定义完再算
+                Err(AsmErrorKind::Undefined(_)) => pending.push((line, name, addr)),
+                Err(kind) => return Err(error(line, kind)),
+            }
+            continue;
+        }
+        if let Some(name) = &line.label {
+            if symbols.insert(name.clone(), addr).is_some() {
+                return Err(error(line, AsmErrorKind::Redefined(name.clone())));
+            }
+        }
+        addr += match op {
+            "" => 0,
+            "ORG" => value(one_operand(line)?, &symbols)? - addr,
+            "DS" => value(one_operand(line)?, &symbols)?,
+            "DB" => db_len(&line.operands),
+            "DW" => 2 * line.operands.len() as i32,
+            "END" => break,
+            _ => i32::from(table.len(op).ok_or_else(|| error(line, AsmErrorKind::UnknownMnemonic(String::from(op))))?),
+        };
+        if !(0..=0x10000).contains(&addr) {
+            return Err(error(line, AsmErrorKind::OutOfRange(addr)));
+        }
+    }
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut rest = Vec::new();
+        for (line, name, addr) in pending {
+            match eval(one_operand(line)?, &Here { symbols: &symbols, addr }) {
+                Ok(v) => {
+                    symbols.insert(name, v);
+                }
+                Err(AsmErrorKind::Undefined(undefined)) => rest.push((line, name, addr, undefined)),
+                Err(kind) => return Err(error(line, kind)),
+            }
+        }
+        if rest.len() == before {
+            let (line, _, _, undefined) = &rest[0];
+            return Err(error(line, AsmErrorKind::Undefined(undefined.clone())));
+        }
+        pending = rest.into_iter().map(|(line, name, addr, _)| (line, name, addr)).collect();
+    }
+    Ok(symbols)
+}
+
+/// Pass 2: the bytes
+fn emit(lines: &[Line], table: &OpTable, symbols: BTreeMap<String, i32>) -> Result<Program, AsmError> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut addr = 0i32;
+    for line in lines {
+        let op = match line.op.as_deref() {
+            Some(op) => op,
+            None => continue,
+        };
+        let here = Here { symbols: &symbols, addr };
+        let value = |expr: &str| eval(expr, &here).map_err(|kind| error(line, kind));
+        let byte = |expr: &str| value(expr).and_then(|v| fit(line, v, -128, 0xff));
+        let word = |expr: &str| value(expr).and_then(|v| fit(line, v, -32768, 0xffff));
+        let mut bytes = Vec::new();
+        match op {
+            "EQU" => continue,
+            "END" => break,
+            "ORG" => {
+                addr = value(one_operand(line)?)?;
+                continue;
+            }
+            "DS" => {
+                addr += value(one_operand(line)?)?;
+                continue;
+            }
+            "DB" => for operand in &line.operands {
+                match string_operand(operand) {
+                    Some(s) if s.len() != 1 => bytes.extend(s.bytes()),
+                    _ => bytes.push(byte(operand)? as u8),
+                }
+            },
+            "DW" => for operand in &line.operands {
+                let v = word(operand)? as u16;
+                bytes.extend_from_slice(&[v as u8, (v >> 8) as u8]);
+            },
+            "RST" => {
+                let n = value(one_operand(line)?)?;
+                bytes.push(0xc7 | (fit(line, n, 0, 7)? as u8) << 3);
+            }
+            _ => {
+                let (regs_len, len) = table.shapes[op];
+                let data = if len > 1 { 1 } else { 0 };
+                if line.operands.len() != regs_len + data {
+                    return Err(error(line, AsmErrorKind::Syntax(format!("{} takes {} operands", op, regs_len + data))));
+                }
+                let regs: Vec<String> = line.operands[..regs_len].iter().map(|r| r.to_ascii_uppercase()).collect();
+                let key: Vec<&str> = regs.iter().map(|r| r.as_str()).collect();
+                let op_code = table.opcodes.iter()
+                    .find(|((mnemonic, r), _)| *mnemonic == op && r.as_slice() == key.as_slice())
+                    .map(|(_, op_code)| *op_code)
+                    .ok_or_else(|| error(line, AsmErrorKind::Syntax(format!("bad registers for {}: {}", op, regs.join(",")))))?;
+                bytes.push(op_code);
+                match len {
+                    2 => bytes.push(byte(&line.operands[regs_len])? as u8),
+                    3 => {
+                        let v = word(&line.operands[regs_len])? as u16;
+                        bytes.extend_from_slice(&[v as u8, (v >> 8) as u8]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        match segments.last_mut() {
+            Some(last) if i32::from(last.addr) + last.bytes.len() as i32 == addr => last.bytes.extend(&bytes),
+            _ if !bytes.is_empty() => segments.push(Segment { addr: addr as u16, bytes: bytes.clone() }),
+            _ => {}
+        }
+        addr += bytes.len() as i32;
+    }
+    let symbols = symbols.into_iter().map(|(name, v)| (name, v as u16)).collect();
+    Ok(Program { segments, symbols })
+}
+
+fn fit(line: &Line, value: i32, min: i32, max: i32) -> Result<i32, AsmError> {
+    if (min..=max).contains(&value) {
+        Ok(value & max)
+    } else {
+        Err(error(line, AsmErrorKind::OutOfRange(value)))
+    }
+}
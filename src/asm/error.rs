@@ -0,0 +1,41 @@
+use std::error::Error;
+use std::fmt;
+
+/// What is wrong with a source line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    Syntax(String),
+    UnknownMnemonic(String),
+    /// A symbol that is never defined, or defined after an ORG, DS or EQU that needs it
+    Undefined(String),
+    Redefined(String),
+    /// The value does not fit in the byte or word it is assembled into
+    OutOfRange(i32),
+}
+
+/// An error on a 1-based source line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmErrorKind::Syntax(msg) => write!(f, "{}", msg),
+            AsmErrorKind::UnknownMnemonic(name) => write!(f, "unknown instruction '{}'", name),
+            AsmErrorKind::Undefined(name) => write!(f, "undefined symbol '{}'", name),
+            AsmErrorKind::Redefined(name) => write!(f, "'{}' is already defined", name),
+            AsmErrorKind::OutOfRange(value) => write!(f, "value {} is out of range", value),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl Error for AsmError {}
@@ -0,0 +1,235 @@
+use crate::asm::AsmErrorKind;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(i32),
+    Name(String),
+    /// A quoted character constant, one or two characters
+    Chars(String),
+    Op(char),
+}
+
+/// Value lookup for names and `$`
+pub trait Scope {
+    fn symbol(&self, name: &str) -> Option<i32>;
+
+    /// Address of the current line
+    fn here(&self) -> i32;
+}
+
+/// Evaluate an expression: numbers (`12`, `0CH`, `1010B`, `14O`/`14Q`, `0x0c`), `'c'`, `$`, symbols,
+/// `+ - * /`, `MOD SHL SHR AND OR XOR NOT HIGH LOW` and parentheses
+pub fn eval(text: &str, scope: &dyn Scope) -> Result<i32, AsmErrorKind> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0, scope };
+    let value = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(_) => Err(syntax(format!("unexpected text in '{}'", text.trim()))),
+    }
+}
+
+fn syntax(msg: String) -> AsmErrorKind {
+    AsmErrorKind::Syntax(msg)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, AsmErrorKind> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let (s, next) = quoted(&chars, i)?;
+            tokens.push(Token::Chars(s));
+            i = next;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(number(&word)?));
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '?' || c == '@' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || "_?@".contains(chars[i])) {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect::<String>().to_ascii_uppercase()));
+        } else if "+-*/()$".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return Err(syntax(format!("unexpected '{}'", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// The string starting at the quote `chars[start]`, a doubled quote stands for itself.
+/// Returns the text and the index after the closing quote
+pub fn quoted(chars: &[char], start: usize) -> Result<(String, usize), AsmErrorKind> {
+    let quote = chars[start];
+    let mut s = String::new();
+    let mut i = start + 1;
+    loop {
+        match chars.get(i) {
+            None => return Err(syntax(String::from("unterminated string"))),
+            Some(&c) if c == quote => {
+                if chars.get(i + 1) == Some(&quote) {
+                    s.push(quote);
+                    i += 2;
+                } else {
+                    return Ok((s, i + 1));
+                }
+            }
+            Some(&c) => {
+                s.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn number(word: &str) -> Result<i32, AsmErrorKind> {
+    let upper = word.to_ascii_uppercase();
+    let (digits, radix) = if let Some(hex) = upper.strip_prefix("0X") {
+        (hex, 16)
+    } else if let Some(hex) = upper.strip_suffix('H') {
+        (hex, 16)
+    } else if let Some(bin) = upper.strip_suffix('B') {
+        (bin, 2)
+    } else if let Some(oct) = upper.strip_suffix('O').or_else(|| upper.strip_suffix('Q')) {
+        (oct, 8)
+    } else if let Some(dec) = upper.strip_suffix('D') {
+        (dec, 10)
+    } else {
+        (upper.as_str(), 10)
+    };
+    i32::from_str_radix(digits, radix).map_err(|_| syntax(format!("bad number '{}'", word)))
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    scope: &'a dyn Scope,
+}
+
+impl<'a> Parser<'a> {
+    fn peek_name(&self, names: &[&str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Name(name)) => ["OR", "XOR", "AND", "NOT", "MOD", "SHL", "SHR", "HIGH", "LOW"].iter()
+                .find(|op| *op == name && names.contains(op)).copied(),
+            _ => None,
+        }
+    }
+
+    fn peek_op(&self, ops: &str) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(c)) if ops.contains(*c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn or(&mut self) -> Result<i32, AsmErrorKind> {
+        let mut value = self.and()?;
+        while let Some(op) = self.peek_name(&["OR", "XOR"]) {
+            self.pos += 1;
+            let rhs = self.and()?;
+            value = if op == "OR" { value | rhs } else { value ^ rhs };
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<i32, AsmErrorKind> {
+        let mut value = self.not()?;
+        while self.peek_name(&["AND"]).is_some() {
+            self.pos += 1;
+            value &= self.not()?;
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<i32, AsmErrorKind> {
+        if self.peek_name(&["NOT"]).is_some() {
+            self.pos += 1;
+            return Ok(!self.not()?);
+        }
+        self.add()
+    }
+
+    fn add(&mut self) -> Result<i32, AsmErrorKind> {
+        let mut value = self.mul()?;
+        while let Some(op) = self.peek_op("+-") {
+            self.pos += 1;
+            let rhs = self.mul()?;
+            value = if op == '+' { value.wrapping_add(rhs) } else { value.wrapping_sub(rhs) };
+        }
+        Ok(value)
+    }
+
+    fn mul(&mut self) -> Result<i32, AsmErrorKind> {
+        let mut value = self.unary()?;
+        loop {
+            let op = match (self.peek_op("*/"), self.peek_name(&["MOD", "SHL", "SHR"])) {
+                (Some('*'), _) => "*",
+                (Some(_), _) => "/",
+                (None, Some(name)) => name,
+                (None, None) => return Ok(value),
+            };
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match op {
+                "*" => value.wrapping_mul(rhs),
+                "/" | "MOD" if rhs == 0 => return Err(syntax(String::from("division by zero"))),
+                "/" => value / rhs,
+                "MOD" => value % rhs,
+                "SHL" => value.wrapping_shl(rhs as u32),
+                _ => value.wrapping_shr(rhs as u32),
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<i32, AsmErrorKind> {
+        if let Some(op) = self.peek_op("+-") {
+            self.pos += 1;
+            let value = self.unary()?;
+            return Ok(if op == '-' { value.wrapping_neg() } else { value });
+        }
+        if let Some(op) = self.peek_name(&["HIGH", "LOW"]) {
+            self.pos += 1;
+            let value = self.unary()?;
+            return Ok(if op == "HIGH" { (value >> 8) & 0xff } else { value & 0xff });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<i32, AsmErrorKind> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| syntax(String::from("missing value")))?;
+        self.pos += 1;
+        match token {
+            Token::Num(value) => Ok(value),
+            Token::Op('$') => Ok(self.scope.here()),
+            Token::Op('(') => {
+                let value = self.or()?;
+                if self.peek_op(")").is_none() {
+                    return Err(syntax(String::from("missing ')'")));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Token::Chars(s) => {
+                let bytes = s.as_bytes();
+                match bytes.len() {
+                    1 => Ok(i32::from(bytes[0])),
+                    2 => Ok(i32::from(bytes[0]) << 8 | i32::from(bytes[1])),
+                    _ => Err(syntax(format!("'{}' is not a one or two character constant", s))),
+                }
+            }
+            Token::Name(name) => self.scope.symbol(&name).ok_or(AsmErrorKind::Undefined(name)),
+            Token::Op(c) => Err(syntax(format!("unexpected '{}'", c))),
+        }
+    }
+}
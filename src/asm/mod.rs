@@ -0,0 +1,8 @@
+mod assembler;
+mod error;
+mod expr;
+mod program;
+
+pub use assembler::assemble;
+pub use error::{AsmError, AsmErrorKind};
+pub use program::{Program, Segment};
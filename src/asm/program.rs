@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+/// Bytes assembled to consecutive addresses
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+}
+
+/// Output of `assemble`
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    pub segments: Vec<Segment>,
+    /// Labels and EQUs
+    pub symbols: BTreeMap<String, u16>,
+}
+
+impl Program {
+    /// Copy every segment into a 64K memory image
+    pub fn load(&self, mem: &mut [u8]) {
+        for segment in &self.segments {
+            let start = usize::from(segment.addr);
+            mem[start..start + segment.bytes.len()].copy_from_slice(&segment.bytes);
+        }
+    }
+
+    /// Start address and one flat image up to the last byte, gaps left by ORG or DS are zero
+    pub fn to_binary(&self) -> (u16, Vec<u8>) {
+        let start = match self.segments.iter().map(|s| s.addr).min() {
+            Some(start) => start,
+            None => return (0, Vec::new()),
+        };
+        let end = self.segments.iter().map(|s| usize::from(s.addr) + s.bytes.len()).max().unwrap_or(0);
+        let mut image = vec![0u8; end - usize::from(start)];
+        for segment in &self.segments {
+            let at = usize::from(segment.addr - start);
+            image[at..at + segment.bytes.len()].copy_from_slice(&segment.bytes);
+        }
+        (start, image)
+    }
+
+    /// Intel HEX with 16 byte data records and an end of file record
+    pub fn to_intel_hex(&self) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            for (i, chunk) in segment.bytes.chunks(16).enumerate() {
+                let addr = segment.addr.wrapping_add((i * 16) as u16);
+                let mut record = vec![chunk.len() as u8, (addr >> 8) as u8, addr as u8, 0x00];
+                record.extend_from_slice(chunk);
+                let checksum = record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg();
+                record.push(checksum);
+                out.push(':');
+                out.extend(record.iter().map(|b| format!("{:02X}", b)));
+                out.push('\n');
+            }
+        }
+        out.push_str(":00000001FF\n");
+        out
+    }
+
+    /// `ADDR NAME` lines, the format `disasm::Symbols` reads
+    pub fn symbol_file(&self) -> String {
+        self.symbols.iter().map(|(name, addr)| format!("{:04X} {}\n", addr, name)).collect()
+    }
+}
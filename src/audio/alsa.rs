@@ -0,0 +1,156 @@
+use std::ffi::{c_char, c_int, c_long, c_uint, c_ulong, c_void, CStr, CString};
+use std::io;
+use std::ptr;
+
+use crate::audio::AudioSink;
+
+const RTLD_NOW: c_int = 2;
+const SND_PCM_STREAM_PLAYBACK: c_int = 0;
+const SND_PCM_FORMAT_S16_LE: c_int = 2;
+const SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+
+// glibc 2.34 之前 dlopen 在 libdl 里
+#[link(name = "dl")]
+extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+type Open = unsafe extern "C" fn(*mut *mut c_void, *const c_char, c_int, c_int) -> c_int;
+type SetParams = unsafe extern "C" fn(*mut c_void, c_int, c_int, c_uint, c_uint, c_int, c_uint) -> c_int;
+type Writei = unsafe extern "C" fn(*mut c_void, *const c_void, c_ulong) -> c_long;
+type Delay = unsafe extern "C" fn(*mut c_void, *mut c_long) -> c_int;
+type Recover = unsafe extern "C" fn(*mut c_void, c_int, c_int) -> c_int;
+type Close = unsafe extern "C" fn(*mut c_void) -> c_int;
+type Strerror = unsafe extern "C" fn(c_int) -> *const c_char;
+
+/// The few libasound calls used, looked up when the sink opens so the build does not need ALSA
+struct Lib {
+    handle: *mut c_void,
+    writei: Writei,
+    delay: Delay,
+    recover: Recover,
+    drain: Close,
+    close: Close,
+    strerror: Strerror,
+}
+
+impl Lib {
+    fn load() -> io::Result<(Self, Open, SetParams)> {
+        let handle = unsafe { dlopen(b"libasound.so.2\0".as_ptr() as *const c_char, RTLD_NOW) };
+        if handle.is_null() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "libasound.so.2 is not installed"));
+        }
+        // 名字以 0 结尾
+        let symbol = |name: &[u8]| {
+            let f = unsafe { dlsym(handle, name.as_ptr() as *const c_char) };
+            if f.is_null() {
+                Err(io::Error::other(format!("libasound has no {}", String::from_utf8_lossy(&name[..name.len() - 1]))))
+            } else {
+                Ok(f)
+            }
+        };
+        // 函数指针的类型和 alsa/pcm.h 的声明一致
+        let loaded = (|| unsafe {
+            let lib = Lib {
+                handle,
+                writei: std::mem::transmute::<*mut c_void, Writei>(symbol(b"snd_pcm_writei\0")?),
+                delay: std::mem::transmute::<*mut c_void, Delay>(symbol(b"snd_pcm_delay\0")?),
+                recover: std::mem::transmute::<*mut c_void, Recover>(symbol(b"snd_pcm_recover\0")?),
+                drain: std::mem::transmute::<*mut c_void, Close>(symbol(b"snd_pcm_drain\0")?),
+                close: std::mem::transmute::<*mut c_void, Close>(symbol(b"snd_pcm_close\0")?),
+                strerror: std::mem::transmute::<*mut c_void, Strerror>(symbol(b"snd_strerror\0")?),
+            };
+            let open = std::mem::transmute::<*mut c_void, Open>(symbol(b"snd_pcm_open\0")?);
+            let set_params = std::mem::transmute::<*mut c_void, SetParams>(symbol(b"snd_pcm_set_params\0")?);
+            Ok((lib, open, set_params))
+        })();
+        if loaded.is_err() {
+            unsafe { dlclose(handle) };
+        }
+        loaded
+    }
+
+    fn error(&self, code: c_int) -> io::Error {
+        let text = unsafe { CStr::from_ptr((self.strerror)(code)) };
+        io::Error::other(format!("ALSA: {}", text.to_string_lossy()))
+    }
+}
+
+/// Plays mono 16 bit sound on the default ALSA device. Writes block once `latency` worth is queued
+pub struct AlsaSink {
+    lib: Lib,
+    pcm: *mut c_void,
+}
+
+impl AlsaSink {
+    /// The default device. `latency` is its buffer in microseconds
+    pub fn open(rate: u32, latency: u32) -> io::Result<Self> {
+        Self::open_device("default", rate, latency)
+    }
+
+    /// An ALSA device by name, like `default`, `hw:0` or `null`
+    pub fn open_device(device: &str, rate: u32, latency: u32) -> io::Result<Self> {
+        let device = CString::new(device).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "bad device name"))?;
+        let (lib, open, set_params) = Lib::load()?;
+        let mut pcm = ptr::null_mut();
+        let code = unsafe { open(&mut pcm, device.as_ptr(), SND_PCM_STREAM_PLAYBACK, 0) };
+        if code < 0 {
+            let e = lib.error(code);
+            unsafe { dlclose(lib.handle) };
+            return Err(e);
+        }
+        let sink = Self { lib, pcm };
+        // 允许 ALSA 重采样
+        let code = unsafe { set_params(pcm, SND_PCM_FORMAT_S16_LE, SND_PCM_ACCESS_RW_INTERLEAVED, 1, rate, 1, latency) };
+        if code < 0 {
+            return Err(sink.lib.error(code));
+        }
+        Ok(sink)
+    }
+}
+
+impl AudioSink for AlsaSink {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        let mut done = 0;
+        while done < samples.len() {
+            let rest = &samples[done..];
+            let written = unsafe { (self.lib.writei)(self.pcm, rest.as_ptr() as *const c_void, rest.len() as c_ulong) };
+            if written < 0 {
+                // 缓冲放空了就恢复, 接着写
+                let code = unsafe { (self.lib.recover)(self.pcm, written as c_int, 1) };
+                if code < 0 {
+                    return Err(self.lib.error(code));
+                }
+            } else {
+                done += written as usize;
+            }
+        }
+        Ok(())
+    }
+
+    fn queued(&self) -> Option<usize> {
+        let mut delay: c_long = 0;
+        let code = unsafe { (self.lib.delay)(self.pcm, &mut delay) };
+        if code < 0 {
+            Some(0)
+        } else {
+            Some(delay.max(0) as usize)
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        unsafe { (self.lib.drain)(self.pcm) };
+        Ok(())
+    }
+}
+
+impl Drop for AlsaSink {
+    fn drop(&mut self) {
+        unsafe {
+            (self.lib.close)(self.pcm);
+            dlclose(self.lib.handle);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+#[cfg(target_os = "linux")]
+mod alsa;
+mod sink;
+mod wav;
+
+#[cfg(target_os = "linux")]
+pub use alsa::AlsaSink;
+pub use sink::{AudioSink, NullSink, WavSink};
+pub use wav::{Wav, WavWriter};
+
+/// Sample rate of everything the emulator records or plays
+pub const SAMPLE_RATE: u32 = 44100;
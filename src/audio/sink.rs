@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+
+use crate::audio::{SAMPLE_RATE, WavWriter};
+
+/// Where mixed sound goes: mono samples at `SAMPLE_RATE`
+pub trait AudioSink {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()>;
+
+    /// Samples written but not played yet, for sinks that play in real time. The emulation
+    /// can follow this clock instead of its own, see `RateControl`
+    fn queued(&self) -> Option<usize> {
+        None
+    }
+
+    /// Flush whatever is buffered, the sink is not written to after this
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Throws the sound away, counting the samples
+#[derive(Debug, Default)]
+pub struct NullSink {
+    samples: u64,
+}
+
+impl NullSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+}
+
+impl AudioSink for NullSink {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.samples += samples.len() as u64;
+        Ok(())
+    }
+}
+
+/// Records the sound to a WAV file
+pub struct WavSink {
+    wav: Option<WavWriter<BufWriter<File>>>,
+}
+
+impl WavSink {
+    pub fn create(path: &str) -> io::Result<Self> {
+        let wav = WavWriter::new(BufWriter::new(File::create(path)?), SAMPLE_RATE, 1)?;
+        Ok(Self { wav: Some(wav) })
+    }
+}
+
+impl AudioSink for WavSink {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        match self.wav.as_mut() {
+            Some(wav) => wav.write(samples),
+            None => Err(io::Error::other("the WAV file is finished")),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.wav.take() {
+            Some(wav) => wav.finish().map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 没调用 finish 也尽量写好文件头
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Keeps everything in memory, for tests
+impl AudioSink for Vec<i16> {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.extend_from_slice(samples);
+        Ok(())
+    }
+}
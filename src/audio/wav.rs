@@ -0,0 +1,126 @@
+use std::io;
+use std::io::{Seek, SeekFrom, Write};
+
+/// A decoded WAV file, samples interleaved by channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Wav {
+    pub rate: u32,
+    pub channels: u16,
+    pub samples: Vec<i16>,
+}
+
+impl Wav {
+    /// Read 8 or 16 bit PCM
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(String::from("not a WAV file"));
+        }
+        let mut format = None;
+        let mut pos = 12;
+        while pos + 8 <= data.len() {
+            let id = &data[pos..pos + 4];
+            let len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+            let body = &data[pos + 8..data.len().min(pos + 8 + len)];
+            match id {
+                b"fmt " if body.len() >= 16 => {
+                    let u16_at = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+                    let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                    format = Some((u16_at(0), u16_at(2), rate, u16_at(14)));
+                }
+                b"data" => {
+                    let (tag, channels, rate, bits) = format.ok_or("the data chunk comes before fmt")?;
+                    // 1 是 PCM, 0xfffe 是扩展格式, 这里只当作 PCM 读
+                    if (tag != 1 && tag != 0xfffe) || channels == 0 || rate == 0 {
+                        return Err(String::from("only PCM WAV files are supported"));
+                    }
+                    let samples = match bits {
+                        8 => body.iter().map(|&b| (i16::from(b) - 128) << 8).collect(),
+                        16 => body.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect(),
+                        _ => return Err(format!("{} bit samples are not supported", bits)),
+                    };
+                    return Ok(Self { rate, channels, samples });
+                }
+                _ => {}
+            }
+            // 块按偶数字节对齐
+            pos += 8 + len + (len & 1);
+        }
+        Err(String::from("no sound data in the WAV file"))
+    }
+
+    /// Mixed down to one channel and resampled to `rate` with linear interpolation
+    pub fn mono(&self, rate: u32) -> Vec<i16> {
+        let channels = usize::from(self.channels);
+        let mixed: Vec<i32> = self.samples.chunks_exact(channels)
+            .map(|frame| frame.iter().map(|&s| i32::from(s)).sum::<i32>() / channels as i32)
+            .collect();
+        if mixed.is_empty() {
+            return Vec::new();
+        }
+        let len = (mixed.len() as u64 * u64::from(rate) / u64::from(self.rate)) as usize;
+        (0..len).map(|i| {
+            // 源位置的 16 位定点小数
+            let at = i as u64 * u64::from(self.rate) * 65536 / u64::from(rate);
+            let (index, frac) = ((at >> 16) as usize, (at & 0xffff) as i64);
+            let a = i64::from(mixed[index]);
+            let b = i64::from(*mixed.get(index + 1).unwrap_or(&mixed[index]));
+            (a + (b - a) * frac / 65536) as i16
+        }).collect()
+    }
+}
+
+/// 16 bit PCM WAV. The sizes in the header are filled in by `finish`
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    channels: u16,
+    samples: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, rate: u32, channels: u16) -> io::Result<Self> {
+        let block = channels * 2;
+        out.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        // PCM
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&channels.to_le_bytes())?;
+        out.write_all(&rate.to_le_bytes())?;
+        out.write_all(&(rate * u32::from(block)).to_le_bytes())?;
+        out.write_all(&block.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data\0\0\0\0")?;
+        Ok(Self { out, channels, samples: 0 })
+    }
+
+    /// Samples, interleaved when there is more than one channel
+    pub fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        let mut data = Vec::with_capacity(samples.len() * 2);
+        for s in samples {
+            data.extend(&s.to_le_bytes());
+        }
+        self.out.write_all(&data)?;
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    /// Sample frames written so far, one sample per channel each
+    pub fn len(&self) -> u32 {
+        self.samples / u32::from(self.channels)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+
+    /// Fill in the RIFF and data sizes and hand back the output
+    pub fn finish(mut self) -> io::Result<W> {
+        let data = self.samples * 2;
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + data).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&data.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use rust8080::asm::assemble;
+
+const USAGE: &str = "usage: rust8080-asm [--hex] [-o OUT] [--symbols FILE] SOURCE
+Writes a flat binary from the lowest assembled address, or Intel HEX with --hex.
+OUT defaults to SOURCE with a .bin or .hex extension.";
+
+struct Options {
+    source: String,
+    output: Option<String>,
+    symbols: Option<String>,
+    hex: bool,
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1).collect()).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    if let Err(e) = run(&options) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options { source: String::new(), output: None, symbols: None, hex: false };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().cloned().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--hex" => options.hex = true,
+            "-o" => options.output = Some(value()?),
+            "--symbols" => options.symbols = Some(value()?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown argument '{}'", flag)),
+            source if options.source.is_empty() => options.source = String::from(source),
+            _ => return Err(String::from("only one SOURCE")),
+        }
+    }
+    if options.source.is_empty() {
+        return Err(String::from("missing SOURCE"));
+    }
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let source = fs::read_to_string(&options.source).map_err(|e| format!("{}: {}", options.source, e))?;
+    let program = assemble(&source).map_err(|e| format!("{}: {}", options.source, e))?;
+    let extension = if options.hex { "hex" } else { "bin" };
+    let output = options.output.clone().unwrap_or_else(|| {
+        Path::new(&options.source).with_extension(extension).to_string_lossy().into_owned()
+    });
+    let (start, image) = program.to_binary();
+    if options.hex {
+        fs::write(&output, program.to_intel_hex())
+    } else {
+        fs::write(&output, &image)
+    }.map_err(|e| format!("{}: {}", output, e))?;
+    if let Some(symbols) = &options.symbols {
+        fs::write(symbols, program.symbol_file()).map_err(|e| format!("{}: {}", symbols, e))?;
+    }
+    println!("{}: {} bytes at {:04X}H", output, image.len(), start);
+    Ok(())
+}
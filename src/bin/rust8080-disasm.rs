@@ -0,0 +1,108 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use rust8080::disasm::{decode_bytes, hex, Symbols};
+use rust8080::util::parse_hex;
+
+const USAGE: &str = "usage: rust8080-disasm [--org ADDR] [--symbols FILE]... [--from ADDR] [--to ADDR] [FILE...]
+FILEs are loaded back to back at ORG (default 0), the Invaders ROMs in ./res if none are given.
+Symbol files hold 'ADDR NAME' or 'NAME EQU ADDR' lines, addresses are hex.";
+
+const INVADERS_ROMS: [&str; 4] = ["res/invaders.h", "res/invaders.g", "res/invaders.f", "res/invaders.e"];
+
+struct Options {
+    org: u16,
+    from: Option<u16>,
+    to: Option<u16>,
+    symbols: Symbols,
+    files: Vec<String>,
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1).collect()).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    let mut image = Vec::new();
+    for file in &options.files {
+        match fs::read(file) {
+            Ok(bytes) => image.extend(bytes),
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                process::exit(1);
+            }
+        }
+    }
+    if image.len() + usize::from(options.org) > 0x10000 {
+        eprintln!("the image does not fit below 10000H");
+        process::exit(1);
+    }
+    print!("{}", listing(&image, &options));
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options { org: 0, from: None, to: None, symbols: Symbols::new(), files: Vec::new() };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--org" => options.org = parse_hex(value()?)?,
+            "--from" => options.from = Some(parse_hex(value()?)?),
+            "--to" => options.to = Some(parse_hex(value()?)?),
+            "--symbols" => options.symbols.load(value()?).map_err(|e| e.to_string())?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown argument '{}'", flag)),
+            file => options.files.push(String::from(file)),
+        }
+    }
+    if options.files.is_empty() {
+        options.files = INVADERS_ROMS.iter().map(|f| String::from(*f)).collect();
+    }
+    if let Some(to) = options.to {
+        if to < options.org {
+            return Err(String::from("--to is below --org"));
+        }
+        if options.from.is_some_and(|from| from > to) {
+            return Err(String::from("--from is after --to"));
+        }
+    }
+    Ok(options)
+}
+
+/// One line per instruction: address, bytes, text and cycles, with symbol labels above
+fn listing(image: &[u8], options: &Options) -> String {
+    let org = usize::from(options.org);
+    let start = options.from.map_or(org, usize::from).max(org) - org;
+    // 范围在镜像外时列表为空
+    let end = options.to.map_or(org + image.len(), |to| usize::from(to) + 1).clamp(org, org + image.len()) - org;
+    let mut out = String::new();
+    let mut at = start;
+    while at < end {
+        let addr = (org + at) as u16;
+        if let Some(name) = options.symbols.get(addr) {
+            out.push_str(&format!("{}:\n", name));
+        }
+        let (len, text) = match decode_bytes(&image[at..]) {
+            Some(instruction) => {
+                let mut text = format!("{:<18}; {}", instruction.format(&options.symbols), instruction.cycles);
+                if instruction.cycles_taken != instruction.cycles {
+                    text.push_str(&format!("/{}", instruction.cycles_taken));
+                }
+                if instruction.undocumented {
+                    text.push_str(", undocumented");
+                }
+                (usize::from(instruction.len), text)
+            }
+            // 镜像末尾被截断的指令
+            None => (1, format!("DB {}", hex(format!("{:02X}", image[at])))),
+        };
+        let bytes: Vec<String> = image[at..at + len].iter().map(|b| format!("{:02X}", b)).collect();
+        out.push_str(&format!("{:04X}  {:<9} {}\n", addr, bytes.join(" "), text));
+        at += len;
+    }
+    out
+}
@@ -1,17 +1,31 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::mem;
 use std::rc::Rc;
 
-use crate::cpu::IO;
+use crate::cpu::{CpuError, FaultPolicy, IO};
 use crate::cpu::register::Register;
 use crate::memory::address::AddressBus;
-use crate::util::U16Util;
+use crate::util::{Snapshot, StateError, StateReader, StateWriter, U16Util};
 
 /// Abstraction of Intel 8080
 pub struct Cpu {
     pub register: Register,
     pub addring: Box<dyn AddressBus>,
+    /// INTE
     interrupt: bool,
+    /// EI only takes effect after the next instruction
+    ei_delay: bool,
+    /// The instruction a device put on the data bus, kept until INTE allows it
+    interrupt_pending: Option<u8>,
+    /// Stopped by HLT, only an interrupt wakes the CPU up
+    halted: bool,
+    fault_policy: FaultPolicy,
+    /// The first fault of the current instruction when trapping
+    fault: Cell<Option<CpuError>>,
+    /// Address of the instruction being executed
+    op_pc: u16,
+    /// Cycles of the last instruction, also when it faulted
+    last_cycles: u8,
     io: Rc<RefCell<dyn IO>>,
 }
 
@@ -40,20 +54,69 @@ impl Cpu {
             register,
             addring,
             interrupt: false,
+            ei_delay: false,
+            interrupt_pending: None,
+            halted: false,
+            fault_policy: FaultPolicy::Log,
+            fault: Cell::new(None),
+            op_pc: pc,
+            last_cycles: 0,
             io,
         }
     }
 
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.fault_policy = policy;
+    }
+
+    pub fn fault_policy(&self) -> FaultPolicy {
+        self.fault_policy
+    }
+
+    fn fault(&self, error: CpuError) {
+        match self.fault_policy {
+            FaultPolicy::Ignore => {}
+            FaultPolicy::Log => eprintln!("{}", error),
+            FaultPolicy::Trap => {
+                let first = self.fault.take();
+                self.fault.set(first.or(Some(error)));
+            }
+        }
+    }
+
+    /// 读内存, a faulting read gives 0xFF
+    fn read_mem(&self, addr: u16) -> u8 {
+        self.addring.read(addr).unwrap_or_else(|error| {
+            self.fault(CpuError::Bus { pc: self.op_pc, error });
+            0xff
+        })
+    }
+
+    /// 写内存, a faulting write is dropped
+    fn write_mem(&mut self, addr: u16, val: u8) {
+        if let Err(error) = self.addring.write(addr, val) {
+            self.fault(CpuError::Bus { pc: self.op_pc, error });
+        }
+    }
+
+    fn read_word(&self, addr: u16) -> u16 {
+        U16Util::from_le_bytes(self.read_mem(addr), self.read_mem(addr.wrapping_add(1)))
+    }
+
+    fn write_word(&mut self, addr: u16, value: u16) {
+        self.write_mem(addr, value as u8);
+        self.write_mem(addr.wrapping_add(1), (value >> 8) as u8);
+    }
+
     fn get_next_byte(&mut self) -> u8 {
-        let byte = self.addring.get_mem(self.register.pc);
-        self.register.pc += 1;
+        let byte = self.read_mem(self.register.pc);
+        self.register.pc = self.register.pc.wrapping_add(1);
         byte
     }
 
     fn get_next_word(&mut self) -> u16 {
-        let addr = self.register.pc;
-        let word = U16Util::from_le_bytes(self.addring.get_mem(addr), self.addring.get_mem(addr + 1));
-        self.register.pc += 2;
+        let word = self.read_word(self.register.pc);
+        self.register.pc = self.register.pc.wrapping_add(2);
         word
     }
 
@@ -73,7 +136,8 @@ impl Cpu {
         self.register.flag_z = new_r == 0;
         self.register.flag_s = (new_r & 0b10000000) != 0;
         self.register.flag_p = new_r.count_ones() % 2 == 0x00;
-        self.register.flag_ac = new_r > 0xf;
+        // 8080 减法是加补码, 低 4 位不借位时 AC 为 1
+        self.register.flag_ac = (new_r & 0x0f) != 0x0f;
         new_r
     }
 
@@ -138,7 +202,7 @@ impl Cpu {
         self.register.flag_s = (new_a & 0b10000000) != 0;
         self.register.flag_p = new_a.count_ones() % 2 == 0x00;
         self.register.flag_cy = old_a < new_a;
-        self.register.flag_ac = (old_a as i8 & 0x0f) - (r as i8 & 0x0f) >= 0x00;
+        self.register.flag_ac = sub_half_carry(old_a, r, 0);
         self.register.a = new_a
     }
 
@@ -153,7 +217,7 @@ impl Cpu {
         self.register.flag_s = (new_a & 0b10000000) != 0;
         self.register.flag_p = new_a.count_ones() % 2 == 0x00;
         self.register.flag_cy = u16::from(old_a) < (u16::from(r) + u16::from(old_cy));
-        self.register.flag_ac = (old_a as i8 & 0x0f) - (r as i8 & 0x0f) - (c as i8) >= 0x00;
+        self.register.flag_ac = sub_half_carry(old_a, r, c);
         self.register.a = new_a;
     }
 
@@ -165,7 +229,8 @@ impl Cpu {
         self.register.flag_s = (new_a & 0b10000000) != 0;
         self.register.flag_p = new_a.count_ones() % 2 == 0x00;
         self.register.flag_cy = false;
-        self.register.flag_ac = ((self.register.a & r) & 0x08) != 0;
+        // AC is the OR of bit 3 of both operands
+        self.register.flag_ac = ((self.register.a | r) & 0x08) != 0;
         self.register.a = new_a;
     }
 
@@ -202,18 +267,18 @@ impl Cpu {
         self.register.flag_s = (new_a & 0b10000000) != 0;
         self.register.flag_p = new_a.count_ones() % 2 == 0x00;
         self.register.flag_cy = old_a < new_a;
-        self.register.flag_ac = ((old_a as i8 & 0x0f) - (r as i8 & 0x0f)) >= 0x00;
+        self.register.flag_ac = sub_half_carry(old_a, r, 0);
     }
 
     /// Add value to Stack
     fn stack_add(&mut self, value: u16) {
         self.register.sp = self.register.sp.wrapping_sub(2);
-        self.addring.set_word(self.register.sp, value);
+        self.write_word(self.register.sp, value);
     }
 
     /// Pop value from Stack
     fn stack_pop(&mut self) -> u16 {
-        let value = self.addring.get_word(self.register.sp);
+        let value = self.read_word(self.register.sp);
         self.register.sp = self.register.sp.wrapping_add(2);
         value
     }
@@ -223,12 +288,11 @@ impl Cpu {
     }
 
     /// 根据跳转判断是否做 JMP 操作
-    fn condition_jmp(&mut self, condition: bool) -> bool {
+    fn condition_jmp(&mut self, condition: bool) {
         let word = self.get_next_word();
         if condition {
             self.register.pc = word;
         }
-        condition
     }
 
     /// 根据跳转判断是否做 CALL 操作
@@ -241,6 +305,14 @@ impl Cpu {
         condition
     }
 
+    /// 根据跳转判断是否做 RET 操作
+    fn condition_ret(&mut self, condition: bool) -> bool {
+        if condition {
+            self.register.pc = self.stack_pop();
+        }
+        condition
+    }
+
     fn call(&mut self) {
         let word = self.get_next_word();
         self.stack_add(self.register.pc);
@@ -253,10 +325,48 @@ impl Cpu {
     }
 
 
-    /// 下一步指令
-    pub fn next(&mut self) -> u8 {
+    /// 下一步指令, a pending interrupt is served instead when INTE allows it.
+    /// Returns the cycles taken, or the fault when the policy is `FaultPolicy::Trap`.
+    pub fn next(&mut self) -> Result<u8, CpuError> {
+        self.op_pc = self.register.pc;
+        let cycles = self.next_cycles();
+        self.last_cycles = cycles;
+        match self.fault.take() {
+            Some(error) => Err(error),
+            None => Ok(cycles),
+        }
+    }
+
+    /// Cycles the last `next` took. A trapped fault still ran its instruction, so the clock
+    /// has to count these when `next` returns an error
+    pub fn last_cycles(&self) -> u8 {
+        self.last_cycles
+    }
+
+    fn next_cycles(&mut self) -> u8 {
+        let accept = self.interrupt && !self.ei_delay;
+        self.ei_delay = false;
+        if accept {
+            if let Some(op_code) = self.interrupt_pending.take() {
+                // 响应中断时 PC 不增加
+                self.interrupt = false;
+                self.halted = false;
+                return self.execute(op_code);
+            }
+        }
+        if self.halted {
+            return HALT_CYCLES;
+        }
         let op_code = self.get_next_byte();
-        let mut ex_cycle: bool = true;
+        self.execute(op_code)
+    }
+
+    fn execute(&mut self, op_code: u8) -> u8 {
+        if UNDOCUMENTED.contains(&op_code) {
+            self.fault(CpuError::UndocumentedOpcode { opcode: op_code, addr: self.op_pc });
+        }
+        // 条件 CALL/RET 成立时多 6 个周期
+        let mut ex_cycle: bool = false;
         match op_code {
             // NOP          1
             0x00 => { /* Nothing */ }
@@ -266,7 +376,7 @@ impl Cpu {
                 self.register.b = self.get_next_byte();
             }
             // STAX B       1                      (BC) <- A
-            0x02 => self.addring.set_mem(self.register.get_bc(), self.register.a),
+            0x02 => self.write_mem(self.register.get_bc(), self.register.a),
             // INX B        1                      BC <- BC+1
             0x03 => self.register.set_bc(self.register.get_bc().wrapping_add(1)),
             // INR B        1    Z, S, P, AC       B <- B+1
@@ -281,14 +391,11 @@ impl Cpu {
                 self.register.flag_cy = (self.register.a & 1) != 0;
             }
             // -
-            0x08 => {
-                eprintln!("未实现 {:#04X}", op_code);
-                /* Nothing */
-            }
+            0x08 => { /* Nothing */ }
             // DAD B        1    CY                HL = HL + BC
             0x09 => self.dad_add(self.register.get_bc()),
             // LDAX B       1                      A <- (BC)
-            0x0a => self.register.a = self.addring.get_mem(self.register.get_bc()),
+            0x0a => self.register.a = self.read_mem(self.register.get_bc()),
             // DCX B        1                      BC = BC-1
             0x0b => self.register.set_bc(self.register.get_bc().wrapping_sub(1)),
             // INR C        1    Z, S, P, AC       C <- C+1
@@ -303,17 +410,14 @@ impl Cpu {
                 self.register.flag_cy = (self.register.a & 0x80) != 0;
             }
             // -
-            0x10 => {
-                eprintln!("未实现 {:#04X}", op_code);
-                /* Nothing */
-            }
+            0x10 => { /* Nothing */ }
             // LXI D,D16    3                      D <- byte 3, E <- byte 2
             0x11 => {
                 let word = self.get_next_word();
                 self.register.set_de(word);
             }
             // STAX D       1                      (DE) <- A
-            0x12 => self.addring.set_mem(self.register.get_de(), self.register.a),
+            0x12 => self.write_mem(self.register.get_de(), self.register.a),
             // INX D        1                      DE <- DE + 1
             0x13 => self.register.set_de(self.register.get_de().wrapping_add(1)),
             // INR D        1    Z, S, P, AC       D <- D+1
@@ -333,7 +437,7 @@ impl Cpu {
             // DAD D        1    CY                HL = HL + DE
             0x19 => self.dad_add(self.register.get_de()),
             // LDAX D       1                      A <- (DE)
-            0x1a => self.register.a = self.addring.get_mem(self.register.get_de()),
+            0x1a => self.register.a = self.read_mem(self.register.get_de()),
             // DCX D        1                      DE = DE-1
             0x1b => self.register.set_de(self.register.get_de().wrapping_sub(1)),
             // INR E        1    Z, S, P, AC       E <-E+1
@@ -358,7 +462,7 @@ impl Cpu {
             // SHLD adr     3                      (adr) <-L; (adr+1)<-H
             0x22 => {
                 let addr = self.get_next_word();
-                self.addring.set_word(addr, self.register.get_hl());
+                self.write_word(addr, self.register.get_hl());
             }
             // INX H        1                      HL <- HL + 1
             0x23 => self.register.set_hl(self.register.get_hl().wrapping_add(1)),
@@ -379,7 +483,7 @@ impl Cpu {
             // LHLD adr     3                      L <- (adr); H<-(adr+1)
             0x2a => {
                 let addr = self.get_next_word();
-                let word = self.addring.get_word(addr);
+                let word = self.read_word(addr);
                 self.register.set_hl(word);
             }
             // DCX H        1                      HL = HL-1
@@ -402,26 +506,26 @@ impl Cpu {
             // STA adr      3                      (adr) <- A
             0x32 => {
                 let addr = self.get_next_word();
-                self.addring.set_mem(addr, self.register.a)
+                self.write_mem(addr, self.register.a)
             }
             // INX SP       1                      SP = SP + 1
             0x33 => self.register.sp = self.register.sp.wrapping_add(1),
             // INR M        1    Z, S, P, AC       (HL) <- (HL)+1
             0x34 => {
                 let addr = self.register.get_hl();
-                let new_value = self.inr_add(self.addring.get_mem(addr));
-                self.addring.set_mem(addr, new_value);
+                let new_value = self.inr_add(self.read_mem(addr));
+                self.write_mem(addr, new_value);
             }
             // DCR M        1    Z, S, P, AC       (HL) <- (HL)-1
             0x35 => {
                 let addr = self.register.get_hl();
-                let new_value = self.dcr_sub(self.addring.get_mem(addr));
-                self.addring.set_mem(addr, new_value);
+                let new_value = self.dcr_sub(self.read_mem(addr));
+                self.write_mem(addr, new_value);
             }
             // MVI M,D8     2                      (HL) <- byte 2
             0x36 => {
                 let byte = self.get_next_byte();
-                self.addring.set_mem(self.register.get_hl(), byte);
+                self.write_mem(self.register.get_hl(), byte);
             }
             // STC          1    CY                CY = 1
             0x37 => self.register.flag_cy = true,
@@ -432,7 +536,7 @@ impl Cpu {
             // LDA adr      3                       A <- (adr)
             0x3a => {
                 let addr = self.get_next_word();
-                self.register.a = self.addring.get_mem(addr)
+                self.register.a = self.read_mem(addr)
             }
             // DCX SP       1                       SP = SP-1
             0x3b => self.register.sp = self.register.sp.wrapping_sub(1),
@@ -457,7 +561,7 @@ impl Cpu {
             // MOV B,L      1                       B <- L
             0x45 => self.register.b = self.register.l,
             // MOV B,M      1                       B <- (HL)
-            0x46 => self.register.b = self.addring.get_mem(self.register.get_hl()),
+            0x46 => self.register.b = self.read_mem(self.register.get_hl()),
             // MOV B,A      1                       B <- A
             0x47 => self.register.b = self.register.a,
             // MOV C,B      1                       C <- B
@@ -473,7 +577,7 @@ impl Cpu {
             // MOV C,L      1                       C <- L
             0x4d => self.register.c = self.register.l,
             // MOV C,M      1                       C <- (HL)
-            0x4e => self.register.c = self.addring.get_mem(self.register.get_hl()),
+            0x4e => self.register.c = self.read_mem(self.register.get_hl()),
             // MOV C,A      1                       C <- A
             0x4f => self.register.c = self.register.a,
             // MOV D,B      1                       D <- B
@@ -489,7 +593,7 @@ impl Cpu {
             // MOV D,L      1                       D <- L
             0x55 => self.register.d = self.register.l,
             // MOV D,M      1                       D <- (HL)
-            0x56 => self.register.d = self.addring.get_mem(self.register.get_hl()),
+            0x56 => self.register.d = self.read_mem(self.register.get_hl()),
             // MOV D,A      1                       D <- A
             0x57 => self.register.d = self.register.a,
             // MOV E,B      1                       E <- B
@@ -505,7 +609,7 @@ impl Cpu {
             // MOV E,L      1                       E <- L
             0x5d => self.register.e = self.register.l,
             // MOV E,M      1                       E <- (HL)
-            0x5e => self.register.e = self.addring.get_mem(self.register.get_hl()),
+            0x5e => self.register.e = self.read_mem(self.register.get_hl()),
             // MOV E,A      1                       E <- A
             0x5f => self.register.e = self.register.a,
             // MOV H,B      1                       H <- B
@@ -521,7 +625,7 @@ impl Cpu {
             // MOV H,L      1                       H <- L
             0x65 => self.register.h = self.register.l,
             // MOV H,M      1                       H <- (HL)
-            0x66 => self.register.h = self.addring.get_mem(self.register.get_hl()),
+            0x66 => self.register.h = self.read_mem(self.register.get_hl()),
             // MOV H,A      1                       H <- A
             0x67 => self.register.h = self.register.a,
             // MOV L,B      1                       L <- B
@@ -537,25 +641,25 @@ impl Cpu {
             // MOV L,L      1                       L <- L
             0x6d => { /* Nothing */ }
             // MOV L,M      1                       L <- (HL)
-            0x6e => self.register.l = self.addring.get_mem(self.register.get_hl()),
+            0x6e => self.register.l = self.read_mem(self.register.get_hl()),
             // MOV L,A      1                       L <- A
             0x6f => self.register.l = self.register.a,
             // MOV M,B      1                       (HL) <- B
-            0x70 => self.addring.set_mem(self.register.get_hl(), self.register.b),
+            0x70 => self.write_mem(self.register.get_hl(), self.register.b),
             // MOV M,C      1                       (HL) <- C
-            0x71 => self.addring.set_mem(self.register.get_hl(), self.register.c),
+            0x71 => self.write_mem(self.register.get_hl(), self.register.c),
             // MOV M,D      1                       (HL) <- D
-            0x72 => self.addring.set_mem(self.register.get_hl(), self.register.d),
+            0x72 => self.write_mem(self.register.get_hl(), self.register.d),
             // MOV M,E      1                       (HL) <- E
-            0x73 => self.addring.set_mem(self.register.get_hl(), self.register.e),
+            0x73 => self.write_mem(self.register.get_hl(), self.register.e),
             // MOV M,H      1                       (HL) <- H
-            0x74 => self.addring.set_mem(self.register.get_hl(), self.register.h),
+            0x74 => self.write_mem(self.register.get_hl(), self.register.h),
             // MOV M,L      1                       (HL) <- L
-            0x75 => self.addring.set_mem(self.register.get_hl(), self.register.l),
+            0x75 => self.write_mem(self.register.get_hl(), self.register.l),
             // HLT          1                       special   HALT INSTRUCTION
-            0x76 => ::std::process::exit(0),
+            0x76 => self.halted = true,
             // MOV M,A      1                       (HL) <- A
-            0x77 => self.addring.set_mem(self.register.get_hl(), self.register.a),
+            0x77 => self.write_mem(self.register.get_hl(), self.register.a),
             // MOV A,B      1                       A <- B
             0x78 => self.register.a = self.register.b,
             // MOV A,C      1                       A <- C
@@ -569,7 +673,7 @@ impl Cpu {
             // MOV A,L      1                       A <- L
             0x7d => self.register.a = self.register.l,
             // MOV A,M      1                       A <- (HL)
-            0x7e => self.register.a = self.addring.get_mem(self.register.get_hl()),
+            0x7e => self.register.a = self.read_mem(self.register.get_hl()),
             // MOV A,A      1                       A <- A
             0x7f => { /* Nothing */ }
             // ADD B        1    Z, S, P, CY, AC    A <- A + B
@@ -585,7 +689,7 @@ impl Cpu {
             // ADD L        1    Z, S, P, CY, AC    A <- A + L
             0x85 => self.add(self.register.l),
             // ADD M        1    Z, S, P, CY, AC    A <- A + (HL)
-            0x86 => self.add(self.addring.get_mem(self.register.get_hl())),
+            0x86 => self.add(self.read_mem(self.register.get_hl())),
             // ADD A        1    Z, S, P, CY, AC    A <- A + A
             0x87 => self.add(self.register.a),
             // ADC B        1    Z, S, P, CY, AC    A <- A + B + CY
@@ -601,7 +705,7 @@ impl Cpu {
             // ADC L        1    Z, S, P, CY, AC    A <- A + L + CY
             0x8d => self.adc(self.register.l),
             // ADC M        1    Z, S, P, CY, AC    A <- A + (HL) + CY
-            0x8e => self.adc(self.addring.get_mem(self.register.get_hl())),
+            0x8e => self.adc(self.read_mem(self.register.get_hl())),
             // ADC A        1    Z, S, P, CY, AC    A <- A + A + CY
             0x8f => self.adc(self.register.a),
             // SUB B        1    Z, S, P, CY, AC    A <- A - B
@@ -617,7 +721,7 @@ impl Cpu {
             // SUB L        1    Z, S, P, CY, AC    A <- A - L
             0x95 => self.sub(self.register.l),
             // SUB M        1    Z, S, P, CY, AC    A <- A + (HL)
-            0x96 => self.sub(self.addring.get_mem(self.register.get_hl())),
+            0x96 => self.sub(self.read_mem(self.register.get_hl())),
             // SUB A        1    Z, S, P, CY, AC    A <- A - A
             0x97 => self.sub(self.register.a),
             // SBB B        1    Z, S, P, CY, AC    A <- A - B - CY
@@ -633,7 +737,7 @@ impl Cpu {
             // SBB L        1    Z, S, P, CY, AC    A <- A - L - CY
             0x9d => self.sbb(self.register.l),
             // SBB M        1    Z, S, P, CY, AC    A <- A - (HL) - CY
-            0x9e => self.sbb(self.addring.get_mem(self.register.get_hl())),
+            0x9e => self.sbb(self.read_mem(self.register.get_hl())),
             // SBB A        1    Z, S, P, CY, AC    A <- A - A - CY
             0x9f => self.sbb(self.register.a),
             // ANA B        1    Z, S, P, CY, AC    A <- A & B
@@ -649,7 +753,7 @@ impl Cpu {
             // ANA L        1    Z, S, P, CY, AC    A <- A & L
             0xa5 => self.ana(self.register.l),
             // ANA M        1    Z, S, P, CY, AC    A <- A & (HL)
-            0xa6 => self.ana(self.addring.get_mem(self.register.get_hl())),
+            0xa6 => self.ana(self.read_mem(self.register.get_hl())),
             // ANA A        1    Z, S, P, CY, AC    A <- A & A
             0xa7 => self.ana(self.register.a),
             // XRA B        1    Z, S, P, CY, AC    A <- A ^ B
@@ -665,7 +769,7 @@ impl Cpu {
             // XRA L        1    Z, S, P, CY, AC    A <- A ^ L
             0xad => self.xra(self.register.l),
             // XRA M        1    Z, S, P, CY, AC    A <- A ^ (HL)
-            0xae => self.xra(self.addring.get_mem(self.register.get_hl())),
+            0xae => self.xra(self.read_mem(self.register.get_hl())),
             // XRA A        1    Z, S, P, CY, AC    A <- A ^ A
             0xaf => self.xra(self.register.a),
             // ORA B        1    Z, S, P, CY, AC    A <- A | B
@@ -681,7 +785,7 @@ impl Cpu {
             // ORA L        1    Z, S, P, CY, AC    A <- A | L
             0xb5 => self.ora(self.register.l),
             // ORA M        1    Z, S, P, CY, AC    A <- A | (HL)
-            0xb6 => self.ora(self.addring.get_mem(self.register.get_hl())),
+            0xb6 => self.ora(self.read_mem(self.register.get_hl())),
             // ORA A        1    Z, S, P, CY, AC    A <- A | A
             0xb7 => self.ora(self.register.a),
             // CMP B        1    Z, S, P, CY, AC    A - B
@@ -697,18 +801,18 @@ impl Cpu {
             // CMP L        1    Z, S, P, CY, AC    A - L
             0xbd => self.cmp(self.register.l),
             // CMP M        1    Z, S, P, CY, AC    A - (HL)
-            0xbe => self.cmp(self.addring.get_mem(self.register.get_hl())),
+            0xbe => self.cmp(self.read_mem(self.register.get_hl())),
             // CMP A        1    Z, S, P, CY, AC    A - A
             0xbf => self.cmp(self.register.a),
             // RNZ          1                       if NZ, RET
-            0xc0 => if !self.register.flag_z { self.register.pc = self.stack_pop(); },
+            0xc0 => ex_cycle = self.condition_ret(!self.register.flag_z),
             // POP B        1                       C <- (sp); B <- (sp+1); sp <- sp+2
             0xc1 => {
                 let value = self.stack_pop();
                 self.register.set_bc(value);
             }
             // JNZ adr      3                       if NZ, PC <- adr
-            0xc2 => ex_cycle = self.condition_jmp(!self.register.flag_z),
+            0xc2 => self.condition_jmp(!self.register.flag_z),
             // JMP adr      3                       PC <= adr
             0xc3 => self.jmp(),
             // CNZ adr      3                       if NZ, CALL adr
@@ -723,16 +827,13 @@ impl Cpu {
             // RST 0        1                       CALL $0
             0xc7 => self.rst(op_code),
             // RZ           1                       if Z, RET
-            0xc8 => if self.register.flag_z { self.register.pc = self.stack_pop(); },
+            0xc8 => ex_cycle = self.condition_ret(self.register.flag_z),
             // RET          1                       PC.lo <- (sp); PC.hi<-(sp+1); SP <- SP+2
             0xc9 => self.register.pc = self.stack_pop(),
             // JZ adr       3                       if Z, PC <- adr
-            0xca => ex_cycle = self.condition_jmp(self.register.flag_z),
-            // -
-            0xcb => {
-                eprintln!("未实现 {:#04X}", op_code);
-                // TODO
-            }
+            0xca => self.condition_jmp(self.register.flag_z),
+            // - 0xC3
+            0xcb => self.jmp(),
             // CZ adr       3                       if Z, CALL adr
             0xcc => ex_cycle = self.condition_call(self.register.flag_z),
             // CALL adr     3                       (SP-1)<-PC.hi;(SP-2)<-PC.lo;SP<-SP+2;PC=adr
@@ -746,18 +847,19 @@ impl Cpu {
             // RST 1        1                       CALL $8
             0xcf => self.rst(op_code),
             // RNC          1                       if NCY, RET
-            0xd0 => if !self.register.flag_cy { self.register.pc = self.stack_pop(); },
+            0xd0 => ex_cycle = self.condition_ret(!self.register.flag_cy),
             // POP D        1                       E <- (sp); D <- (sp+1); sp <- sp+2
             0xd1 => {
                 let value = self.stack_pop();
                 self.register.set_de(value);
             }
             // JNC adr      3                       if NCY, PC<-adr
-            0xd2 => ex_cycle = self.condition_jmp(!self.register.flag_cy),
+            0xd2 => self.condition_jmp(!self.register.flag_cy),
             // OUT D8       2                       special
             0xd3 => {
-                //eprintln!("未完整实现 {:#04X}", op_code);
-                let _byte = self.get_next_byte();
+                let byte = self.get_next_byte();
+                let io = self.io.clone();
+                io.borrow_mut().output(self, byte);
             }
             // CNC adr      3                       if NCY, CALL adr
             0xd4 => ex_cycle = self.condition_call(!self.register.flag_cy),
@@ -771,11 +873,11 @@ impl Cpu {
             // RST 2        1                       CALL $10
             0xd7 => self.rst(op_code),
             // RC           1                       if CY, RET
-            0xd8 => if self.register.flag_cy { self.register.pc = self.stack_pop(); },
+            0xd8 => ex_cycle = self.condition_ret(self.register.flag_cy),
             // - 0xC9
             0xd9 => self.register.pc = self.stack_pop(),
             // JC adr       3                       if CY, PC<-adr
-            0xda => ex_cycle = self.condition_jmp(self.register.flag_cy),
+            0xda => self.condition_jmp(self.register.flag_cy),
             // IN D8        2                       special
             0xdb => {
                 let byte = self.get_next_byte();
@@ -794,20 +896,20 @@ impl Cpu {
             // RST 3        1                       CALL $18
             0xdf => self.rst(op_code),
             // RPO          1                       if PO, RET
-            0xe0 => if !self.register.flag_p { self.register.pc = self.stack_pop(); },
+            0xe0 => ex_cycle = self.condition_ret(!self.register.flag_p),
             // POP H        1                       L <- (sp); H <- (sp+1); sp <- sp+2
             0xe1 => {
                 let value = self.stack_pop();
                 self.register.set_hl(value);
             }
             // JPO adr      3                       if PO, PC <- adr
-            0xe2 => ex_cycle = self.condition_jmp(!self.register.flag_p),
+            0xe2 => self.condition_jmp(!self.register.flag_p),
             // XTHL         1                       L <-> (SP); H <-> (SP+1)
             0xe3 => {
-                let addr = self.addring.get_word(self.register.sp);
+                let addr = self.read_word(self.register.sp);
                 let hl = self.register.get_hl();
                 self.register.set_hl(addr);
-                self.addring.set_word(self.register.sp, hl);
+                self.write_word(self.register.sp, hl);
             }
             // CPO adr      3                       if PO, CALL adr
             0xe4 => ex_cycle = self.condition_call(!self.register.flag_p),
@@ -821,11 +923,11 @@ impl Cpu {
             // RST 4        1                       CALL $20
             0xe7 => self.rst(op_code),
             // RPE          1                       if PE, RET
-            0xe8 => if self.register.flag_p { self.register.pc = self.stack_pop(); },
+            0xe8 => ex_cycle = self.condition_ret(self.register.flag_p),
             // PCHL         1                       PC.hi <- H; PC.lo <- L
             0xe9 => self.register.pc = self.register.get_hl(),
             // JPE adr      3                       if PE, PC <- adr
-            0xea => ex_cycle = self.condition_jmp(self.register.flag_p),
+            0xea => self.condition_jmp(self.register.flag_p),
             // XCHG         1                       H <-> D; L <-> E
             0xeb => {
                 mem::swap(&mut self.register.h, &mut self.register.d);
@@ -843,7 +945,7 @@ impl Cpu {
             // RST 5        1                       CALL $28
             0xef => self.rst(op_code),
             // RP           1                       if P, RET
-            0xf0 => if !self.register.flag_s { self.register.pc = self.stack_pop(); },
+            0xf0 => ex_cycle = self.condition_ret(!self.register.flag_s),
             // POP PSW      1                       flags <- (sp); A <- (sp+1); sp <- sp+2
             0xf1 => {
                 let value = self.stack_pop();
@@ -851,7 +953,7 @@ impl Cpu {
                 self.register.set_flags((value & 0x00d5 | 0x0002) as u8);
             }
             // JP adr       3                       if P=1 PC <- adr
-            0xf2 => ex_cycle = self.condition_jmp(self.register.flag_s),
+            0xf2 => self.condition_jmp(!self.register.flag_s),
             // DI           1                       special
             0xf3 => self.interrupt = false,
             // CP adr       3                       if P, PC <- adr    Call if  Plus
@@ -868,13 +970,16 @@ impl Cpu {
             // RST 6        1                       CALL $30
             0xf7 => self.rst(op_code),
             // RM           1                       if M, RET
-            0xf8 => if self.register.flag_s { self.register.pc = self.stack_pop(); },
+            0xf8 => ex_cycle = self.condition_ret(self.register.flag_s),
             // SPHL         1                       SP=HL
             0xf9 => self.register.sp = self.register.get_hl(),
             // JM adr       3                       if M, PC <- adr
-            0xfa => ex_cycle = self.condition_jmp(self.register.flag_s),
+            0xfa => self.condition_jmp(self.register.flag_s),
             // EI           1                       special
-            0xfb => self.interrupt = true,
+            0xfb => {
+                self.interrupt = true;
+                self.ei_delay = true;
+            }
             // CM adr       3                       if M, CALL adr   Call If Minus
             0xfc => ex_cycle = self.condition_call(self.register.flag_s),
             // -
@@ -886,7 +991,6 @@ impl Cpu {
             }
             // RST 7        1                       CALL $38
             0xff => self.rst(op_code),
-            n => println!("unknow opcode 0x{:X}", n)
         };
         return if ex_cycle {
             OP_CYCLES[op_code as usize] + 6
@@ -895,20 +999,96 @@ impl Cpu {
         };
     }
 
-    pub fn interrupt(&mut self, code: u8) -> bool {
-        return if self.interrupt {
-            self.rst(code);
-            self.interrupt = false;
-            true
-        } else {
-            false
-        };
+    /// Raise the INT line with the instruction the device will put on the data bus,
+    /// usually an RST. It stays pending until INTE is on and replaces any earlier request.
+    /// Operands of a multi-byte instruction are still read from memory at PC.
+    pub fn interrupt(&mut self, instruction: u8) {
+        self.interrupt_pending = Some(instruction);
+    }
+
+    /// The RESET line: PC goes to 0, INTE and HLT are cleared, registers and memory are kept
+    pub fn reset(&mut self) {
+        self.register.pc = 0;
+        self.interrupt = false;
+        self.ei_delay = false;
+        self.interrupt_pending = None;
+        self.halted = false;
+    }
+
+    /// Drop the pending interrupt without serving it
+    pub fn clear_interrupt(&mut self) {
+        self.interrupt_pending = None;
+    }
+
+    pub fn interrupt_pending(&self) -> Option<u8> {
+        self.interrupt_pending
+    }
+
+    /// Whether the CPU is stopped by HLT. `next` only burns cycles until an interrupt
+    /// is accepted, with INTE off that never happens, so the machine decides what to do.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// INTE, the interrupt enable flip-flop
+    pub fn interrupt_enabled(&self) -> bool {
+        self.interrupt
     }
 }
 
+/// Registers, INTE, a pending EI, HLT and the pending interrupt.
+/// The fault policy is a setting of the host and is not saved
+impl Snapshot for Cpu {
+    fn save(&self, w: &mut StateWriter) {
+        let r = &self.register;
+        for v in [r.a, r.b, r.c, r.d, r.e, r.h, r.l, r.get_flags()].iter() {
+            w.u8(*v);
+        }
+        w.u16(r.pc);
+        w.u16(r.sp);
+        w.bool(self.interrupt);
+        w.bool(self.ei_delay);
+        w.bool(self.halted);
+        w.bool(self.interrupt_pending.is_some());
+        w.u8(self.interrupt_pending.unwrap_or(0));
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let reg = &mut self.register;
+        reg.a = r.u8()?;
+        reg.b = r.u8()?;
+        reg.c = r.u8()?;
+        reg.d = r.u8()?;
+        reg.e = r.u8()?;
+        reg.h = r.u8()?;
+        reg.l = r.u8()?;
+        reg.set_flags(r.u8()?);
+        reg.pc = r.u16()?;
+        reg.sp = r.u16()?;
+        self.interrupt = r.bool()?;
+        self.ei_delay = r.bool()?;
+        self.halted = r.bool()?;
+        let pending = r.bool()?;
+        let op_code = r.u8()?;
+        self.interrupt_pending = if pending { Some(op_code) } else { None };
+        self.op_pc = reg.pc;
+        Ok(())
+    }
+}
+
+/// AC of `a - r - borrow`: the 8080 adds the complement, so AC is the carry out of bit 3 of `a + !r + !borrow`
+fn sub_half_carry(a: u8, r: u8, borrow: u8) -> bool {
+    (a & 0x0f) + (!r & 0x0f) + (1 - borrow) > 0x0f
+}
+
+/// Opcodes missing from the Intel manual, they alias NOP, JMP, RET and CALL
+pub(crate) const UNDOCUMENTED: [u8; 12] = [0x08, 0x10, 0x18, 0x20, 0x28, 0x30, 0x38, 0xcb, 0xd9, 0xdd, 0xed, 0xfd];
+
+/// Cycles a halted CPU idles for on each `next`
+const HALT_CYCLES: u8 = 4;
 
 //  0   1   2   3   4   5   6   7   8   9   a   b   c   d   e   f
-const OP_CYCLES: [u8; 256] = [
+pub(crate) const OP_CYCLES: [u8; 256] = [
     04, 10, 07, 05, 05, 05, 07, 04, 04, 10, 07, 05, 05, 05, 07, 04, // 0
     04, 10, 07, 05, 05, 05, 07, 04, 04, 10, 07, 05, 05, 05, 07, 04, // 1
     04, 10, 16, 05, 05, 05, 07, 04, 04, 10, 16, 05, 05, 05, 07, 04, // 2
@@ -0,0 +1,43 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::memory::BusError;
+
+/// Something went wrong while executing an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CpuError {
+    /// One of the opcodes Intel left undocumented, executed as its usual alias
+    UndocumentedOpcode { opcode: u8, addr: u16 },
+    /// The bus refused a memory access of the instruction at `pc`
+    Bus { pc: u16, error: BusError },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::UndocumentedOpcode { opcode, addr } =>
+                write!(f, "undocumented opcode {:#04X} at {:#06X}", opcode, addr),
+            CpuError::Bus { pc, error } => write!(f, "{} (instruction at {:#06X})", error, pc),
+        }
+    }
+}
+
+impl Error for CpuError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CpuError::Bus { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// What `Cpu::next` does when an instruction faults
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultPolicy {
+    /// Keep running silently, a faulting read gives 0xFF and a faulting write is dropped
+    Ignore,
+    /// Like `Ignore`, but print the fault to stderr
+    Log,
+    /// Finish the instruction as `Ignore` would, then return the fault from `next`
+    Trap,
+}
@@ -1,7 +1,12 @@
+mod error;
 mod io;
+mod testio;
 pub mod cpu;
 pub mod register;
 
 pub use cpu::Cpu;
+pub(crate) use cpu::{OP_CYCLES, UNDOCUMENTED};
+pub use error::{CpuError, FaultPolicy};
 pub use register::Register;
-pub use io::IO;
\ No newline at end of file
+pub use io::IO;
+pub use testio::TestIO;
@@ -0,0 +1,34 @@
+use crate::cpu::{Cpu, IO};
+
+/// IO for tests, every port is a plain byte latch
+pub struct TestIO {
+    /// The value `IN port` loads into A
+    pub input: [u8; 256],
+    /// The last value written by `OUT port`
+    pub output: [u8; 256],
+}
+
+impl IO for TestIO {
+    fn input(&mut self, cpu: &mut Cpu, port: u8) {
+        cpu.register.a = self.input[port as usize];
+    }
+
+    fn output(&mut self, cpu: &mut Cpu, port: u8) {
+        self.output[port as usize] = cpu.register.a;
+    }
+}
+
+impl TestIO {
+    pub fn new() -> Self {
+        Self {
+            input: [0u8; 256],
+            output: [0u8; 256],
+        }
+    }
+}
+
+impl Default for TestIO {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::mem;
+use std::rc::Rc;
+
+use crate::cpu::{Cpu, CpuError};
+use crate::disasm::{decode, is_call};
+use crate::debugger::watch::{NullBus, Watch, WatchBus, WatchHit, WatchState};
+
+/// Something the debugger can single-step: a bare `Cpu` or a whole machine
+pub trait Target {
+    fn cpu(&self) -> &Cpu;
+
+    fn cpu_mut(&mut self) -> &mut Cpu;
+
+    /// Execute one instruction, including whatever the machine does around it
+    fn step(&mut self) -> Result<u8, CpuError>;
+}
+
+impl Target for Cpu {
+    fn cpu(&self) -> &Cpu {
+        self
+    }
+
+    fn cpu_mut(&mut self) -> &mut Cpu {
+        self
+    }
+
+    fn step(&mut self) -> Result<u8, CpuError> {
+        self.next()
+    }
+}
+
+/// Why execution stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// A single instruction was executed
+    Step,
+    Breakpoint(u16),
+    Watch(WatchHit),
+    /// HLT with interrupts disabled, nothing will wake the CPU up
+    Halted,
+    Fault(CpuError),
+    /// The condition of `run_until` became true
+    Done,
+    /// The step limit of `run_until` was reached
+    Limit,
+}
+
+/// PC breakpoints and memory watchpoints on top of a `Cpu`
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watch: Rc<RefCell<WatchState>>,
+}
+
+impl Debugger {
+    /// Wrap the CPU's bus so that its memory accesses can be watched
+    pub fn attach(cpu: &mut Cpu) -> Self {
+        let watch = Rc::new(RefCell::new(WatchState::default()));
+        let bus = mem::replace(&mut cpu.addring, Box::new(NullBus));
+        cpu.addring = Box::new(WatchBus::new(bus, watch.clone()));
+        Self {
+            breakpoints: BTreeSet::new(),
+            watch,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> Vec<u16> {
+        self.breakpoints.iter().copied().collect()
+    }
+
+    pub fn add_watch(&mut self, addr: u16, watch: Watch) {
+        self.watch.borrow_mut().watches.insert(addr, watch);
+    }
+
+    pub fn remove_watch(&mut self, addr: u16) -> bool {
+        self.watch.borrow_mut().watches.remove(&addr).is_some()
+    }
+
+    pub fn watches(&self) -> Vec<(u16, Watch)> {
+        self.watch.borrow().watches.iter().map(|(a, w)| (*a, *w)).collect()
+    }
+
+    /// Execute one instruction
+    pub fn step<T: Target + ?Sized>(&mut self, target: &mut T) -> Stop {
+        self.watch.borrow_mut().hits.clear();
+        if let Err(e) = target.step() {
+            return Stop::Fault(e);
+        }
+        if let Some(hit) = self.watch.borrow().hits.first() {
+            return Stop::Watch(*hit);
+        }
+        let cpu = target.cpu();
+        if cpu.is_halted() && !cpu.interrupt_enabled() {
+            return Stop::Halted;
+        }
+        if self.breakpoints.contains(&cpu.register.pc) {
+            return Stop::Breakpoint(cpu.register.pc);
+        }
+        Stop::Step
+    }
+
+    /// Run until `done` is true, or a breakpoint, watchpoint, fault or dead halt stops it first.
+    /// `limit` caps the number of instructions.
+    pub fn run_until<T, F>(&mut self, target: &mut T, limit: Option<u64>, mut done: F) -> Stop
+        where T: Target + ?Sized, F: FnMut(&T) -> bool {
+        let mut steps = 0u64;
+        loop {
+            match self.step(target) {
+                Stop::Step => {}
+                stop => return stop,
+            }
+            if done(target) {
+                return Stop::Done;
+            }
+            steps += 1;
+            if limit.is_some_and(|l| steps >= l) {
+                return Stop::Limit;
+            }
+        }
+    }
+
+    /// Like `step`, but runs a CALL or RST until it returns to the next instruction
+    pub fn step_over<T: Target + ?Sized>(&mut self, target: &mut T) -> Stop {
+        let cpu = target.cpu();
+        let pc = cpu.register.pc;
+        if !is_call(cpu.addring.get_mem(pc)) {
+            return self.step(target);
+        }
+        let ret = pc.wrapping_add(decode(&*cpu.addring, pc).len);
+        let sp = cpu.register.sp;
+        // SP guards against a recursive call passing the same address
+        self.run_until(target, None, |t| t.cpu().register.pc == ret && t.cpu().register.sp >= sp)
+    }
+}
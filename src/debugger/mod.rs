@@ -0,0 +1,7 @@
+mod debugger;
+mod monitor;
+mod watch;
+
+pub use debugger::{Debugger, Stop, Target};
+pub use monitor::{Monitor, MonitorExit};
+pub use watch::{Watch, WatchBus, WatchHit};
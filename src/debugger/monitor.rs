@@ -0,0 +1,275 @@
+use std::io;
+use std::io::{BufRead, Write};
+
+use crate::cpu::Cpu;
+use crate::debugger::{Debugger, Stop, Target, Watch, WatchHit};
+use crate::disasm::decode;
+use crate::memory::AddressBus;
+use crate::util::parse_hex;
+
+const HELP: &str = "\
+s [N]            step N instructions
+n                step over CALL/RST
+u ADDR           run until PC = ADDR
+c                continue
+b [ADDR]         set a breakpoint, or list them
+bd ADDR          delete a breakpoint
+w ADDR [r|w|rw]  watch memory, or list watchpoints without ADDR
+wd ADDR          delete a watchpoint
+r [REG VALUE]    show registers, or set A B C D E H L SP PC
+m ADDR [LEN]     hexdump memory
+e ADDR BYTE...   edit memory
+d [ADDR] [N]     disassemble, around PC by default
+q                quit
+Numbers are hex.";
+
+/// How the monitor was left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonitorExit {
+    /// Resume the machine
+    Continue,
+    Quit,
+}
+
+/// Line based REPL over a `Debugger`
+pub struct Monitor<R: BufRead, W: Write> {
+    pub debugger: Debugger,
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Monitor<R, W> {
+    pub fn new(debugger: Debugger, input: R, output: W) -> Self {
+        Self {
+            debugger,
+            input,
+            output,
+        }
+    }
+
+    /// Read commands until `c`, `q` or the end of the input
+    pub fn prompt<T: Target + ?Sized>(&mut self, target: &mut T) -> io::Result<MonitorExit> {
+        self.show_pc(target.cpu())?;
+        loop {
+            write!(self.output, "> ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(MonitorExit::Quit);
+            }
+            let args: Vec<&str> = line.split_whitespace().collect();
+            if args.is_empty() {
+                continue;
+            }
+            match self.command(target, &args) {
+                Ok(Some(exit)) => return Ok(exit),
+                Ok(None) => {}
+                Err(e) => writeln!(self.output, "{}", e)?,
+            }
+        }
+    }
+
+    /// Print why the machine stopped
+    pub fn report(&mut self, stop: Stop) -> io::Result<()> {
+        match stop {
+            Stop::Step | Stop::Done => {}
+            Stop::Breakpoint(addr) => writeln!(self.output, "breakpoint {:04X}", addr)?,
+            Stop::Watch(WatchHit::Read { addr, val }) => writeln!(self.output, "read {:04X} = {:02X}", addr, val)?,
+            Stop::Watch(WatchHit::Write { addr, old, val }) =>
+                writeln!(self.output, "write {:04X}: {:02X} -> {:02X}", addr, old, val)?,
+            Stop::Halted => writeln!(self.output, "halted with interrupts disabled")?,
+            Stop::Fault(e) => writeln!(self.output, "fault: {}", e)?,
+            Stop::Limit => writeln!(self.output, "step limit reached")?,
+        }
+        Ok(())
+    }
+
+    fn stopped<T: Target + ?Sized>(&mut self, target: &T, stop: Stop) -> Result<(), String> {
+        self.report(stop).and_then(|_| self.show_pc(target.cpu())).map_err(|e| e.to_string())
+    }
+
+    fn command<T: Target + ?Sized>(&mut self, target: &mut T, args: &[&str]) -> Result<Option<MonitorExit>, String> {
+        let num = |i: usize| -> Result<u16, String> {
+            let arg = args.get(i).ok_or_else(|| format!("{} needs more arguments", args[0]))?;
+            parse_hex(arg)
+        };
+        match args[0] {
+            "s" => {
+                let n = if args.len() > 1 { num(1)? } else { 1 };
+                let mut stop = Stop::Step;
+                for _ in 0..n {
+                    stop = self.debugger.step(target);
+                    if stop != Stop::Step {
+                        break;
+                    }
+                }
+                self.stopped(target, stop)?;
+            }
+            "n" => {
+                let stop = self.debugger.step_over(target);
+                self.stopped(target, stop)?;
+            }
+            "u" => {
+                let addr = num(1)?;
+                let stop = self.debugger.run_until(target, None, |t| t.cpu().register.pc == addr);
+                self.stopped(target, stop)?;
+            }
+            "c" => return Ok(Some(MonitorExit::Continue)),
+            "q" => return Ok(Some(MonitorExit::Quit)),
+            "b" if args.len() > 1 => self.debugger.add_breakpoint(num(1)?),
+            "b" => {
+                let list: Vec<String> = self.debugger.breakpoints().iter().map(|a| format!("{:04X}", a)).collect();
+                self.line(list.join(" "))?;
+            }
+            "bd" => {
+                if !self.debugger.remove_breakpoint(num(1)?) {
+                    return Err(String::from("no such breakpoint"));
+                }
+            }
+            "w" if args.len() > 1 => {
+                let watch = match args.get(2).copied().unwrap_or("rw") {
+                    "r" => Watch::Read,
+                    "w" => Watch::Write,
+                    "rw" => Watch::ReadWrite,
+                    other => return Err(format!("unknown watch kind '{}'", other)),
+                };
+                self.debugger.add_watch(num(1)?, watch);
+            }
+            "w" => {
+                for (addr, watch) in self.debugger.watches() {
+                    self.line(format!("{:04X} {:?}", addr, watch))?;
+                }
+            }
+            "wd" => {
+                if !self.debugger.remove_watch(num(1)?) {
+                    return Err(String::from("no such watchpoint"));
+                }
+            }
+            "r" if args.len() > 1 => set_register(target.cpu_mut(), args[1], num(2)?)?,
+            "r" => {
+                let text = registers(target.cpu());
+                self.line(text)?;
+            }
+            "m" => {
+                let addr = num(1)?;
+                let len = if args.len() > 2 { num(2)? } else { 0x40 };
+                let dump = hexdump(&*target.cpu().addring, addr, len);
+                self.line(dump)?;
+            }
+            "e" => {
+                let addr = num(1)?;
+                for i in 2..args.len() {
+                    let byte = num(i)?;
+                    if byte > 0xff {
+                        return Err(format!("{} is not a byte", args[i]));
+                    }
+                    target.cpu_mut().addring.set_mem(addr.wrapping_add((i - 2) as u16), byte as u8);
+                }
+            }
+            "d" => {
+                let cpu = target.cpu();
+                let addr = if args.len() > 1 { num(1)? } else { context_start(&*cpu.addring, cpu.register.pc) };
+                let count = if args.len() > 2 { num(2)? } else { 10 };
+                let text = listing(&*cpu.addring, addr, count, cpu.register.pc);
+                self.line(text)?;
+            }
+            "h" | "?" | "help" => self.line(String::from(HELP))?,
+            other => return Err(format!("unknown command '{}', h for help", other)),
+        }
+        Ok(None)
+    }
+
+    fn line(&mut self, text: String) -> Result<(), String> {
+        writeln!(self.output, "{}", text).map_err(|e| e.to_string())
+    }
+
+    fn show_pc(&mut self, cpu: &Cpu) -> io::Result<()> {
+        let instruction = decode(&*cpu.addring, cpu.register.pc);
+        writeln!(self.output, "{}\n{:04X}  {}", registers(cpu), cpu.register.pc, instruction)
+    }
+}
+
+fn registers(cpu: &Cpu) -> String {
+    let r = &cpu.register;
+    let flag = |on: bool, name: char| if on { name } else { '-' };
+    format!("PC={:04X} SP={:04X} A={:02X} B={:02X} C={:02X} D={:02X} E={:02X} H={:02X} L={:02X} F={}{}{}{}{}{}{}",
+            r.pc, r.sp, r.a, r.b, r.c, r.d, r.e, r.h, r.l,
+            flag(r.flag_s, 'S'), flag(r.flag_z, 'Z'), flag(r.flag_ac, 'A'), flag(r.flag_p, 'P'), flag(r.flag_cy, 'C'),
+            if cpu.interrupt_enabled() { " INTE" } else { "" },
+            if cpu.is_halted() { " HALT" } else { "" })
+}
+
+fn set_register(cpu: &mut Cpu, name: &str, value: u16) -> Result<(), String> {
+    let r = &mut cpu.register;
+    match name.to_ascii_uppercase().as_str() {
+        "PC" => r.pc = value,
+        "SP" => r.sp = value,
+        "BC" => r.set_bc(value),
+        "DE" => r.set_de(value),
+        "HL" => r.set_hl(value),
+        reg => {
+            if value > 0xff {
+                return Err(format!("{:X} does not fit in {}", value, reg));
+            }
+            let byte = value as u8;
+            match reg {
+                "A" => r.a = byte,
+                "B" => r.b = byte,
+                "C" => r.c = byte,
+                "D" => r.d = byte,
+                "E" => r.e = byte,
+                "H" => r.h = byte,
+                "L" => r.l = byte,
+                "F" => r.set_flags(byte),
+                _ => return Err(format!("unknown register '{}'", name)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn hexdump(bus: &dyn AddressBus, addr: u16, len: u16) -> String {
+    let mut lines = Vec::new();
+    let mut row = addr;
+    let end = u32::from(addr) + u32::from(len);
+    while u32::from(row) < end {
+        let count = (end - u32::from(row)).min(16) as u16;
+        let bytes: Vec<u8> = (0..count).map(|i| bus.get_mem(row.wrapping_add(i))).collect();
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let text: String = bytes.iter().map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' }).collect();
+        lines.push(format!("{:04X}  {:<48} {}", row, hex.join(" "), text));
+        if u32::from(row) + 16 > 0xffff {
+            break;
+        }
+        row += 16;
+    }
+    lines.join("\n")
+}
+
+fn listing(bus: &dyn AddressBus, addr: u16, count: u16, pc: u16) -> String {
+    let mut lines = Vec::new();
+    let mut at = addr;
+    for _ in 0..count {
+        let instruction = decode(bus, at);
+        let len = instruction.len;
+        let bytes: Vec<String> = (0..len).map(|i| format!("{:02X}", bus.get_mem(at.wrapping_add(i)))).collect();
+        lines.push(format!("{} {:04X}  {:<9} {}", if at == pc { '>' } else { ' ' }, at, bytes.join(" "), instruction));
+        at = at.wrapping_add(len);
+    }
+    lines.join("\n")
+}
+
+/// A few instructions before `pc`: the farthest address whose decoding lands exactly on `pc`
+fn context_start(bus: &dyn AddressBus, pc: u16) -> u16 {
+    for back in (1..=9u16).rev() {
+        let start = pc.wrapping_sub(back);
+        let mut at = start;
+        while at.wrapping_sub(start) < back {
+            at = at.wrapping_add(decode(bus, at).len);
+        }
+        if at == pc {
+            return start;
+        }
+    }
+    pc
+}
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::memory::{AddressBus, BusError};
+
+/// Which accesses a watchpoint stops on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Watch {
+    fn on_read(self) -> bool {
+        self != Watch::Write
+    }
+
+    fn on_write(self) -> bool {
+        self != Watch::Read
+    }
+}
+
+/// A watched access made by the CPU
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchHit {
+    Read { addr: u16, val: u8 },
+    Write { addr: u16, old: u8, val: u8 },
+}
+
+/// Watchpoints and the hits not yet reported, shared between the `Debugger` and its `WatchBus`
+#[derive(Default)]
+pub struct WatchState {
+    pub watches: BTreeMap<u16, Watch>,
+    pub hits: Vec<WatchHit>,
+}
+
+/// Wraps the CPU's bus and records watched accesses.
+/// Only `read`/`write`, the path the CPU takes, trigger; `get_mem`/`set_mem` pass straight through
+/// so the monitor can look at memory without tripping its own watchpoints.
+pub struct WatchBus {
+    inner: Box<dyn AddressBus>,
+    state: Rc<RefCell<WatchState>>,
+}
+
+impl WatchBus {
+    pub fn new(inner: Box<dyn AddressBus>, state: Rc<RefCell<WatchState>>) -> Self {
+        Self { inner, state }
+    }
+}
+
+impl AddressBus for WatchBus {
+    fn get_mem(&self, addr: u16) -> u8 {
+        self.inner.get_mem(addr)
+    }
+
+    fn set_mem(&mut self, addr: u16, val: u8) {
+        self.inner.set_mem(addr, val)
+    }
+
+    fn read(&self, addr: u16) -> Result<u8, BusError> {
+        let val = self.inner.read(addr)?;
+        let mut state = self.state.borrow_mut();
+        if state.watches.get(&addr).is_some_and(|w| w.on_read()) {
+            state.hits.push(WatchHit::Read { addr, val });
+        }
+        Ok(val)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) -> Result<(), BusError> {
+        let watched = self.state.borrow().watches.get(&addr).is_some_and(|w| w.on_write());
+        let old = if watched { self.inner.get_mem(addr) } else { 0 };
+        self.inner.write(addr, val)?;
+        if watched {
+            self.state.borrow_mut().hits.push(WatchHit::Write { addr, old, val });
+        }
+        Ok(())
+    }
+}
+
+/// Stands in for the real bus while it is moved into a `WatchBus`
+pub(crate) struct NullBus;
+
+impl AddressBus for NullBus {
+    fn get_mem(&self, _addr: u16) -> u8 {
+        0xff
+    }
+
+    fn set_mem(&mut self, _addr: u16, _val: u8) {}
+}
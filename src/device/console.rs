@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// Ctrl-] leaves the emulator, every other key goes to the guest
+pub const QUIT_KEY: u8 = 0x1d;
+
+/// A character terminal, behind the CP/M BIOS console calls or a serial card
+pub trait Console {
+    /// A key is waiting
+    fn status(&mut self) -> bool;
+
+    /// The next key, waiting for it if needed. `None` ends the session
+    fn read(&mut self) -> Option<u8>;
+
+    fn write(&mut self, c: u8);
+}
+
+/// A console the caller keeps a handle to, e.g. to read what a `BufferConsole` collected
+impl<C: Console> Console for Rc<RefCell<C>> {
+    fn status(&mut self) -> bool {
+        self.borrow_mut().status()
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        self.borrow_mut().read()
+    }
+
+    fn write(&mut self, c: u8) {
+        self.borrow_mut().write(c)
+    }
+}
+
+/// Scripted console for tests: reads from `input` until it runs out, collects the output
+#[derive(Default)]
+pub struct BufferConsole {
+    pub input: VecDeque<u8>,
+    pub output: Vec<u8>,
+}
+
+impl BufferConsole {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.bytes().collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Console for BufferConsole {
+    fn status(&mut self) -> bool {
+        !self.input.is_empty()
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn write(&mut self, c: u8) {
+        self.output.push(c);
+    }
+}
+
+/// The host terminal: stdin is read on a thread so that CONST never blocks.
+/// The terminal is switched to raw mode with `stty` while the console lives
+pub struct TerminalConsole {
+    keys: Receiver<u8>,
+    pending: Option<u8>,
+    /// Empty CONST polls in a row, to idle instead of spinning
+    idle_polls: u32,
+    raw: bool,
+}
+
+impl TerminalConsole {
+    pub fn new() -> Self {
+        let raw = stty(&["raw", "-echo"]);
+        let (tx, keys) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for byte in stdin.lock().bytes() {
+                let byte = match byte {
+                    Ok(byte) => byte,
+                    Err(_) => break,
+                };
+                // 非 raw 模式下回车键给的是 LF
+                let byte = if byte == b'\n' && !raw { b'\r' } else { byte };
+                if tx.send(byte).is_err() || byte == QUIT_KEY {
+                    break;
+                }
+            }
+        });
+        Self {
+            keys,
+            pending: None,
+            idle_polls: 0,
+            raw,
+        }
+    }
+
+    /// The terminal is in raw mode, so typed characters are not echoed by it
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
+
+    fn poll(&mut self) -> bool {
+        if self.pending.is_none() {
+            match self.keys.try_recv() {
+                Ok(key) => self.pending = Some(key),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => {}
+            }
+        }
+        self.pending.is_some()
+    }
+}
+
+impl Default for TerminalConsole {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console for TerminalConsole {
+    fn status(&mut self) -> bool {
+        if self.poll() {
+            self.idle_polls = 0;
+            return true;
+        }
+        self.idle_polls += 1;
+        if self.idle_polls.is_multiple_of(1000) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        false
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        let key = match self.pending.take() {
+            Some(key) => key,
+            None => self.keys.recv().ok()?,
+        };
+        if key == QUIT_KEY {
+            None
+        } else {
+            Some(key)
+        }
+    }
+
+    fn write(&mut self, c: u8) {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let _ = out.write_all(&[c]);
+        let _ = out.flush();
+    }
+}
+
+impl Drop for TerminalConsole {
+    fn drop(&mut self) {
+        if self.raw {
+            stty(&["sane"]);
+        }
+    }
+}
+
+/// A line typed on `console`, without the CR or LF that ends it. Backspace and DEL edit it,
+/// `echo` prints what is typed. `None` when the console closes
+pub fn read_line(console: &mut dyn Console, echo: bool) -> Option<String> {
+    let mut line = String::new();
+    loop {
+        match console.read()? {
+            b'\r' | b'\n' => {
+                if echo {
+                    console.write(b'\r');
+                    console.write(b'\n');
+                }
+                return Some(line);
+            }
+            0x08 | 0x7f => {
+                let erased = line.pop().is_some();
+                if erased && echo {
+                    console.write(0x08);
+                    console.write(b' ');
+                    console.write(0x08);
+                }
+            }
+            c if (0x20..0x7f).contains(&c) => {
+                line.push(c as char);
+                if echo {
+                    console.write(c);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Run `stty` on the controlling terminal, false if that is not possible
+fn stty(args: &[&str]) -> bool {
+    Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::null()).status().is_ok_and(|s| s.success())
+}
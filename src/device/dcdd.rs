@@ -0,0 +1,340 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::cpu::{Cpu, IO};
+
+pub const DCDD_TRACKS: u8 = 77;
+pub const DCDD_SECTORS: u8 = 32;
+/// Bytes of a hard sector as the controller delivers them, sync and checksum included
+pub const DCDD_SECTOR_SIZE: usize = 137;
+/// A full 8" Altair disk image, 337,568 bytes
+pub const DCDD_IMAGE_SIZE: u64 = DCDD_TRACKS as u64 * DCDD_SECTORS as u64 * DCDD_SECTOR_SIZE as u64;
+
+/// OUT selects a drive, IN reads the status
+const SELECT_STATUS: u8 = 0x08;
+/// OUT drives the head, IN reads the sector position
+const CONTROL_SECTOR: u8 = 0x09;
+const DATA: u8 = 0x0a;
+
+const DRIVES: usize = 16;
+/// 360 RPM at 2 MHz is 333,333 cycles a revolution, divided into 32 hard sectors
+const CYCLES_PER_SECTOR: u32 = 10_416;
+/// How long Sector True stays on at the start of a sector, about 30µs
+const SECTOR_TRUE_CYCLES: u32 = 60;
+
+// 状态位, 读端口时取反 (低有效)
+const ENWD: u8 = 0x01;
+const MOVE_HEAD: u8 = 0x02;
+const HEAD_STATUS: u8 = 0x04;
+/// Bits 3 and 4 always read 0
+const UNUSED: u8 = 0x18;
+const INTE: u8 = 0x20;
+const TRACK_0: u8 = 0x40;
+const NRDA: u8 = 0x80;
+
+// 控制端口的命令位
+const STEP_IN: u8 = 0x01;
+const STEP_OUT: u8 = 0x02;
+const HEAD_LOAD: u8 = 0x04;
+const HEAD_UNLOAD: u8 = 0x08;
+const INT_ENABLE: u8 = 0x10;
+const INT_DISABLE: u8 = 0x20;
+const WRITE_ENABLE: u8 = 0x80;
+
+/// A drive with an Altair `.dsk` image: 77 tracks of 32 sectors of 137 bytes, one after the other
+pub struct DcddDrive {
+    file: File,
+    read_only: bool,
+    track: u8,
+    head_loaded: bool,
+}
+
+impl DcddDrive {
+    /// Opened read only when the file cannot be written
+    pub fn open(path: &str) -> io::Result<Self> {
+        match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => Ok(Self::new(file, false)),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(Self::read_only(File::open(path)?)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write protected, writes from the CPU are dropped like with the write protect tab on
+    pub fn read_only(file: File) -> Self {
+        Self::new(file, true)
+    }
+
+    fn new(file: File, read_only: bool) -> Self {
+        Self {
+            file,
+            read_only,
+            track: 0,
+            head_loaded: false,
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn track(&self) -> u8 {
+        self.track
+    }
+
+    fn offset(track: u8, sector: u8) -> u64 {
+        (u64::from(track) * u64::from(DCDD_SECTORS) + u64::from(sector)) * DCDD_SECTOR_SIZE as u64
+    }
+
+    /// Past the end of a short image the sector reads as zeros
+    fn read_sector(&mut self, track: u8, sector: u8, buf: &mut [u8; DCDD_SECTOR_SIZE]) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(Self::offset(track, sector)))?;
+        let mut filled = 0;
+        while filled < DCDD_SECTOR_SIZE {
+            match self.file.read(&mut buf[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        buf[filled..].iter_mut().for_each(|b| *b = 0);
+        Ok(())
+    }
+
+    fn write_sector(&mut self, track: u8, sector: u8, buf: &[u8; DCDD_SECTOR_SIZE]) -> io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        // 短镜像先补齐到写入位置
+        let offset = Self::offset(track, sector);
+        let len = self.file.metadata()?.len();
+        if len < offset {
+            self.file.seek(SeekFrom::Start(len))?;
+            self.file.write_all(&vec![0u8; (offset - len) as usize])?;
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(buf)?;
+        self.file.flush()
+    }
+}
+
+/// The MITS 88-DCDD floppy controller on ports 08H-0AH, for up to 16 drives.
+/// The disk turns with the CPU clock, the machine passes the cycles of each instruction to `tick`.
+/// Data bytes are ready as soon as the CPU asks for them, a written sector goes to the image at once
+pub struct Dcdd {
+    drives: Vec<Option<DcddDrive>>,
+    selected: Option<usize>,
+    sector: u8,
+    /// Cycles since the current sector started
+    position: u32,
+    interrupts: bool,
+    buffer: [u8; DCDD_SECTOR_SIZE],
+    /// The buffer holds the sector under the head
+    loaded: bool,
+    /// Next byte of the buffer to read or write
+    byte: usize,
+    /// Write enabled, until the sector is complete or passes
+    writing: bool,
+}
+
+impl Dcdd {
+    pub fn new() -> Self {
+        Self {
+            drives: (0..DRIVES).map(|_| None).collect(),
+            selected: None,
+            sector: 0,
+            position: 0,
+            interrupts: false,
+            buffer: [0u8; DCDD_SECTOR_SIZE],
+            loaded: false,
+            byte: 0,
+            writing: false,
+        }
+    }
+
+    /// Put a disk in `drive`, returns the one that was there
+    pub fn attach(&mut self, drive: usize, disk: DcddDrive) -> Option<DcddDrive> {
+        self.finish_write();
+        self.drives[drive].replace(disk)
+    }
+
+    pub fn detach(&mut self, drive: usize) -> Option<DcddDrive> {
+        self.finish_write();
+        if self.selected == Some(drive) {
+            self.selected = None;
+        }
+        self.drives[drive].take()
+    }
+
+    pub fn drive(&self, drive: usize) -> Option<&DcddDrive> {
+        self.drives.get(drive).and_then(|d| d.as_ref())
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Turn the disk by `cycles` CPU cycles
+    pub fn tick(&mut self, cycles: u32) {
+        self.position += cycles;
+        while self.position >= CYCLES_PER_SECTOR {
+            self.position -= CYCLES_PER_SECTOR;
+            // 扇区转过去了, 没写完的也要落盘
+            self.finish_write();
+            self.loaded = false;
+            self.sector = (self.sector + 1) % DCDD_SECTORS;
+        }
+    }
+
+    fn current(&mut self) -> Option<&mut DcddDrive> {
+        let drive = self.selected?;
+        self.drives[drive].as_mut()
+    }
+
+    fn select(&mut self, data: u8) {
+        self.finish_write();
+        self.loaded = false;
+        let drive = usize::from(data & 0x0f);
+        // 第 7 位是取消选择
+        self.selected = if data & 0x80 == 0 && self.drives[drive].is_some() { Some(drive) } else { None };
+    }
+
+    fn status(&mut self) -> u8 {
+        let (writing, byte, interrupts) = (self.writing, self.byte, self.interrupts);
+        let drive = match self.current() {
+            Some(drive) => drive,
+            None => return 0xff,
+        };
+        let mut flags = UNUSED | MOVE_HEAD;
+        if drive.track == 0 {
+            flags |= TRACK_0;
+        }
+        if drive.head_loaded {
+            flags |= HEAD_STATUS | NRDA;
+        }
+        if writing && byte < DCDD_SECTOR_SIZE {
+            flags |= ENWD;
+        }
+        if interrupts {
+            flags |= INTE;
+        }
+        !flags
+    }
+
+    fn control(&mut self, data: u8) {
+        if self.current().is_none() {
+            return;
+        }
+        if data & (STEP_IN | STEP_OUT) != 0 {
+            self.finish_write();
+            self.loaded = false;
+        }
+        if data & INT_ENABLE != 0 {
+            self.interrupts = true;
+        }
+        if data & INT_DISABLE != 0 {
+            self.interrupts = false;
+        }
+        let drive = self.current().unwrap();
+        if data & STEP_IN != 0 && drive.track < DCDD_TRACKS - 1 {
+            drive.track += 1;
+        }
+        if data & STEP_OUT != 0 && drive.track > 0 {
+            drive.track -= 1;
+        }
+        if data & HEAD_LOAD != 0 {
+            drive.head_loaded = true;
+        }
+        if data & HEAD_UNLOAD != 0 {
+            drive.head_loaded = false;
+        }
+        if data & WRITE_ENABLE != 0 {
+            self.writing = true;
+            self.loaded = false;
+            self.byte = 0;
+            self.buffer = [0u8; DCDD_SECTOR_SIZE];
+        }
+    }
+
+    /// Bit 0 is Sector True (active low), bits 1-5 the sector number
+    fn sector_position(&mut self) -> u8 {
+        let sector = self.sector;
+        let sector_true = self.position < SECTOR_TRUE_CYCLES;
+        match self.current() {
+            Some(drive) if drive.head_loaded => 0xc0 | sector << 1 | if sector_true { 0 } else { 1 },
+            _ => 0xff,
+        }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        if self.writing || !self.current().is_some_and(|d| d.head_loaded) {
+            return 0xff;
+        }
+        if !self.loaded {
+            let (sector, mut buffer) = (self.sector, [0u8; DCDD_SECTOR_SIZE]);
+            let drive = self.current().unwrap();
+            let track = drive.track;
+            if let Err(e) = drive.read_sector(track, sector, &mut buffer) {
+                eprintln!("88-DCDD read of track {} sector {}: {}", track, sector, e);
+            }
+            self.buffer = buffer;
+            self.loaded = true;
+            self.byte = 0;
+        }
+        let data = self.buffer.get(self.byte).copied().unwrap_or(0);
+        self.byte += 1;
+        data
+    }
+
+    fn write_data(&mut self, data: u8) {
+        if !self.writing || self.byte >= DCDD_SECTOR_SIZE {
+            return;
+        }
+        self.buffer[self.byte] = data;
+        self.byte += 1;
+        if self.byte == DCDD_SECTOR_SIZE {
+            self.finish_write();
+        }
+    }
+
+    /// Write the sector being written to the image
+    fn finish_write(&mut self) {
+        if !self.writing {
+            return;
+        }
+        self.writing = false;
+        let (sector, buffer) = (self.sector, self.buffer);
+        if let Some(drive) = self.current() {
+            let track = drive.track;
+            if let Err(e) = drive.write_sector(track, sector, &buffer) {
+                eprintln!("88-DCDD write of track {} sector {}: {}", track, sector, e);
+            }
+        }
+    }
+}
+
+impl Default for Dcdd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IO for Dcdd {
+    fn input(&mut self, cpu: &mut Cpu, port: u8) {
+        cpu.register.a = match port {
+            SELECT_STATUS => self.status(),
+            CONTROL_SECTOR => self.sector_position(),
+            DATA => self.read_data(),
+            _ => 0xff,
+        };
+    }
+
+    fn output(&mut self, cpu: &mut Cpu, port: u8) {
+        let data = cpu.register.a;
+        match port {
+            SELECT_STATUS => self.select(data),
+            CONTROL_SECTOR => self.control(data),
+            DATA => self.write_data(data),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,9 @@
+mod console;
+mod dcdd;
+mod serial;
+mod tcp;
+
+pub use console::{BufferConsole, Console, QUIT_KEY, read_line, TerminalConsole};
+pub use dcdd::{Dcdd, DCDD_IMAGE_SIZE, DCDD_SECTOR_SIZE, DCDD_SECTORS, DCDD_TRACKS, DcddDrive};
+pub use serial::{BREAK_KEY, Serial};
+pub use tcp::TcpConsole;
@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+
+use crate::device::Console;
+
+/// Ctrl-E on the serial console stops the machine, like the STOP switch
+pub const BREAK_KEY: u8 = 0x05;
+
+/// A serial line to a console. Received characters wait here until the CPU reads them
+pub struct Serial {
+    console: Box<dyn Console>,
+    rx: VecDeque<u8>,
+    /// Last character read, what the data register holds when nothing new came in
+    data: u8,
+    break_requested: bool,
+    closed: bool,
+}
+
+impl Serial {
+    pub fn new(console: Box<dyn Console>) -> Self {
+        Self {
+            console,
+            rx: VecDeque::new(),
+            data: 0,
+            break_requested: false,
+            closed: false,
+        }
+    }
+
+    /// Move what the console has into the receive buffer, catching the break key
+    pub fn poll(&mut self) {
+        while !self.closed && self.console.status() {
+            match self.console.read() {
+                Some(BREAK_KEY) => self.break_requested = true,
+                Some(c) => self.rx.push_back(c),
+                None => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// The console ended the session, e.g. the quit key on the terminal
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Queue characters as if typed, e.g. a paper tape in the reader
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.rx.extend(bytes);
+    }
+
+    pub fn ready(&mut self) -> bool {
+        if self.rx.is_empty() {
+            self.poll();
+        }
+        !self.rx.is_empty()
+    }
+
+    pub fn read(&mut self) -> u8 {
+        if let Some(c) = self.rx.pop_front() {
+            self.data = c;
+        }
+        self.data
+    }
+
+    pub fn write(&mut self, c: u8) {
+        self.console.write(c);
+    }
+
+    /// Whether the break key came in since the last call
+    pub fn take_break(&mut self) -> bool {
+        std::mem::replace(&mut self.break_requested, false)
+    }
+
+    /// 88-SIO status, active low: bit 0 clear when a character is waiting, bit 7 clear when
+    /// the transmitter is free
+    pub fn sio_status(&mut self) -> u8 {
+        if self.ready() { 0x7e } else { 0x7f }
+    }
+
+    /// 88-2SIO (MC6850 ACIA) status: bit 0 receive data register full, bit 1 transmit data register empty
+    pub fn acia_status(&mut self) -> u8 {
+        if self.ready() { 0x03 } else { 0x02 }
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::device::Console;
+
+/// Telnet "interpret as command"
+const IAC: u8 = 0xff;
+/// Telnet commands that take an option byte: WILL, WONT, DO and DONT
+const WILL: u8 = 0xfb;
+const DONT: u8 = 0xfe;
+/// Telnet subnegotiation begin and end
+const SB: u8 = 0xfa;
+const SE: u8 = 0xf0;
+
+/// A write is sent at once unless one was sent within `FLUSH_INTERVAL`, then it waits for
+/// a line end, `FLUSH_SIZE` bytes or the next poll for input
+const FLUSH_SIZE: usize = 1024;
+const FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+/// Output kept for a client that does not read, more is dropped
+const OUTPUT_LIMIT: usize = 1 << 20;
+
+/// Where the telnet parser is in the input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Telnet {
+    Data,
+    /// After IAC
+    Iac,
+    /// After IAC WILL/WONT/DO/DONT, the option byte is next
+    Option,
+    /// Inside IAC SB ... IAC SE
+    Sub,
+    /// After IAC inside a subnegotiation
+    SubIac,
+}
+
+/// A console on a local TCP socket, for `telnet localhost PORT` or `nc`.
+/// One client at a time, a new one is accepted after the last disconnects.
+/// Telnet commands and option negotiation are dropped
+pub struct TcpConsole {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    input: VecDeque<u8>,
+    /// Written but not sent yet
+    output: VecDeque<u8>,
+    /// When a write last sent output
+    sent: Option<Instant>,
+    telnet: Telnet,
+    last_cr: bool,
+}
+
+impl TcpConsole {
+    /// Listen on `addr`, e.g. `127.0.0.1:8800`. Port 0 picks a free one
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            client: None,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            sent: None,
+            telnet: Telnet::Data,
+            last_cr: false,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept a waiting client, send what is waiting and read what it sent, without blocking
+    fn poll(&mut self) {
+        if self.client.is_none() {
+            if let Ok((stream, _)) = self.listener.accept() {
+                if stream.set_nonblocking(true).is_ok() {
+                    self.client = Some(stream);
+                    self.telnet = Telnet::Data;
+                    self.last_cr = false;
+                }
+            }
+        }
+        self.flush();
+        let mut buf = [0u8; 256];
+        while let Some(client) = self.client.as_mut() {
+            match client.read(&mut buf) {
+                Ok(0) => self.disconnect(),
+                Ok(n) => buf[..n].iter().for_each(|b| self.receive(*b)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.disconnect(),
+            }
+        }
+    }
+
+    /// Send as much output as the socket takes, the rest waits for the next call
+    fn flush(&mut self) {
+        while let Some(client) = self.client.as_mut() {
+            let (bytes, _) = self.output.as_slices();
+            if bytes.is_empty() {
+                break;
+            }
+            match client.write(bytes) {
+                Ok(0) => self.disconnect(),
+                Ok(n) => {
+                    self.output.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.disconnect(),
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.output.clear();
+    }
+
+    fn receive(&mut self, b: u8) {
+        self.telnet = match (self.telnet, b) {
+            (Telnet::Data, IAC) => Telnet::Iac,
+            (Telnet::Data, _) => {
+                self.data(b);
+                Telnet::Data
+            }
+            // IAC IAC 是数据里的 0xff
+            (Telnet::Iac, IAC) => {
+                self.data(IAC);
+                Telnet::Data
+            }
+            (Telnet::Iac, WILL..=DONT) => Telnet::Option,
+            (Telnet::Iac, SB) => Telnet::Sub,
+            // NOP, GA 之类的两字节命令
+            (Telnet::Iac, _) => Telnet::Data,
+            (Telnet::Option, _) => Telnet::Data,
+            (Telnet::Sub, IAC) => Telnet::SubIac,
+            (Telnet::Sub, _) => Telnet::Sub,
+            (Telnet::SubIac, SE) => Telnet::Data,
+            (Telnet::SubIac, _) => Telnet::Sub,
+        };
+    }
+
+    fn data(&mut self, b: u8) {
+        // telnet 的回车是 CR LF 或 CR NUL
+        let after_cr = self.last_cr;
+        self.last_cr = b == b'\r';
+        if after_cr && (b == b'\n' || b == 0) {
+            return;
+        }
+        self.input.push_back(b);
+    }
+}
+
+impl Console for TcpConsole {
+    fn status(&mut self) -> bool {
+        self.poll();
+        !self.input.is_empty()
+    }
+
+    /// Nothing to read without a client never ends the session, so this waits for input
+    fn read(&mut self) -> Option<u8> {
+        loop {
+            self.poll();
+            if let Some(b) = self.input.pop_front() {
+                return Some(b);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    fn write(&mut self, c: u8) {
+        if self.client.is_none() || self.output.len() >= OUTPUT_LIMIT {
+            return;
+        }
+        self.output.push_back(c);
+        let idle = self.sent.is_none_or(|sent| sent.elapsed() >= FLUSH_INTERVAL);
+        if c == b'\n' || self.output.len() >= FLUSH_SIZE || idle {
+            self.sent = Some(Instant::now());
+            self.flush();
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use crate::cpu::{OP_CYCLES, UNDOCUMENTED};
+use crate::disasm::Symbols;
+use crate::memory::AddressBus;
+use crate::util::U16Util;
+
+const REGS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "M", "A"];
+const PAIRS: [&str; 4] = ["B", "D", "H", "SP"];
+const ALU: [&str; 8] = ["ADD", "ADC", "SUB", "SBB", "ANA", "XRA", "ORA", "CMP"];
+const ALU_IMM: [&str; 8] = ["ADI", "ACI", "SUI", "SBI", "ANI", "XRI", "ORI", "CPI"];
+
+/// One operand as written in Intel syntax
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// A register, register pair, `M`, `SP` or `PSW`
+    Register(&'static str),
+    Byte(u8),
+    Word(u16),
+    /// The number of an RST
+    Vector(u8),
+}
+
+/// Immediate data following the opcode
+enum Data {
+    None,
+    Byte,
+    Word,
+}
+
+/// A decoded instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub op_code: u8,
+    pub mnemonic: &'static str,
+    pub operands: Vec<Operand>,
+    pub len: u16,
+    pub cycles: u8,
+    /// Cycles of a conditional CALL or RET that is taken, the same as `cycles` for everything else
+    pub cycles_taken: u8,
+    /// An alias of a documented opcode, see `CpuError::UndocumentedOpcode`
+    pub undocumented: bool,
+}
+
+impl Instruction {
+    /// Word operands that have a name in `symbols` are shown by name
+    pub fn format(&self, symbols: &Symbols) -> String {
+        let operands: Vec<String> = self.operands.iter().map(|operand| match *operand {
+            Operand::Register(name) => String::from(name),
+            Operand::Byte(val) => hex(format!("{:02X}", val)),
+            Operand::Word(val) => symbols.get(val).map(String::from).unwrap_or_else(|| hex(format!("{:04X}", val))),
+            Operand::Vector(n) => n.to_string(),
+        }).collect();
+        if operands.is_empty() {
+            String::from(self.mnemonic)
+        } else {
+            format!("{} {}", self.mnemonic, operands.join(","))
+        }
+    }
+
+    /// The word operand of LXI, LDA, JMP, CALL...
+    pub fn word(&self) -> Option<u16> {
+        self.operands.iter().find_map(|operand| match operand {
+            Operand::Word(val) => Some(*val),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(&Symbols::new()))
+    }
+}
+
+/// The instruction at `addr`
+pub fn decode(bus: &dyn AddressBus, addr: u16) -> Instruction {
+    // 总线上每个地址都有值, 不会失败
+    decode_with(|i| Some(bus.get_mem(addr.wrapping_add(i)))).unwrap()
+}
+
+/// The instruction at the start of `bytes`, `None` if it is cut off
+pub fn decode_bytes(bytes: &[u8]) -> Option<Instruction> {
+    decode_with(|i| bytes.get(usize::from(i)).copied())
+}
+
+fn decode_with<F: Fn(u16) -> Option<u8>>(fetch: F) -> Option<Instruction> {
+    let op_code = fetch(0)?;
+    let (mnemonic, mut operands, data) = shape(op_code);
+    let len = match data {
+        Data::None => 1,
+        Data::Byte => {
+            operands.push(Operand::Byte(fetch(1)?));
+            2
+        }
+        Data::Word => {
+            operands.push(Operand::Word(U16Util::from_le_bytes(fetch(1)?, fetch(2)?)));
+            3
+        }
+    };
+    let cycles = OP_CYCLES[op_code as usize];
+    // 条件 CALL/RET 成立时多 6 个周期
+    let conditional = op_code & 0xc7 == 0xc0 || op_code & 0xc7 == 0xc4;
+    Some(Instruction {
+        op_code,
+        mnemonic,
+        operands,
+        len,
+        cycles,
+        cycles_taken: if conditional { cycles + 6 } else { cycles },
+        undocumented: UNDOCUMENTED.contains(&op_code),
+    })
+}
+
+/// Mnemonic, register operands and the kind of immediate data of `op_code`
+fn shape(op_code: u8) -> (&'static str, Vec<Operand>, Data) {
+    let dst = Operand::Register(REGS[((op_code >> 3) & 0x07) as usize]);
+    let src = Operand::Register(REGS[(op_code & 0x07) as usize]);
+    let pair = PAIRS[((op_code >> 4) & 0x03) as usize];
+    let stack_pair = Operand::Register(if pair == "SP" { "PSW" } else { pair });
+    let pair = Operand::Register(pair);
+    let cond = ((op_code >> 3) & 0x07) as usize;
+    match op_code {
+        0x76 => ("HLT", vec![], Data::None),
+        0x40..=0x7f => ("MOV", vec![dst, src], Data::None),
+        0x80..=0xbf => (ALU[cond], vec![src], Data::None),
+        0x00 | 0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => ("NOP", vec![], Data::None),
+        0x01 | 0x11 | 0x21 | 0x31 => ("LXI", vec![pair], Data::Word),
+        0x02 | 0x12 => ("STAX", vec![pair], Data::None),
+        0x0a | 0x1a => ("LDAX", vec![pair], Data::None),
+        0x03 | 0x13 | 0x23 | 0x33 => ("INX", vec![pair], Data::None),
+        0x0b | 0x1b | 0x2b | 0x3b => ("DCX", vec![pair], Data::None),
+        0x09 | 0x19 | 0x29 | 0x39 => ("DAD", vec![pair], Data::None),
+        0x04 | 0x0c | 0x14 | 0x1c | 0x24 | 0x2c | 0x34 | 0x3c => ("INR", vec![dst], Data::None),
+        0x05 | 0x0d | 0x15 | 0x1d | 0x25 | 0x2d | 0x35 | 0x3d => ("DCR", vec![dst], Data::None),
+        0x06 | 0x0e | 0x16 | 0x1e | 0x26 | 0x2e | 0x36 | 0x3e => ("MVI", vec![dst], Data::Byte),
+        0x07 => ("RLC", vec![], Data::None),
+        0x0f => ("RRC", vec![], Data::None),
+        0x17 => ("RAL", vec![], Data::None),
+        0x1f => ("RAR", vec![], Data::None),
+        0x22 => ("SHLD", vec![], Data::Word),
+        0x2a => ("LHLD", vec![], Data::Word),
+        0x27 => ("DAA", vec![], Data::None),
+        0x2f => ("CMA", vec![], Data::None),
+        0x32 => ("STA", vec![], Data::Word),
+        0x3a => ("LDA", vec![], Data::Word),
+        0x37 => ("STC", vec![], Data::None),
+        0x3f => ("CMC", vec![], Data::None),
+        0xc9 | 0xd9 => ("RET", vec![], Data::None),
+        0xc3 | 0xcb => ("JMP", vec![], Data::Word),
+        0xcd | 0xdd | 0xed | 0xfd => ("CALL", vec![], Data::Word),
+        0xc0 | 0xc8 | 0xd0 | 0xd8 | 0xe0 | 0xe8 | 0xf0 | 0xf8 =>
+            (["RNZ", "RZ", "RNC", "RC", "RPO", "RPE", "RP", "RM"][cond], vec![], Data::None),
+        0xc2 | 0xca | 0xd2 | 0xda | 0xe2 | 0xea | 0xf2 | 0xfa =>
+            (["JNZ", "JZ", "JNC", "JC", "JPO", "JPE", "JP", "JM"][cond], vec![], Data::Word),
+        0xc4 | 0xcc | 0xd4 | 0xdc | 0xe4 | 0xec | 0xf4 | 0xfc =>
+            (["CNZ", "CZ", "CNC", "CC", "CPO", "CPE", "CP", "CM"][cond], vec![], Data::Word),
+        0xc1 | 0xd1 | 0xe1 | 0xf1 => ("POP", vec![stack_pair], Data::None),
+        0xc5 | 0xd5 | 0xe5 | 0xf5 => ("PUSH", vec![stack_pair], Data::None),
+        0xc6 | 0xce | 0xd6 | 0xde | 0xe6 | 0xee | 0xf6 | 0xfe => (ALU_IMM[cond], vec![], Data::Byte),
+        0xc7 | 0xcf | 0xd7 | 0xdf | 0xe7 | 0xef | 0xf7 | 0xff => ("RST", vec![Operand::Vector(cond as u8)], Data::None),
+        0xd3 => ("OUT", vec![], Data::Byte),
+        0xdb => ("IN", vec![], Data::Byte),
+        0xe3 => ("XTHL", vec![], Data::None),
+        0xe9 => ("PCHL", vec![], Data::None),
+        0xeb => ("XCHG", vec![], Data::None),
+        0xf9 => ("SPHL", vec![], Data::None),
+        0xf3 => ("DI", vec![], Data::None),
+        0xfb => ("EI", vec![], Data::None),
+    }
+}
+
+/// Intel style hex, a leading digit keeps it from reading as a name: 0FFH
+pub fn hex(digits: String) -> String {
+    if digits.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        format!("0{}H", digits)
+    } else {
+        format!("{}H", digits)
+    }
+}
+
+/// CALL, conditional CALL and RST return to the next instruction
+pub fn is_call(op_code: u8) -> bool {
+    matches!(op_code, 0xcd | 0xdd | 0xed | 0xfd) || op_code & 0xc7 == 0xc4 || op_code & 0xc7 == 0xc7
+}
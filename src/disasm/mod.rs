@@ -0,0 +1,5 @@
+mod instruction;
+mod symbols;
+
+pub use instruction::{decode, decode_bytes, hex, Instruction, is_call, Operand};
+pub use symbols::Symbols;
@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use crate::util::parse_hex;
+
+/// Names for addresses, read from `ADDR NAME` or `NAME EQU ADDR` lines
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, addr: u16, name: &str) {
+        self.names.insert(addr, String::from(name));
+    }
+
+    pub fn get(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(|name| name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(u16, &str)> {
+        self.names.iter().map(|(addr, name)| (*addr, name.as_str()))
+    }
+
+    /// Add the symbols in `text`. Addresses are hex, `;` starts a comment
+    pub fn parse(&mut self, text: &str) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (addr, name) = match words.as_slice() {
+                [] => continue,
+                [name, equ, addr] if equ.eq_ignore_ascii_case("EQU") => (*addr, name.trim_end_matches(':')),
+                [addr, name] => (*addr, *name),
+                _ => return Err(format!("line {}: expected 'ADDR NAME' or 'NAME EQU ADDR'", i + 1)),
+            };
+            let addr = parse_hex(addr).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            self.insert(addr, name);
+        }
+        Ok(())
+    }
+
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        self.parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+    }
+}
@@ -0,0 +1,63 @@
+use crate::cpu::{Cpu, IO};
+use crate::device::{Dcdd, Serial};
+
+/// 88-SIO status and data
+const SIO_STATUS: u8 = 0x00;
+const SIO_DATA: u8 = 0x01;
+/// 88-2SIO port A status/control and data, port B has nothing attached
+const SIO2_A_STATUS: u8 = 0x10;
+const SIO2_A_DATA: u8 = 0x11;
+const SIO2_B_STATUS: u8 = 0x12;
+/// The 88-DCDD floppy controller
+const DCDD_PORTS: std::ops::RangeInclusive<u8> = 0x08..=0x0a;
+/// The front panel sense switches A15-A8
+const SENSE_SWITCHES: u8 = 0xff;
+
+/// The Altair IO ports. The 88-SIO and the 88-2SIO both talk to the same serial line,
+/// so software written for either board finds the console
+pub struct AltairIO {
+    pub serial: Serial,
+    pub dcdd: Dcdd,
+    /// Sense switches A15-A8, what `IN 0FFH` reads
+    pub switches: u8,
+}
+
+impl AltairIO {
+    pub fn new(serial: Serial) -> Self {
+        Self {
+            serial,
+            dcdd: Dcdd::new(),
+            switches: 0,
+        }
+    }
+}
+
+impl IO for AltairIO {
+    fn input(&mut self, cpu: &mut Cpu, port: u8) {
+        if DCDD_PORTS.contains(&port) {
+            return self.dcdd.input(cpu, port);
+        }
+        cpu.register.a = match port {
+            SIO_STATUS => self.serial.sio_status(),
+            SIO_DATA | SIO2_A_DATA => self.serial.read(),
+            SIO2_A_STATUS => self.serial.acia_status(),
+            // B 口没有接终端, 只报告发送空闲
+            SIO2_B_STATUS => 0x02,
+            SENSE_SWITCHES => self.switches,
+            // 没有设备的端口, 总线上是上拉的 1
+            _ => 0xff,
+        };
+    }
+
+    fn output(&mut self, cpu: &mut Cpu, port: u8) {
+        if DCDD_PORTS.contains(&port) {
+            return self.dcdd.output(cpu, port);
+        }
+        match port {
+            // 最高位是奇偶校验位, 终端不需要
+            SIO_DATA | SIO2_A_DATA => self.serial.write(cpu.register.a & 0x7f),
+            // 2SIO 的控制寄存器 (复位, 波特率分频, 中断) 对模拟没有影响
+            _ => {}
+        }
+    }
+}
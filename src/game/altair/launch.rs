@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use crate::device::{Console, DcddDrive, TcpConsole, TerminalConsole};
+use crate::game::altair::{AltairMachine, FrontPanel, load_tape};
+use crate::game::Launch;
+
+/// An Altair 8800 with the front panel on the host terminal and the serial card on
+/// the terminal too, or on a TCP port
+pub struct AltairLaunch {
+    /// RAM from address 0, in bytes
+    pub ram: usize,
+    /// Serve the serial console on this local TCP port instead of the terminal
+    pub tcp: Option<u16>,
+    /// Binary images and their load addresses
+    pub loads: Vec<(String, u16)>,
+    /// MITS checksum paper tapes, PC goes to the start address of the last one
+    pub tapes: Vec<String>,
+    /// 88-DCDD images for drive 0, 1...
+    pub disks: Vec<String>,
+    /// Files queued on the serial input
+    pub feeds: Vec<String>,
+    pub switches: u8,
+    /// Start running from here instead of stopping at the panel
+    pub run: Option<u16>,
+}
+
+impl Launch for AltairLaunch {
+    fn start(&self) {
+        let terminal = Rc::new(RefCell::new(TerminalConsole::new()));
+        let echo = terminal.borrow().is_raw();
+        let serial: Box<dyn Console> = match self.tcp {
+            Some(port) => match TcpConsole::bind(&format!("127.0.0.1:{}", port)) {
+                Ok(tcp) => {
+                    println!("serial console on telnet localhost {}\r", port);
+                    Box::new(tcp)
+                }
+                Err(e) => {
+                    eprintln!("port {}: {}", port, e);
+                    return;
+                }
+            },
+            None => Box::new(terminal.clone()),
+        };
+        let mut machine = AltairMachine::new(self.ram, serial);
+        if let Err(e) = self.load(&mut machine) {
+            eprintln!("{}", e);
+            return;
+        }
+        machine.io.borrow_mut().switches = self.switches;
+        let mut panel = FrontPanel::new(machine, Box::new(terminal), echo);
+        // 串口在 TCP 上时终端只管面板, 运行中也要能停下和退出
+        panel.set_watch(self.tcp.is_some());
+        if let Some(addr) = self.run {
+            panel.machine.cpu.register.pc = addr;
+            if !panel.run() {
+                return;
+            }
+        }
+        panel.prompt();
+    }
+}
+
+impl AltairLaunch {
+    pub fn new(ram: usize) -> Self {
+        Self {
+            ram,
+            tcp: None,
+            loads: Vec::new(),
+            tapes: Vec::new(),
+            disks: Vec::new(),
+            feeds: Vec::new(),
+            switches: 0,
+            run: None,
+        }
+    }
+
+    fn load(&self, machine: &mut AltairMachine) -> Result<(), String> {
+        let read = |path: &String| fs::read(path).map_err(|e| format!("{}: {}", path, e));
+        for (path, addr) in &self.loads {
+            machine.load(*addr, &read(path)?);
+        }
+        for path in &self.tapes {
+            let start = load_tape(&mut *machine.cpu.addring, &read(path)?).map_err(|e| format!("{}: {}", path, e))?;
+            if let Some(start) = start {
+                machine.cpu.register.pc = start;
+            }
+        }
+        for (drive, path) in self.disks.iter().enumerate() {
+            let disk = DcddDrive::open(path).map_err(|e| format!("{}: {}", path, e))?;
+            machine.io.borrow_mut().dcdd.attach(drive, disk);
+        }
+        for path in &self.feeds {
+            machine.io.borrow_mut().serial.feed(&read(path)?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::cpu::{Cpu, CpuError};
+use crate::debugger::Target;
+use crate::device::{BREAK_KEY, Console, Serial};
+use crate::game::altair::AltairIO;
+use crate::memory::Ram;
+
+/// Instructions between polls of the serial line for the break key
+const POLL_INTERVAL: u32 = 4096;
+
+/// Why `AltairMachine::run` returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AltairStop {
+    /// The break key was typed on the serial console, like the STOP switch
+    Break,
+    /// The serial console ended the session
+    Closed,
+    /// HLT with interrupts disabled
+    Halted,
+    Limit,
+}
+
+/// An Altair 8800 with `ram_size` bytes of RAM from address 0 and a serial card on the console
+pub struct AltairMachine {
+    pub cpu: Cpu,
+    pub io: Rc<RefCell<AltairIO>>,
+    ram_size: usize,
+    /// Instructions since the last poll
+    since_poll: u32,
+}
+
+impl AltairMachine {
+    pub fn new(ram_size: usize, console: Box<dyn Console>) -> Self {
+        let io = Rc::new(RefCell::new(AltairIO::new(Serial::new(console))));
+        let cpu = Cpu::new(Box::new(Ram::new(ram_size)), 0, io.clone());
+        Self {
+            cpu,
+            io,
+            ram_size,
+            since_poll: 0,
+        }
+    }
+
+    pub fn ram_size(&self) -> usize {
+        self.ram_size
+    }
+
+    /// Copy `bytes` to `addr`, what falls beyond the RAM is dropped
+    pub fn load(&mut self, addr: u16, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            self.cpu.addring.set_mem(addr.wrapping_add(i as u16), *b);
+        }
+    }
+
+    /// Execute one instruction, the disks turn meanwhile
+    pub fn step(&mut self) -> Result<u8, CpuError> {
+        let result = self.cpu.next();
+        self.io.borrow_mut().dcdd.tick(u32::from(self.cpu.last_cycles()));
+        result
+    }
+
+    /// Run until the break key, a dead halt or `limit` instructions
+    pub fn run(&mut self, limit: Option<u64>) -> Result<AltairStop, CpuError> {
+        self.run_watching(limit, None)
+    }
+
+    /// Like `run`, also stopping on the break key or the end of the session on `panel`,
+    /// for when the serial card is on another console. Other keys typed there are dropped
+    pub fn run_watching(&mut self, limit: Option<u64>, mut panel: Option<&mut dyn Console>) -> Result<AltairStop, CpuError> {
+        let mut steps = 0u64;
+        loop {
+            if limit.is_some_and(|l| steps >= l) {
+                return Ok(AltairStop::Limit);
+            }
+            self.step()?;
+            steps += 1;
+            if self.cpu.is_halted() && !self.cpu.interrupt_enabled() {
+                return Ok(AltairStop::Halted);
+            }
+            self.since_poll += 1;
+            if self.since_poll >= POLL_INTERVAL {
+                self.since_poll = 0;
+                // 程序不读串口时也要能停下来
+                self.io.borrow_mut().serial.poll();
+                if let Some(panel) = panel.as_mut() {
+                    while panel.status() {
+                        match panel.read() {
+                            Some(BREAK_KEY) => return Ok(AltairStop::Break),
+                            Some(_) => {}
+                            None => return Ok(AltairStop::Closed),
+                        }
+                    }
+                }
+            }
+            let mut io = self.io.borrow_mut();
+            if io.serial.take_break() {
+                return Ok(AltairStop::Break);
+            }
+            if io.serial.is_closed() {
+                return Ok(AltairStop::Closed);
+            }
+        }
+    }
+}
+
+impl Target for AltairMachine {
+    fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    fn step(&mut self) -> Result<u8, CpuError> {
+        AltairMachine::step(self)
+    }
+}
@@ -0,0 +1,11 @@
+pub use io::AltairIO;
+pub use launch::AltairLaunch;
+pub use machine::{AltairMachine, AltairStop};
+pub use panel::FrontPanel;
+pub use tape::load_tape;
+
+mod io;
+mod launch;
+mod machine;
+mod panel;
+mod tape;
@@ -0,0 +1,194 @@
+use std::fs;
+
+use crate::device::{BREAK_KEY, Console, read_line};
+use crate::game::altair::{AltairMachine, AltairStop, load_tape};
+use crate::util::parse_hex;
+
+const HELP: &str = "\
+x [ADDR]          examine ADDR, or show the current address again
+xn                examine next
+d BYTE            deposit at the current address
+dn BYTE           deposit next
+sw BYTE           set the sense switches A15-A8
+r                 run from the current address, Ctrl-E stops
+s [N]             single-step N instructions
+reset             reset the CPU, memory is kept
+load FILE [ADDR]  load a binary image at ADDR, 0 by default
+tape FILE         load a MITS checksum paper tape, the address goes to its start
+feed FILE         queue FILE on the serial input as if it was typed
+q                 quit
+Numbers are hex.";
+
+/// The front panel as a line REPL. The address LEDs show PC and the data LEDs the byte under it,
+/// so examine moves PC like on the real panel
+pub struct FrontPanel {
+    pub machine: AltairMachine,
+    console: Box<dyn Console>,
+    /// Echo typed characters, for a terminal in raw mode
+    echo: bool,
+    /// The serial card is on another console, so the stop and quit keys are read here while running
+    watch: bool,
+}
+
+impl FrontPanel {
+    pub fn new(machine: AltairMachine, console: Box<dyn Console>, echo: bool) -> Self {
+        Self {
+            machine,
+            console,
+            echo,
+            watch: false,
+        }
+    }
+
+    /// Read the stop and quit keys from the panel's console while running, for a serial card
+    /// that is not on it
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch;
+    }
+
+    /// Read commands until `q` or the console closes
+    pub fn prompt(&mut self) {
+        self.lights();
+        loop {
+            self.print("* ");
+            let line = match read_line(&mut *self.console, self.echo) {
+                Some(line) => line,
+                None => return,
+            };
+            let args: Vec<&str> = line.split_whitespace().collect();
+            if args.is_empty() {
+                continue;
+            }
+            match self.command(&args) {
+                Ok(true) => self.lights(),
+                Ok(false) => return,
+                Err(e) => self.println(&e),
+            }
+        }
+    }
+
+    /// Run until the STOP key, false if the console closed meanwhile
+    pub fn run(&mut self) -> bool {
+        let stop = if self.watch {
+            self.machine.run_watching(None, Some(&mut *self.console))
+        } else {
+            self.machine.run(None)
+        };
+        self.println("");
+        match stop {
+            Ok(AltairStop::Break) => self.println(&format!("stopped at {:04X}", self.machine.cpu.register.pc)),
+            Ok(AltairStop::Halted) => self.println(&format!("halted at {:04X}", self.machine.cpu.register.pc)),
+            Ok(AltairStop::Closed) => return false,
+            Ok(AltairStop::Limit) => {}
+            Err(e) => self.println(&e.to_string()),
+        }
+        true
+    }
+
+    /// `Ok(false)` leaves the panel
+    fn command(&mut self, args: &[&str]) -> Result<bool, String> {
+        let num = |i: usize| -> Result<u16, String> {
+            let arg = args.get(i).ok_or_else(|| format!("{} needs more arguments", args[0]))?;
+            parse_hex(arg)
+        };
+        let byte = |i: usize| -> Result<u8, String> {
+            let value = num(i)?;
+            if value > 0xff {
+                return Err(format!("{} is not a byte", args[i]));
+            }
+            Ok(value as u8)
+        };
+        let cpu = &mut self.machine.cpu;
+        match args[0] {
+            "x" if args.len() > 1 => cpu.register.pc = num(1)?,
+            "x" => {}
+            "xn" => cpu.register.pc = cpu.register.pc.wrapping_add(1),
+            "d" => {
+                let value = byte(1)?;
+                cpu.addring.set_mem(cpu.register.pc, value);
+            }
+            "dn" => {
+                let value = byte(1)?;
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
+                cpu.addring.set_mem(cpu.register.pc, value);
+            }
+            "sw" => self.machine.io.borrow_mut().switches = byte(1)?,
+            "r" => {
+                self.println(&format!("running, Ctrl-{} stops", (BREAK_KEY + b'@') as char));
+                return Ok(self.run());
+            }
+            "s" => {
+                let n = if args.len() > 1 { num(1)? } else { 1 };
+                for _ in 0..n {
+                    self.machine.step().map_err(|e| e.to_string())?;
+                }
+            }
+            "reset" => cpu.reset(),
+            "load" => {
+                let bytes = read(args.get(1))?;
+                let addr = if args.len() > 2 { num(2)? } else { 0 };
+                self.machine.load(addr, &bytes);
+                self.println(&format!("{} bytes at {:04X}", bytes.len(), addr));
+            }
+            "tape" => {
+                let bytes = read(args.get(1))?;
+                match load_tape(&mut *self.machine.cpu.addring, &bytes)? {
+                    Some(start) => self.machine.cpu.register.pc = start,
+                    None => self.println("the tape has no start address"),
+                }
+            }
+            "feed" => {
+                let bytes = read(args.get(1))?;
+                self.machine.io.borrow_mut().serial.feed(&bytes);
+            }
+            "q" => return Ok(false),
+            "h" | "?" | "help" => self.println(HELP),
+            other => return Err(format!("unknown command '{}', h for help", other)),
+        }
+        Ok(true)
+    }
+
+    /// The address and data LEDs and the status lights that matter here, `*` is lit
+    fn lights(&mut self) {
+        let cpu = &self.machine.cpu;
+        let addr = cpu.register.pc;
+        let data = cpu.addring.get_mem(addr);
+        let text = format!("A {} {:04X}   D {} {:02X}   {}  {}  WAIT",
+                           leds(u32::from(addr), 16), addr, leds(u32::from(data), 8), data,
+                           if cpu.interrupt_enabled() { "INTE" } else { "----" },
+                           if cpu.is_halted() { "HLTA" } else { "----" });
+        self.println(&text);
+    }
+
+    fn print(&mut self, text: &str) {
+        for b in text.bytes() {
+            // 终端是 raw 模式, 换行要带回车
+            if b == b'\n' {
+                self.console.write(b'\r');
+            }
+            self.console.write(b);
+        }
+    }
+
+    fn println(&mut self, text: &str) {
+        self.print(text);
+        self.print("\n");
+    }
+}
+
+/// `bits` LEDs in groups of four, most significant first
+fn leds(value: u32, bits: u32) -> String {
+    let mut text = String::new();
+    for bit in (0..bits).rev() {
+        text.push(if value & (1 << bit) != 0 { '*' } else { '.' });
+        if bit % 4 == 0 && bit > 0 {
+            text.push(' ');
+        }
+    }
+    text
+}
+
+fn read(path: Option<&&str>) -> Result<Vec<u8>, String> {
+    let path = path.ok_or("needs a file")?;
+    fs::read(path).map_err(|e| format!("{}: {}", path, e))
+}
@@ -0,0 +1,60 @@
+use crate::memory::AddressBus;
+
+/// Starts a load record: count, address low, address high, data, checksum
+const LOAD_RECORD: u8 = 0x3c;
+/// Ends the tape: start address low, high
+const EOF_RECORD: u8 = 0x78;
+
+/// Load a MITS checksum-format paper tape, as punched for Altair BASIC, straight into memory.
+/// The bootstrap loader at the head of the tape is skipped: loading starts at the first
+/// record whose checksum is right and that is followed by another record. Returns the start address of the EOF record, if the tape has one
+pub fn load_tape(bus: &mut dyn AddressBus, tape: &[u8]) -> Result<Option<u16>, String> {
+    let mut pos = (0..tape.len())
+        .find(|&i| chained(tape, i))
+        .ok_or("no load records on the tape")?;
+    loop {
+        match tape.get(pos) {
+            Some(&LOAD_RECORD) => {
+                let (addr, data) = record(tape, pos)
+                    .ok_or(format!("bad checksum in the record at tape offset {}", pos))?;
+                for (i, b) in data.iter().enumerate() {
+                    bus.set_mem(addr.wrapping_add(i as u16), *b);
+                }
+                pos += data.len() + 5;
+            }
+            Some(&EOF_RECORD) => {
+                let start = tape.get(pos + 1..pos + 3).ok_or("EOF record cut short")?;
+                return Ok(Some(u16::from_le_bytes([start[0], start[1]])));
+            }
+            // 记录之间可以有空带
+            Some(0) => pos += 1,
+            Some(b) => return Err(format!("unknown record type {:02X}H at tape offset {}", b, pos)),
+            None => return Ok(None),
+        }
+    }
+}
+
+/// A good load record at `pos` followed by another one or the EOF record,
+/// unlikely to happen by chance inside the bootstrap loader
+fn chained(tape: &[u8], pos: usize) -> bool {
+    record(tape, pos).is_some_and(|(_, data)| {
+        let next = pos + data.len() + 5;
+        tape.get(next) == Some(&EOF_RECORD) || record(tape, next).is_some()
+    })
+}
+
+/// The address and data of a load record at `pos` whose checksum is right
+fn record(tape: &[u8], pos: usize) -> Option<(u16, &[u8])> {
+    if tape.get(pos) != Some(&LOAD_RECORD) {
+        return None;
+    }
+    let count = *tape.get(pos + 1)? as usize;
+    let body = tape.get(pos + 2..pos + 4 + count)?;
+    let checksum = *tape.get(pos + 4 + count)?;
+    // 校验和是地址和数据的累加, 不含长度
+    let sum = body.iter().fold(0u8, |s, b| s.wrapping_add(*b));
+    if count == 0 || sum != checksum {
+        return None;
+    }
+    Some((u16::from_le_bytes([body[0], body[1]]), &body[2..]))
+}
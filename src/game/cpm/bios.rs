@@ -0,0 +1,227 @@
+use std::io;
+
+use crate::cpu::Cpu;
+use crate::device::Console;
+use crate::game::cpm::{Disk, SECTOR_SIZE};
+
+/// CCP and BDOS together, as they sit on the system tracks after the boot sector
+pub const SYSTEM_SIZE: u16 = 0x1600;
+/// BDOS entry relative to the CCP
+const BDOS_ENTRY: u16 = 0x0806;
+const FUNCTIONS: u16 = 17;
+/// Offset from the BIOS base of the RETs the jump table points at, one per function
+const TRAPS: u16 = 0x40;
+/// Offset from the BIOS base of the disk tables
+const TABLES: u16 = 0x60;
+const IOBYTE: u16 = 0x0003;
+const CURRENT_DRIVE: u16 = 0x0004;
+const DEFAULT_DMA: u16 = 0x0080;
+
+/// What the machine does after a BIOS call
+pub enum BiosReturn {
+    /// Execute the RET at the trap address
+    Return,
+    /// The BIOS set PC itself (boot)
+    Jump,
+    /// CONIN has nothing to read and never will
+    ConsoleClosed,
+}
+
+/// A CP/M 2.2 BIOS served from Rust: the jump table points at RETs and the machine traps
+/// on them before they execute
+pub struct Bios {
+    ccp: u16,
+    base: u16,
+    console: Box<dyn Console>,
+    disks: Vec<Disk>,
+    skew: Vec<Vec<u8>>,
+    /// Address of the disk parameter header of each drive
+    dph: Vec<u16>,
+    drive: usize,
+    track: u16,
+    sector: u16,
+    dma: u16,
+}
+
+impl Bios {
+    /// A BIOS right above a CCP at `ccp`. Fails if its tables do not fit below 64K
+    pub fn new(console: Box<dyn Console>, disks: Vec<Disk>, ccp: u16) -> Result<Self, String> {
+        if disks.is_empty() || disks.len() > 16 {
+            return Err(String::from("CP/M needs 1 to 16 drives"));
+        }
+        let base = u32::from(ccp) + u32::from(SYSTEM_SIZE);
+        let tables: u32 = disks.iter().map(|d| {
+            16 + 15 + u32::from(d.format.sectors_per_track) + u32::from(d.format.csv_size()) + u32::from(d.format.alv_size())
+        }).sum();
+        if base + u32::from(TABLES) + SECTOR_SIZE as u32 + tables > 0x10000 {
+            return Err(format!("the BIOS does not fit above a CCP at {:04X}H", ccp));
+        }
+        let skew = disks.iter().map(|d| d.format.skew_table()).collect();
+        Ok(Self {
+            ccp,
+            base: base as u16,
+            console,
+            disks,
+            skew,
+            dph: Vec::new(),
+            drive: 0,
+            track: 0,
+            sector: 1,
+            dma: DEFAULT_DMA,
+        })
+    }
+
+    /// The BIOS function whose trap is at `pc`
+    pub fn function_at(&self, pc: u16) -> Option<u16> {
+        let trap = pc.wrapping_sub(self.base.wrapping_add(TRAPS));
+        if trap < FUNCTIONS {
+            Some(trap)
+        } else {
+            None
+        }
+    }
+
+    pub fn disk_mut(&mut self, drive: usize) -> Option<&mut Disk> {
+        self.disks.get_mut(drive)
+    }
+
+    /// Cold boot: build the jump table and disk tables, then start CP/M
+    pub fn cold_boot(&mut self, cpu: &mut Cpu) -> io::Result<()> {
+        let bus = &mut cpu.addring;
+        for f in 0..FUNCTIONS {
+            let entry = self.base + f * 3;
+            let trap = self.base + TRAPS + f;
+            bus.set_mem(entry, 0xc3);
+            bus.set_word(entry + 1, trap);
+            bus.set_mem(trap, 0xc9);
+        }
+        let mut next = self.base + TABLES;
+        let mut alloc = |size: u16| {
+            let addr = next;
+            next += size;
+            addr
+        };
+        let dirbuf = alloc(SECTOR_SIZE as u16);
+        self.dph.clear();
+        for (drive, disk) in self.disks.iter().enumerate() {
+            let format = disk.format;
+            let dph = alloc(16);
+            let dpb = alloc(15);
+            let xlt = alloc(format.sectors_per_track);
+            let csv = alloc(format.csv_size());
+            let alv = alloc(format.alv_size());
+            for (i, b) in format.dpb().iter().enumerate() {
+                bus.set_mem(dpb + i as u16, *b);
+            }
+            for (i, b) in self.skew[drive].iter().enumerate() {
+                bus.set_mem(xlt + i as u16, *b);
+            }
+            // XLT, 3 个 BDOS 用的临时字, DIRBUF, DPB, CSV, ALV
+            for (i, word) in [xlt, 0, 0, 0, dirbuf, dpb, csv, alv].iter().enumerate() {
+                bus.set_word(dph + 2 * i as u16, *word);
+            }
+            self.dph.push(dph);
+        }
+        cpu.addring.set_mem(IOBYTE, 0);
+        cpu.addring.set_mem(CURRENT_DRIVE, 0);
+        self.warm_boot(cpu)
+    }
+
+    /// Reload the CCP and BDOS from the system tracks of drive A and jump to the CCP
+    pub fn warm_boot(&mut self, cpu: &mut Cpu) -> io::Result<()> {
+        let spt = self.disks[0].format.sectors_per_track;
+        let mut buf = [0u8; SECTOR_SIZE];
+        // 系统从 0 道 2 扇区开始顺序存放, 1 扇区是冷启动引导程序
+        for i in 0..SYSTEM_SIZE / SECTOR_SIZE as u16 {
+            let (track, sector) = ((i + 1) / spt, (i + 1) % spt + 1);
+            self.disks[0].read_sector(track, sector, &mut buf)?;
+            for (j, b) in buf.iter().enumerate() {
+                cpu.addring.set_mem(self.ccp + i * SECTOR_SIZE as u16 + j as u16, *b);
+            }
+        }
+        let bus = &mut cpu.addring;
+        bus.set_mem(0x0000, 0xc3);
+        bus.set_word(0x0001, self.base + 3);
+        bus.set_mem(0x0005, 0xc3);
+        bus.set_word(0x0006, self.ccp + BDOS_ENTRY);
+        self.dma = DEFAULT_DMA;
+        let drive = bus.get_mem(CURRENT_DRIVE) & 0x0f;
+        cpu.register.c = if usize::from(drive) < self.disks.len() { drive } else { 0 };
+        cpu.register.sp = DEFAULT_DMA;
+        cpu.register.pc = self.ccp;
+        Ok(())
+    }
+
+    pub fn call(&mut self, function: u16, cpu: &mut Cpu) -> io::Result<BiosReturn> {
+        let r = &mut cpu.register;
+        match function {
+            0 => {
+                self.cold_boot(cpu)?;
+                return Ok(BiosReturn::Jump);
+            }
+            1 => {
+                self.warm_boot(cpu)?;
+                return Ok(BiosReturn::Jump);
+            }
+            // CONST
+            2 => r.a = if self.console.status() { 0xff } else { 0x00 },
+            // CONIN
+            3 => match self.console.read() {
+                Some(c) => r.a = c & 0x7f,
+                None => return Ok(BiosReturn::ConsoleClosed),
+            },
+            // CONOUT
+            4 => self.console.write(r.c & 0x7f),
+            // LIST, PUNCH: nothing attached
+            5 | 6 => {}
+            // READER: end of file
+            7 => r.a = 0x1a,
+            // HOME
+            8 => self.track = 0,
+            // SELDSK
+            9 => {
+                let drive = usize::from(r.c);
+                match self.dph.get(drive) {
+                    Some(dph) => {
+                        self.drive = drive;
+                        r.set_hl(*dph);
+                    }
+                    None => r.set_hl(0),
+                }
+            }
+            // SETTRK, SETSEC, SETDMA
+            10 => self.track = r.get_bc(),
+            11 => self.sector = r.get_bc(),
+            12 => self.dma = r.get_bc(),
+            // READ
+            13 => {
+                let mut buf = [0u8; SECTOR_SIZE];
+                let ok = self.disks[self.drive].read_sector(self.track, self.sector, &mut buf).is_ok();
+                if ok {
+                    for (i, b) in buf.iter().enumerate() {
+                        cpu.addring.set_mem(self.dma.wrapping_add(i as u16), *b);
+                    }
+                }
+                cpu.register.a = if ok { 0 } else { 1 };
+            }
+            // WRITE
+            14 => {
+                let mut buf = [0u8; SECTOR_SIZE];
+                for (i, b) in buf.iter_mut().enumerate() {
+                    *b = cpu.addring.get_mem(self.dma.wrapping_add(i as u16));
+                }
+                let ok = self.disks[self.drive].write_sector(self.track, self.sector, &buf).is_ok();
+                cpu.register.a = if ok { 0 } else { 1 };
+            }
+            // LISTST: always ready
+            15 => r.a = 0xff,
+            // SECTRAN
+            _ => {
+                let logical = r.get_bc();
+                let physical = self.skew[self.drive].get(usize::from(logical)).map_or(logical + 1, |s| u16::from(*s));
+                r.set_hl(physical);
+            }
+        }
+        Ok(BiosReturn::Return)
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// CP/M always transfers 128 byte records
+pub const SECTOR_SIZE: usize = 128;
+/// What an unwritten, freshly formatted sector holds
+const FORMATTED: u8 = 0xe5;
+
+/// Geometry and file system parameters of a raw disk image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskFormat {
+    pub tracks: u16,
+    pub sectors_per_track: u16,
+    /// System tracks before the directory
+    pub reserved_tracks: u16,
+    /// Allocation block size, 1024 to 16384
+    pub block_size: u16,
+    pub dir_entries: u16,
+    /// Sector skew, 0 for none
+    pub skew: u16,
+}
+
+impl DiskFormat {
+    /// The standard 8" single sided single density disk of CP/M 2.2 (IBM 3740), 250K
+    pub const SSSD: DiskFormat = DiskFormat {
+        tracks: 77,
+        sectors_per_track: 26,
+        reserved_tracks: 2,
+        block_size: 1024,
+        dir_entries: 64,
+        skew: 6,
+    };
+
+    /// `sssd`, or `TRACKS,SECTORS,RESERVED,BLOCK,DIRS,SKEW` in decimal
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        if spec.eq_ignore_ascii_case("sssd") {
+            return Ok(Self::SSSD);
+        }
+        let fields = spec.split(',').map(|f| f.trim().parse::<u16>())
+            .collect::<Result<Vec<u16>, _>>()
+            .map_err(|_| format!("bad disk format '{}'", spec))?;
+        let format = match fields.as_slice() {
+            &[tracks, sectors_per_track, reserved_tracks, block_size, dir_entries, skew] =>
+                DiskFormat { tracks, sectors_per_track, reserved_tracks, block_size, dir_entries, skew },
+            _ => return Err(format!("disk format '{}' needs 6 fields: tracks,sectors,reserved,block,dirs,skew", spec)),
+        };
+        format.check().map(|_| format)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if ![1024, 2048, 4096, 8192, 16384].contains(&self.block_size) {
+            return Err(format!("block size {} is not 1K to 16K", self.block_size));
+        }
+        if self.sectors_per_track == 0 || self.sectors_per_track > 255 || self.reserved_tracks >= self.tracks {
+            return Err(String::from("bad disk geometry"));
+        }
+        if self.blocks() < 2 || self.blocks() > 0x10000 || self.dir_blocks() > 16 || self.dir_entries == 0
+            || !self.dir_entries.is_multiple_of(4) {
+            return Err(String::from("bad disk size or directory size"));
+        }
+        // CP/M 不允许 1K 的块超过 256 个
+        if self.block_size == 1024 && self.blocks() > 256 {
+            return Err(String::from("1K blocks allow at most 256 blocks"));
+        }
+        Ok(())
+    }
+
+    fn blocks(&self) -> u32 {
+        u32::from(self.tracks - self.reserved_tracks) * u32::from(self.sectors_per_track) * SECTOR_SIZE as u32
+            / u32::from(self.block_size)
+    }
+
+    fn dir_blocks(&self) -> u16 {
+        (u32::from(self.dir_entries) * 32).div_ceil(u32::from(self.block_size)) as u16
+    }
+
+    /// Highest block number, DSM
+    pub fn max_block(&self) -> u16 {
+        (self.blocks() - 1) as u16
+    }
+
+    /// Bytes of the allocation vector
+    pub fn alv_size(&self) -> u16 {
+        self.max_block() / 8 + 1
+    }
+
+    /// Bytes of the directory check vector
+    pub fn csv_size(&self) -> u16 {
+        self.dir_entries / 4
+    }
+
+    /// The disk parameter block the BDOS reads
+    pub fn dpb(&self) -> [u8; 15] {
+        let records = self.block_size / SECTOR_SIZE as u16;
+        let bsh = records.trailing_zeros() as u8;
+        let dsm = self.max_block();
+        let exm = if dsm < 256 { self.block_size / 1024 - 1 } else { self.block_size / 2048 - 1 } as u8;
+        let drm = self.dir_entries - 1;
+        let al = (0xffffu32 << (16 - self.dir_blocks())) as u16;
+        let cks = self.csv_size();
+        let spt = self.sectors_per_track;
+        let off = self.reserved_tracks;
+        [spt as u8, (spt >> 8) as u8, bsh, (records - 1) as u8, exm, dsm as u8, (dsm >> 8) as u8,
+            drm as u8, (drm >> 8) as u8, (al >> 8) as u8, al as u8, cks as u8, (cks >> 8) as u8, off as u8, (off >> 8) as u8]
+    }
+
+    /// Physical sector, 1 based, of each logical sector
+    pub fn skew_table(&self) -> Vec<u8> {
+        let spt = usize::from(self.sectors_per_track);
+        if self.skew == 0 {
+            return (1..=spt).map(|s| s as u8).collect();
+        }
+        let mut used = vec![false; spt];
+        let mut table = Vec::with_capacity(spt);
+        let mut pos = 0;
+        for _ in 0..spt {
+            while used[pos] {
+                pos = (pos + 1) % spt;
+            }
+            used[pos] = true;
+            table.push((pos + 1) as u8);
+            pos = (pos + usize::from(self.skew)) % spt;
+        }
+        table
+    }
+}
+
+/// A raw image, track after track with sectors in physical order. Writes go straight to the file
+pub struct Disk {
+    file: File,
+    pub format: DiskFormat,
+    read_only: bool,
+}
+
+impl Disk {
+    /// Open the image for writing, or read only if the file does not allow it
+    pub fn open(path: &str, format: DiskFormat) -> io::Result<Self> {
+        match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => Ok(Self { file, format, read_only: false }),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(Self::read_only(File::open(path)?, format)),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn read_only(file: File, format: DiskFormat) -> Self {
+        Self { file, format, read_only: true }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn offset(&self, track: u16, sector: u16) -> io::Result<u64> {
+        if track >= self.format.tracks || sector == 0 || sector > self.format.sectors_per_track {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no sector {} on track {}", sector, track)));
+        }
+        let index = u64::from(track) * u64::from(self.format.sectors_per_track) + u64::from(sector - 1);
+        Ok(index * SECTOR_SIZE as u64)
+    }
+
+    /// `sector` is 1 based. Past the end of a short image the disk reads as formatted
+    pub fn read_sector(&mut self, track: u16, sector: u16, buf: &mut [u8; SECTOR_SIZE]) -> io::Result<()> {
+        let offset = self.offset(track, sector)?;
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut filled = 0;
+        while filled < SECTOR_SIZE {
+            match self.file.read(&mut buf[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        buf[filled..].iter_mut().for_each(|b| *b = FORMATTED);
+        Ok(())
+    }
+
+    pub fn write_sector(&mut self, track: u16, sector: u16, buf: &[u8; SECTOR_SIZE]) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the disk is read only"));
+        }
+        let offset = self.offset(track, sector)?;
+        // 短镜像先补齐到写入位置
+        let len = self.file.metadata()?.len();
+        if len < offset {
+            self.file.seek(SeekFrom::Start(len))?;
+            self.file.write_all(&vec![FORMATTED; (offset - len) as usize])?;
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(buf)
+    }
+}
@@ -0,0 +1,48 @@
+use crate::device::TerminalConsole;
+use crate::game::cpm::{CpmExit, CpmMachine, Disk, DiskFormat};
+use crate::game::Launch;
+
+/// CP/M 2.2 on the host terminal, Ctrl-] quits
+pub struct CpmLaunch {
+    /// Image paths for drive A, B... and their formats
+    pub disks: Vec<(String, DiskFormat)>,
+    pub ccp: u16,
+}
+
+impl Launch for CpmLaunch {
+    fn start(&self) {
+        let mut disks = Vec::new();
+        for (path, format) in &self.disks {
+            match Disk::open(path, *format) {
+                Ok(disk) => disks.push(disk),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    return;
+                }
+            }
+        }
+        let mut machine = match CpmMachine::new(Box::new(TerminalConsole::new()), disks, self.ccp) {
+            Ok(machine) => machine,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        if let Err(e) = machine.boot() {
+            eprintln!("boot failed: {}", e);
+            return;
+        }
+        match machine.run(None) {
+            Ok(CpmExit::Halted) => eprintln!("\r\nhalted at {:04X}", machine.cpu.register.pc),
+            Ok(CpmExit::BootFailed) => eprintln!("\r\nwarm boot failed, drive A has no system"),
+            Ok(_) => {}
+            Err(e) => eprintln!("\r\n{}", e),
+        }
+    }
+}
+
+impl CpmLaunch {
+    pub fn new(disks: Vec<(String, DiskFormat)>, ccp: u16) -> Self {
+        Self { disks, ccp }
+    }
+}
@@ -0,0 +1,80 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use crate::cpu::{Cpu, CpuError, TestIO};
+use crate::device::Console;
+use crate::game::cpm::Disk;
+use crate::game::cpm::bios::{Bios, BiosReturn};
+use crate::memory::Ram;
+
+/// CCP address of a 64K CP/M 2.2 system
+pub const CCP_64K: u16 = 0xe400;
+
+/// Why `CpmMachine::run` returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CpmExit {
+    /// CONIN found the console closed
+    ConsoleClosed,
+    /// A boot could not read the system tracks
+    BootFailed,
+    /// HLT with interrupts disabled
+    Halted,
+    Limit,
+}
+
+/// A 64K CP/M 2.2 computer: RAM, the BIOS in Rust and up to 16 disk drives
+pub struct CpmMachine {
+    pub cpu: Cpu,
+    bios: Bios,
+}
+
+impl CpmMachine {
+    /// `ccp` is where the system on drive A expects to run, `CCP_64K` for a 64K system
+    pub fn new(console: Box<dyn Console>, disks: Vec<Disk>, ccp: u16) -> Result<Self, String> {
+        let bios = Bios::new(console, disks, ccp)?;
+        let cpu = Cpu::new(Box::new(Ram::new(0x10000)), 0, Rc::new(RefCell::new(TestIO::new())));
+        Ok(Self { cpu, bios })
+    }
+
+    /// Cold boot from drive A
+    pub fn boot(&mut self) -> io::Result<()> {
+        self.bios.cold_boot(&mut self.cpu)
+    }
+
+    pub fn disk_mut(&mut self, drive: usize) -> Option<&mut Disk> {
+        self.bios.disk_mut(drive)
+    }
+
+    /// Execute one instruction, or a BIOS call when PC is on one of its traps.
+    /// `Some` when the machine cannot go on
+    pub fn step(&mut self) -> Result<Option<CpmExit>, CpuError> {
+        if let Some(function) = self.bios.function_at(self.cpu.register.pc) {
+            match self.bios.call(function, &mut self.cpu) {
+                Ok(BiosReturn::Return) => {}
+                Ok(BiosReturn::Jump) => return Ok(None),
+                Ok(BiosReturn::ConsoleClosed) => return Ok(Some(CpmExit::ConsoleClosed)),
+                Err(_) => return Ok(Some(CpmExit::BootFailed)),
+            }
+        }
+        self.cpu.next()?;
+        if self.cpu.is_halted() && !self.cpu.interrupt_enabled() {
+            return Ok(Some(CpmExit::Halted));
+        }
+        Ok(None)
+    }
+
+    /// Run until the machine stops or `limit` instructions have run
+    pub fn run(&mut self, limit: Option<u64>) -> Result<CpmExit, CpuError> {
+        let mut steps = 0u64;
+        loop {
+            if limit.is_some_and(|l| steps >= l) {
+                return Ok(CpmExit::Limit);
+            }
+            if let Some(exit) = self.step()? {
+                return Ok(exit);
+            }
+            steps += 1;
+        }
+    }
+}
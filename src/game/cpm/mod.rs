@@ -0,0 +1,11 @@
+mod bios;
+mod disk;
+mod launch;
+mod machine;
+mod shim;
+
+pub use bios::SYSTEM_SIZE;
+pub use disk::{Disk, DiskFormat, SECTOR_SIZE};
+pub use launch::CpmLaunch;
+pub use machine::{CCP_64K, CpmExit, CpmMachine};
+pub use shim::{CpmShim, TPA};
@@ -0,0 +1,121 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::cpu::{Cpu, CpuError, TestIO};
+use crate::memory::Ram;
+
+/// Where CP/M loads a .COM file
+pub const TPA: u16 = 0x0100;
+/// `CALL 5` enters BDOS
+const BDOS: u16 = 0x0005;
+/// What the word at 0x0006 reports as the top of the TPA, programs put their stack below it
+const MEMORY_TOP: u16 = 0xfe00;
+
+/// Just enough CP/M for the CPU exercisers: BDOS console output on `CALL 5` and exit on `JMP 0`.
+/// Everything else a real BDOS does is ignored.
+pub struct CpmShim {
+    pub cpu: Cpu,
+    console: Vec<u8>,
+    /// Also print console output to stdout while running
+    echo: bool,
+    exited: bool,
+}
+
+impl CpmShim {
+    pub fn new(com: &[u8]) -> Self {
+        assert!(com.len() <= usize::from(MEMORY_TOP - TPA), "a .COM file must fit in the TPA");
+        let mut mem = Ram::new(0x10000);
+        mem.load(TPA, com);
+        // 5: RET, 6-7: top of the TPA. The BDOS call itself is trapped before the RET
+        mem.load(BDOS, &[0xc9, MEMORY_TOP as u8, (MEMORY_TOP >> 8) as u8]);
+        let mut cpu = Cpu::new(Box::new(mem), TPA, Rc::new(RefCell::new(TestIO::new())));
+        // CCP 调用程序时栈上的返回地址是 0
+        cpu.addring.set_word(MEMORY_TOP - 2, 0x0000);
+        cpu.register.sp = MEMORY_TOP - 2;
+        Self {
+            cpu,
+            console: Vec::new(),
+            echo: false,
+            exited: false,
+        }
+    }
+
+    pub fn from_file(path: &str) -> io::Result<Self> {
+        Ok(Self::new(&fs::read(path)?))
+    }
+
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Everything written through BDOS so far
+    pub fn console(&self) -> String {
+        String::from_utf8_lossy(&self.console).into_owned()
+    }
+
+    /// The program jumped to 0, a warm boot
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Execute one instruction, serving BDOS first if PC is at its entry
+    pub fn step(&mut self) -> Result<u8, CpuError> {
+        match self.cpu.register.pc {
+            0x0000 => {
+                self.exited = true;
+                return Ok(0);
+            }
+            BDOS => self.bdos(),
+            _ => {}
+        }
+        self.cpu.next()
+    }
+
+    /// Run until the program exits or `limit` instructions have run. True if it exited
+    pub fn run(&mut self, limit: Option<u64>) -> Result<bool, CpuError> {
+        let mut steps = 0u64;
+        while !self.exited {
+            if limit.is_some_and(|l| steps >= l) {
+                return Ok(false);
+            }
+            self.step()?;
+            steps += 1;
+        }
+        Ok(true)
+    }
+
+    /// Function 2 prints E, function 9 prints the string at DE up to '$', or all of memory without one
+    fn bdos(&mut self) {
+        let start = self.console.len();
+        match self.cpu.register.c {
+            0x00 => self.exited = true,
+            0x02 => self.console.push(self.cpu.register.e),
+            0x09 => {
+                let from = self.cpu.register.get_de();
+                let mut addr = from;
+                loop {
+                    let c = self.cpu.addring.get_mem(addr);
+                    if c == b'$' {
+                        break;
+                    }
+                    self.console.push(c);
+                    addr = addr.wrapping_add(1);
+                    // 没有 '$' 时转一圈就停
+                    if addr == from {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+        if self.echo {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            let _ = out.write_all(&self.console[start..]);
+            let _ = out.flush();
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::Path;
+
+use crate::game::invaders::{blend, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::image::{Image, read_png};
+
+/// Largest artwork width and height
+pub const MAX_ARTWORK_SIZE: usize = 8192;
+
+/// A rectangle in artwork pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self { x, y, width, height }
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// The cabinet around the picture: the monitor is seen in a half mirror over a lit backdrop,
+/// so the playfield adds its light to the backdrop, and the bezel covers both
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Artwork {
+    width: usize,
+    height: usize,
+    /// Where the playfield goes, stretched to fill it
+    screen: Rect,
+    /// 0RGB, black without a backdrop
+    backdrop: Vec<u32>,
+    /// ARGB, transparent without a bezel
+    bezel: Vec<u32>,
+}
+
+impl Artwork {
+    /// Black artwork, no bezel
+    pub fn new(width: usize, height: usize, screen: Rect) -> Result<Self, String> {
+        if width > MAX_ARTWORK_SIZE || height > MAX_ARTWORK_SIZE {
+            return Err(format!("the artwork {}x{} is larger than {}x{}", width, height, MAX_ARTWORK_SIZE, MAX_ARTWORK_SIZE));
+        }
+        let inside = |start: usize, len: usize, end: usize| start.checked_add(len).is_some_and(|e| e <= end);
+        if screen.width == 0 || screen.height == 0 || !inside(screen.x, screen.width, width)
+            || !inside(screen.y, screen.height, height) {
+            return Err(format!("the screen {}x{} at {},{} is not inside the {}x{} artwork",
+                               screen.width, screen.height, screen.x, screen.y, width, height));
+        }
+        Ok(Self {
+            width,
+            height,
+            screen,
+            backdrop: vec![0; width * height],
+            bezel: vec![0; width * height],
+        })
+    }
+
+    /// Read a layout file, the images in it are relative to the file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+        Self::parse(&text, dir).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// One setting per line, `#` starts a comment:
+    /// `backdrop FILE.png`, `bezel FILE.png`, `screen X Y WIDTH HEIGHT` and `size WIDTH HEIGHT`.
+    /// The screen is required, the size is the backdrop's or else the bezel's when not given
+    pub fn parse(text: &str, dir: &Path) -> Result<Self, String> {
+        let mut backdrop = None;
+        let mut bezel = None;
+        let mut screen = None;
+        let mut size = None;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let error = |what: &str| format!("line {}: {}", n + 1, what);
+            let numbers = |count: usize| -> Result<Vec<usize>, String> {
+                if words.len() != count + 1 {
+                    return Err(error(&format!("{} takes {} numbers", words[0], count)));
+                }
+                words[1..].iter().map(|w| w.parse().map_err(|_| error("bad number"))).collect()
+            };
+            match words[0] {
+                "backdrop" | "bezel" => {
+                    if words.len() != 2 {
+                        return Err(error(&format!("{} takes a PNG file", words[0])));
+                    }
+                    let path = dir.join(words[1]);
+                    let image = read_png(&path.to_string_lossy()).map_err(|e| error(&e))?;
+                    if words[0] == "backdrop" {
+                        backdrop = Some(image);
+                    } else {
+                        bezel = Some(image);
+                    }
+                }
+                "screen" => {
+                    let n = numbers(4)?;
+                    screen = Some(Rect::new(n[0], n[1], n[2], n[3]));
+                }
+                "size" => {
+                    let n = numbers(2)?;
+                    size = Some((n[0], n[1]));
+                }
+                _ => return Err(error("expected backdrop, bezel, screen or size")),
+            }
+        }
+        let screen = screen.ok_or("the layout has no screen")?;
+        let (width, height) = size
+            .or_else(|| backdrop.as_ref().map(|i: &Image| (i.width, i.height)))
+            .or_else(|| bezel.as_ref().map(|i: &Image| (i.width, i.height)))
+            .ok_or("the layout needs a size without a backdrop or a bezel")?;
+        let mut artwork = Self::new(width, height, screen)?;
+        if let Some(image) = &backdrop {
+            artwork.set_backdrop(image);
+        }
+        if let Some(image) = &bezel {
+            artwork.set_bezel(image);
+        }
+        Ok(artwork)
+    }
+
+    /// Drawn from the top left, cut to the artwork, transparent parts are black
+    pub fn set_backdrop(&mut self, image: &Image) {
+        self.backdrop = self.fit(image).into_iter().map(|p| blend(0, p)).collect();
+    }
+
+    /// Drawn from the top left, cut to the artwork
+    pub fn set_bezel(&mut self, image: &Image) {
+        self.bezel = self.fit(image);
+    }
+
+    /// The image at the artwork's size, transparent where it does not reach
+    fn fit(&self, image: &Image) -> Vec<u32> {
+        let mut pixels = vec![0; self.width * self.height];
+        for y in 0..self.height.min(image.height) {
+            for x in 0..self.width.min(image.width) {
+                pixels[y * self.width + x] = image.get(x, y);
+            }
+        }
+        pixels
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn screen(&self) -> Rect {
+        self.screen
+    }
+
+    /// Put the playfield, `SCREEN_WIDTH * SCREEN_HEIGHT` of 0RGB, into `buffer` of the artwork's size
+    pub fn compose(&self, playfield: &[u32], buffer: &mut [u32]) {
+        let screen = self.screen;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y * self.width + x;
+                let mut pixel = self.backdrop[index];
+                if screen.contains(x, y) {
+                    // 最近邻缩放, 屏幕的光叠加在背景上
+                    let sx = (x - screen.x) * SCREEN_WIDTH / screen.width;
+                    let sy = (y - screen.y) * SCREEN_HEIGHT / screen.height;
+                    pixel = add(pixel, playfield[sy * SCREEN_WIDTH + sx]);
+                }
+                buffer[index] = blend(pixel, self.bezel[index]);
+            }
+        }
+    }
+}
+
+/// Each channel added, stopping at full brightness
+fn add(a: u32, b: u32) -> u32 {
+    let channel = |shift: u32| ((a >> shift & 0xff) + (b >> shift & 0xff)).min(0xff);
+    channel(16) << 16 | channel(8) << 8 | channel(0)
+}
@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+
+use crate::audio::{SAMPLE_RATE, WavWriter};
+use crate::game::invaders::FRAME_RATE;
+use crate::image::{GifWriter, Y4mWriter};
+
+/// GIF keeps every other frame: browsers slow down delays under 2/100 s
+const GIF_FRAME_STEP: u64 = 2;
+
+enum Output {
+    Gif {
+        gif: GifWriter<BufWriter<File>>,
+        /// The last frame kept and the frame it was shown at, written once it is known how long it stays
+        pending: Option<(Vec<u32>, u64)>,
+    },
+    Y4m {
+        video: Y4mWriter<BufWriter<File>>,
+        audio: WavWriter<BufWriter<File>>,
+    },
+}
+
+/// Records every emulated frame to a file. Timing comes from the frame count, never the wall clock,
+/// so the same input gives the same file
+pub struct Recorder {
+    output: Output,
+    width: usize,
+    height: usize,
+    frames: u64,
+}
+
+impl Recorder {
+    /// `.gif` for an animated GIF, `.y4m` for video with the sound next to it in a `.wav`
+    pub fn create(path: &str, width: usize, height: usize) -> io::Result<Self> {
+        let lower = path.to_ascii_lowercase();
+        let output = if lower.ends_with(".gif") {
+            let gif = GifWriter::new(BufWriter::new(File::create(path)?), width as u16, height as u16)?;
+            Output::Gif { gif, pending: None }
+        } else if lower.ends_with(".y4m") {
+            let video = Y4mWriter::new(BufWriter::new(File::create(path)?), width, height, FRAME_RATE, 1)?;
+            let audio = WavWriter::new(BufWriter::new(File::create(wav_path(path))?), SAMPLE_RATE, 1)?;
+            Output::Y4m { video, audio }
+        } else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "record to a .gif or .y4m file"));
+        };
+        Ok(Self { output, width, height, frames: 0 })
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Sound for the frame about to be added, at `SAMPLE_RATE`. Frames without any get silence
+    pub fn audio(&mut self, samples: &[i16]) -> io::Result<()> {
+        match &mut self.output {
+            Output::Gif { .. } => Ok(()),
+            Output::Y4m { audio, .. } => audio.write(samples),
+        }
+    }
+
+    /// Add the next frame, `width * height` pixels of 0RGB
+    pub fn frame(&mut self, pixels: &[u32]) -> io::Result<()> {
+        assert_eq!(pixels.len(), self.width * self.height, "the pixels do not fill the frame");
+        let frame = self.frames;
+        self.frames += 1;
+        match &mut self.output {
+            Output::Gif { gif, pending } => {
+                if !frame.is_multiple_of(GIF_FRAME_STEP) {
+                    return Ok(());
+                }
+                // 画面没变就让上一帧多停一会, 停到延时放不下为止
+                if let Some((last, shown)) = pending {
+                    if last.as_slice() == pixels && centis(frame + GIF_FRAME_STEP) - centis(*shown) <= u64::from(u16::MAX) {
+                        return Ok(());
+                    }
+                }
+                if let Some((last, shown)) = pending.take() {
+                    gif.frame(&last, gif_delay(shown, frame))?;
+                }
+                *pending = Some((pixels.to_vec(), frame));
+                Ok(())
+            }
+            Output::Y4m { video, audio } => {
+                video.frame(pixels)?;
+                let due = (self.frames * u64::from(SAMPLE_RATE) / u64::from(FRAME_RATE)) as u32;
+                if audio.len() < due {
+                    audio.write(&vec![0; (due - audio.len()) as usize])?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Write out what is left and close the files
+    pub fn finish(self) -> io::Result<()> {
+        match self.output {
+            Output::Gif { mut gif, pending } => {
+                if let Some((last, shown)) = pending {
+                    gif.frame(&last, gif_delay(shown, self.frames).max(1))?;
+                }
+                gif.finish().map(|_| ())
+            }
+            Output::Y4m { video, audio } => {
+                video.finish()?;
+                audio.finish().map(|_| ())
+            }
+        }
+    }
+}
+
+/// Hundredths of a second from frame `from` to frame `to`, rounded at both ends so they add up.
+/// Longer than a GIF delay holds is cut short
+fn gif_delay(from: u64, to: u64) -> u16 {
+    (centis(to) - centis(from)).min(u64::from(u16::MAX)) as u16
+}
+
+/// Hundredths of a second from the start to `frame`
+fn centis(frame: u64) -> u64 {
+    frame * 100 / u64::from(FRAME_RATE)
+}
+
+/// `movie.y4m` records its sound to `movie.wav`
+pub fn wav_path(path: &str) -> String {
+    match path.rfind('.') {
+        Some(dot) => format!("{}.wav", &path[..dot]),
+        None => format!("{}.wav", path),
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::io;
+
+/// When the extra ship is awarded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtraShip {
+    At1000,
+    At1500,
+}
+
+/// The DIP switches read through input port 2
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvadersDipSwitches {
+    /// Ships per game, 3 to 6 (bits 0-1)
+    pub ships: u8,
+    /// Bit 3
+    pub extra_ship: ExtraShip,
+    /// Show the coin info on the attract screen (bit 7, active low)
+    pub coin_info: bool,
+}
+
+impl Default for InvadersDipSwitches {
+    fn default() -> Self {
+        Self {
+            ships: 3,
+            extra_ship: ExtraShip::At1500,
+            coin_info: false,
+        }
+    }
+}
+
+impl InvadersDipSwitches {
+    /// The switch bits of port 2
+    pub fn port2_bits(&self) -> u8 {
+        let mut bits = (self.ships.clamp(3, 6) - 3) & 0b0000_0011;
+        if self.extra_ship == ExtraShip::At1000 { bits |= 0b0000_1000 }
+        if !self.coin_info { bits |= 0b1000_0000 }
+        bits
+    }
+
+    /// The switches that give these port 2 bits, the other bits are ignored
+    pub fn from_port2_bits(bits: u8) -> Self {
+        Self {
+            ships: (bits & 0b0000_0011) + 3,
+            extra_ship: if bits & 0b0000_1000 != 0 { ExtraShip::At1000 } else { ExtraShip::At1500 },
+            coin_info: bits & 0b1000_0000 == 0,
+        }
+    }
+
+    /// Change one switch, `key` is `ships`, `bonus` or `coin_info`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "ships" => {
+                self.ships = match value.parse::<u8>() {
+                    Ok(n) if (3..=6).contains(&n) => n,
+                    _ => return Err(format!("ships must be 3 to 6, got '{}'", value)),
+                }
+            }
+            "bonus" => {
+                self.extra_ship = match value {
+                    "1000" => ExtraShip::At1000,
+                    "1500" => ExtraShip::At1500,
+                    _ => return Err(format!("bonus must be 1000 or 1500, got '{}'", value)),
+                }
+            }
+            "coin_info" | "coin-info" => {
+                self.coin_info = match value {
+                    "on" | "true" | "1" => true,
+                    "off" | "false" | "0" => false,
+                    _ => return Err(format!("coin_info must be on or off, got '{}'", value)),
+                }
+            }
+            _ => return Err(format!("unknown DIP switch '{}'", key)),
+        }
+        Ok(())
+    }
+
+    /// Read `key = value` lines, `#` starts a comment
+    pub fn from_file(path: &str) -> io::Result<Self> {
+        let mut dip = Self::default();
+        for (n, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) => dip.set(key.trim(), value.trim()),
+                None => Err(String::from("expected key = value")),
+            };
+            if let Err(e) = result {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path, n + 1, e)));
+            }
+        }
+        Ok(dip)
+    }
+}
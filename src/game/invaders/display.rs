@@ -2,23 +2,42 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use minifb::{Key, Scale, ScaleMode, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, Scale, ScaleMode, Window, WindowOptions};
+
+use crate::game::invaders::{Artwork, InvadersButton, Overlay, render, render_masked, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// What the screenshot key saves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Screenshot {
+    /// The window contents as PNG
+    Png,
+    /// Video RAM, 1 bit a pixel
+    Raw,
+}
 
 pub struct Display {
     window: Window,
+    /// The window, the playfield alone or put into the artwork
     buffer: Vec<u32>,
+    /// The playfield when there is artwork
+    playfield: Vec<u32>,
     video_arr: Rc<RefCell<Vec<u8>>>,
+    /// Colour of the lit pixels from the overlay, none for white
+    mask: Option<Vec<u32>>,
+    artwork: Option<Artwork>,
+    width: usize,
+    height: usize,
 }
 
 const GAME_NAME: &str = "Space Invaders";
-const WIDTH: usize = 224;
-const HEIGHT: usize = 256;
 
 impl Display {
-    pub fn new(video_arr: Rc<RefCell<Vec<u8>>>) -> Self {
+    /// The window takes the size of the artwork when there is one
+    pub fn new(video_arr: Rc<RefCell<Vec<u8>>>, artwork: Option<Artwork>) -> Self {
+        let (width, height) = artwork.as_ref().map_or((SCREEN_WIDTH, SCREEN_HEIGHT), |a| (a.width(), a.height()));
         let mut window = Window::new(
             format!("{} - Powered by Jelipo", GAME_NAME).as_str(),
-            WIDTH, HEIGHT,
+            width, height,
             WindowOptions {
                 borderless: true,
                 transparency: false,
@@ -33,11 +52,23 @@ impl Display {
         });
         Self {
             window,
-            buffer: vec![0; WIDTH * HEIGHT],
+            buffer: vec![0; width * height],
+            playfield: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
             video_arr,
+            mask: None,
+            artwork,
+            width,
+            height,
         }
     }
 
+    pub fn set_overlay(&mut self, overlay: &Overlay) {
+        self.mask = match overlay {
+            Overlay::None => None,
+            overlay => Some(overlay.mask()),
+        };
+    }
+
     /// This is block method
     pub fn start(&mut self) {
         // 限制最高60帧
@@ -47,57 +78,98 @@ impl Display {
         while self.window.is_open() && !self.window.is_key_down(Key::Escape) {
             lasttime = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
             self.set_buffer(self.video_arr.clone());
-            self.window.update_with_buffer(&self.buffer, WIDTH, HEIGHT).unwrap();
+            self.window.update_with_buffer(&self.buffer, self.width, self.height).unwrap();
         }
     }
 
-    pub fn update_cycle(&mut self) -> Option<Key> {
-        self.set_buffer(self.video_arr.clone());
-        self.window.update_with_buffer(&self.buffer, WIDTH, HEIGHT).unwrap();
-        return if self.window.is_key_down(Key::Left) {
-            Some(Key::Left)
-        } else if self.window.is_key_down(Key::Right) {
-            Some(Key::Right)
-        } else if self.window.is_key_down(Key::Space) {
-            Some(Key::Space)
-        } else if self.window.is_key_down(Key::Enter) {
-            Some(Key::Enter)
-        } else if self.window.is_key_down(Key::C) {
-            Some(Key::C)
-        } else {
-            None
-        };
+    pub fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
     }
 
-    fn set_buffer(&mut self, video_arr: Rc<RefCell<Vec<u8>>>) {
-        let gpu_ram = video_arr.borrow();
-        for i in 0..gpu_ram.len() {
-            let gpu_byte = gpu_ram[i];
-            // display_point
-            let dp = i * 8;
-            let buffer_size = self.buffer.len();
-            self.set_point(dp, get_color(gpu_byte & 0b0000_0001));
-            self.set_point(dp + 1, get_color(gpu_byte & 0b0000_0010));
-            self.set_point(dp + 2, get_color(gpu_byte & 0b0000_0100));
-            self.set_point(dp + 3, get_color(gpu_byte & 0b0000_1000));
-            self.set_point(dp + 4, get_color(gpu_byte & 0b0001_0000));
-            self.set_point(dp + 5, get_color(gpu_byte & 0b0010_0000));
-            self.set_point(dp + 6, get_color(gpu_byte & 0b0100_0000));
-            self.set_point(dp + 7, get_color(gpu_byte & 0b1000_0000));
+    /// F12 breaks into the monitor
+    pub fn break_requested(&self) -> bool {
+        self.window.is_key_pressed(Key::F12, KeyRepeat::No)
+    }
+
+    /// F5 saves the state
+    pub fn save_requested(&self) -> bool {
+        self.window.is_key_pressed(Key::F5, KeyRepeat::No)
+    }
+
+    /// F9 loads the state saved with F5
+    pub fn load_requested(&self) -> bool {
+        self.window.is_key_pressed(Key::F9, KeyRepeat::No)
+    }
+
+    /// F10 takes a screenshot, with Shift a dump of video RAM
+    pub fn screenshot_requested(&self) -> Option<Screenshot> {
+        if !self.window.is_key_pressed(Key::F10, KeyRepeat::No) {
+            return None;
+        }
+        if self.window.is_key_down(Key::LeftShift) || self.window.is_key_down(Key::RightShift) {
+            Some(Screenshot::Raw)
+        } else {
+            Some(Screenshot::Png)
         }
     }
 
-    fn set_point(&mut self, display_point: usize, color: u32) {
-        let new_x = display_point / HEIGHT;
-        let new_y = HEIGHT - 1 - (display_point % HEIGHT);
-        self.buffer[new_y * WIDTH + new_x] = color;
+    /// The last frame drawn, `size()` pixels of 0RGB
+    pub fn buffer(&self) -> &[u32] {
+        &self.buffer
     }
-}
 
-fn get_color(bit: u8) -> u32 {
-    return if bit == 0 { 0 } else { u32::max_value() };
-}
+    /// Width and height of the window
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
 
+    /// Backspace is held down to rewind
+    pub fn rewind_held(&self) -> bool {
+        self.window.is_key_down(Key::Backspace)
+    }
 
+    /// Draw the frame and return the button presses and releases since the last call
+    pub fn update_cycle(&mut self) -> Vec<(InvadersButton, bool)> {
+        self.set_buffer(self.video_arr.clone());
+        self.window.update_with_buffer(&self.buffer, self.width, self.height).unwrap();
+        let mut events = Vec::new();
+        if let Some(keys) = self.window.get_keys_pressed(KeyRepeat::No) {
+            events.extend(keys.into_iter().filter_map(key_button).map(|b| (b, true)));
+        }
+        if let Some(keys) = self.window.get_keys_released() {
+            events.extend(keys.into_iter().filter_map(key_button).map(|b| (b, false)));
+        }
+        events
+    }
 
+    fn set_buffer(&mut self, video_arr: Rc<RefCell<Vec<u8>>>) {
+        let target = match self.artwork {
+            Some(_) => &mut self.playfield,
+            None => &mut self.buffer,
+        };
+        match &self.mask {
+            Some(mask) => render_masked(&video_arr.borrow(), target, mask),
+            None => render(&video_arr.borrow(), target),
+        }
+        if let Some(artwork) = &self.artwork {
+            artwork.compose(&self.playfield, &mut self.buffer);
+        }
+    }
+}
 
+/// 键盘映射
+fn key_button(key: Key) -> Option<InvadersButton> {
+    match key {
+        Key::C => Some(InvadersButton::Coin),
+        Key::Enter | Key::Key1 => Some(InvadersButton::P1Start),
+        Key::Key2 => Some(InvadersButton::P2Start),
+        Key::Space => Some(InvadersButton::P1Fire),
+        Key::Left => Some(InvadersButton::P1Left),
+        Key::Right => Some(InvadersButton::P1Right),
+        Key::W => Some(InvadersButton::P2Fire),
+        Key::A => Some(InvadersButton::P2Left),
+        Key::D => Some(InvadersButton::P2Right),
+        Key::T => Some(InvadersButton::Tilt),
+        _ => None,
+    }
+}
@@ -1,68 +1,190 @@
-use minifb::Key;
-
 use crate::cpu::{Cpu, IO};
+use crate::game::invaders::{InvadersDipSwitches, ShiftRegister};
+use crate::util::{Snapshot, StateError, StateReader, StateWriter};
+
+/// Buttons and switches wired to input ports 0, 1 and 2
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvadersButton {
+    Coin,
+    P1Start,
+    P2Start,
+    P1Fire,
+    P1Left,
+    P1Right,
+    P2Fire,
+    P2Left,
+    P2Right,
+    Tilt,
+}
+
+impl InvadersButton {
+    /// Name used in input scripts: `coin`, `start1`, `start2`, `fire`, `left`, `right`,
+    /// `fire2`, `left2`, `right2` or `tilt`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "coin" => InvadersButton::Coin,
+            "start1" | "start" => InvadersButton::P1Start,
+            "start2" => InvadersButton::P2Start,
+            "fire" | "fire1" => InvadersButton::P1Fire,
+            "left" | "left1" => InvadersButton::P1Left,
+            "right" | "right1" => InvadersButton::P1Right,
+            "fire2" => InvadersButton::P2Fire,
+            "left2" => InvadersButton::P2Left,
+            "right2" => InvadersButton::P2Right,
+            "tilt" => InvadersButton::Tilt,
+            _ => return None,
+        })
+    }
+
+    /// The (port, bit) pairs this button drives, active high
+    fn wiring(self) -> &'static [(usize, u8)] {
+        match self {
+            InvadersButton::Coin => &[(1, 0b0000_0001)],
+            InvadersButton::P2Start => &[(1, 0b0000_0010)],
+            InvadersButton::P1Start => &[(1, 0b0000_0100)],
+            // Port 0 mirrors the player 1 controls
+            InvadersButton::P1Fire => &[(0, 0b0001_0000), (1, 0b0001_0000)],
+            InvadersButton::P1Left => &[(0, 0b0010_0000), (1, 0b0010_0000)],
+            InvadersButton::P1Right => &[(0, 0b0100_0000), (1, 0b0100_0000)],
+            InvadersButton::Tilt => &[(2, 0b0000_0100)],
+            InvadersButton::P2Fire => &[(2, 0b0001_0000)],
+            InvadersButton::P2Left => &[(2, 0b0010_0000)],
+            InvadersButton::P2Right => &[(2, 0b0100_0000)],
+        }
+    }
+}
+
+/// Bits that read as 1 with nothing pressed, port 0 bits 1-3 and port 1 bit 3 are tied high.
+/// The DIP switch bits of port 2 come from `InvadersDipSwitches`.
+const PORT_DEFAULTS: [u8; 3] = [0b0000_1110, 0b0000_1000, 0b0000_0000];
+
+/// A write to sound port 3 or 5, at the CPU cycle since power on the instruction ended on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoundWrite {
+    pub port: u8,
+    pub value: u8,
+    pub cycle: u64,
+}
 
 pub struct InvadersIO {
-    input_temp: Option<Key>
+    /// 输入端口 0, 1, 2 的锁存状态
+    ports: [u8; 3],
+    dip: InvadersDipSwitches,
+    shifter: ShiftRegister,
+    /// Sound writes not taken yet, the ones from `stamped` on still wait for their cycle
+    sound: Vec<SoundWrite>,
+    stamped: usize,
 }
 
 impl InvadersIO {
     pub fn new() -> Self {
         Self {
-            input_temp: None
+            ports: PORT_DEFAULTS,
+            dip: InvadersDipSwitches::default(),
+            shifter: ShiftRegister::new(),
+            sound: Vec::new(),
+            stamped: 0,
         }
     }
 
-    pub fn clean_temp(&mut self) {
-        self.input_temp = None;
+    pub fn press(&mut self, button: InvadersButton) {
+        self.set_button(button, true);
+    }
+
+    pub fn release(&mut self, button: InvadersButton) {
+        self.set_button(button, false);
+    }
+
+    pub fn set_button(&mut self, button: InvadersButton, pressed: bool) {
+        for &(port, bit) in button.wiring() {
+            if pressed {
+                self.ports[port] |= bit;
+            } else {
+                self.ports[port] &= !bit;
+            }
+        }
+    }
+
+    /// The button bits latched on ports 0-2, without the DIP switches
+    pub fn buttons(&self) -> [u8; 3] {
+        self.ports
+    }
+
+    /// Set every button at once, as `buttons` returned them
+    pub fn set_buttons(&mut self, ports: [u8; 3]) {
+        self.ports = ports;
     }
 
-    pub fn set_input_temp(&mut self, key: Key) {
-        self.input_temp = Some(key)
+    /// Let go of every button
+    pub fn release_all(&mut self) {
+        self.ports = PORT_DEFAULTS;
+    }
+
+    /// Give the sound writes of the instruction that just ran their cycle
+    pub fn stamp_sound(&mut self, cycle: u64) {
+        for write in &mut self.sound[self.stamped..] {
+            write.cycle = cycle;
+        }
+        self.stamped = self.sound.len();
+    }
+
+    /// The sound port writes since the last call, oldest first
+    pub fn take_sound_writes(&mut self) -> Vec<SoundWrite> {
+        self.stamped = 0;
+        std::mem::take(&mut self.sound)
+    }
+
+    pub fn set_dip_switches(&mut self, dip: InvadersDipSwitches) {
+        self.dip = dip;
+    }
+
+    /// The value `IN port` reads for input ports 0, 1 and 2
+    pub fn port(&self, port: u8) -> u8 {
+        match port {
+            2 => self.ports[2] | self.dip.port2_bits(),
+            _ => self.ports[port as usize],
+        }
+    }
+}
+
+impl Default for InvadersIO {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl IO for InvadersIO {
     fn input(&mut self, cpu: &mut Cpu, byte: u8) {
-        let input_key = match self.input_temp {
-            None => {
-                println!("执行input {:X}", byte);
-                return;
-            }
-            Some(i) => i
-        };
-        let reg_a = match byte {
-            1 => {
-                match input_key {
-                    Key::C => { Some(0b0000_0001) }
-                    Key::Enter => { Some(0b0000_0100) }
-                    Key::Space => { Some(0b0001_0000) }
-                    Key::Left => { Some(0b0010_0000) }
-                    Key::Right => { Some(0b0100_0000) }
-                    _ => None
-                }
-            }
-            2 => match input_key {
-                // Key::C => { Some(0b0000_0001) }
-                // Key::Enter => { Some(0b0000_0100) }
-                // Key::Space => { Some(0b0001_0000) }
-                // Key::Left => { Some(0b0010_0000) }
-                // Key::Right => { Some(0b0100_0000) }
-                _ => Some(0b1000_0000)
-            },
-            3 => None,
-            _ => None,
-        };
-        match reg_a {
-            Some(a) => cpu.register.a = a,
+        match byte {
+            0..=2 => cpu.register.a = self.port(byte),
+            // 移位寄存器的结果
+            3 => cpu.register.a = self.shifter.read(),
             _ => {}
         }
-        self.input_temp = None;
-        println!("执行input {:X}", byte);
     }
 
     fn output(&mut self, cpu: &mut Cpu, byte: u8) {
-        println!("执行output");
+        match byte {
+            // 移位量
+            2 => self.shifter.set_offset(cpu.register.a),
+            // 移位数据
+            4 => self.shifter.push(cpu.register.a),
+            3 | 5 => self.sound.push(SoundWrite { port: byte, value: cpu.register.a, cycle: 0 }),
+            // 6: watchdog
+            _ => {}
+        }
     }
 }
 
+/// The latched buttons and the shift register. The DIP switches belong to the cabinet, not the state
+impl Snapshot for InvadersIO {
+    fn save(&self, w: &mut StateWriter) {
+        w.bytes(&self.ports);
+        self.shifter.save(w);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.ports.copy_from_slice(r.bytes(3)?);
+        self.shifter.load(r)
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::process;
+
+use crate::audio::{AudioSink, NullSink, WavSink};
+use crate::cpu::CpuError;
+use crate::game::invaders::{InvadersButton, InvadersDipSwitches, InvadersMachine, InvadersSound, Movie, MoviePlayer,
+                            Overlay, Recorder, render_masked, screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::game::Launch;
+use crate::image::write_png;
+
+/// Frames a `tap` holds the button down when the script does not say
+const TAP_FRAMES: u64 = 5;
+
+/// Button presses and releases by frame, counted from the start of the run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputScript {
+    events: BTreeMap<u64, Vec<(InvadersButton, bool)>>,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// One event per line: `FRAME press BUTTON`, `FRAME release BUTTON` or `FRAME tap BUTTON [FRAMES]`.
+    /// `#` starts a comment, button names are those of `InvadersButton::from_name`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut script = Self::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let error = |what: &str| format!("line {}: {}", n + 1, what);
+            if words.len() < 3 {
+                return Err(error("expected FRAME ACTION BUTTON"));
+            }
+            let frame: u64 = words[0].parse().map_err(|_| error("bad frame number"))?;
+            let button = InvadersButton::from_name(words[2]).ok_or_else(|| error("unknown button"))?;
+            match (words[1], words.get(3)) {
+                ("press", None) => script.press(frame, button),
+                ("release", None) => script.release(frame, button),
+                ("tap", len) => {
+                    let len = match len {
+                        Some(len) => len.parse().map_err(|_| error("bad frame count"))?,
+                        None => TAP_FRAMES,
+                    };
+                    script.tap(frame, button, len);
+                }
+                _ => return Err(error("expected press, release or tap")),
+            }
+        }
+        Ok(script)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn press(&mut self, frame: u64, button: InvadersButton) {
+        self.events.entry(frame).or_default().push((button, true));
+    }
+
+    pub fn release(&mut self, frame: u64, button: InvadersButton) {
+        self.events.entry(frame).or_default().push((button, false));
+    }
+
+    /// Press `button` for `len` frames
+    pub fn tap(&mut self, frame: u64, button: InvadersButton, len: u64) {
+        self.press(frame, button);
+        self.release(frame + len.max(1), button);
+    }
+
+    /// What happens before `frame` runs, in script order
+    pub fn events(&self, frame: u64) -> &[(InvadersButton, bool)] {
+        self.events.get(&frame).map_or(&[], |e| e.as_slice())
+    }
+}
+
+/// Runs the machine without a window, as fast as it goes
+pub struct Headless {
+    pub machine: InvadersMachine,
+    script: InputScript,
+    /// Frames run so far
+    frame: u64,
+    pub sound: InvadersSound,
+    /// Sound of the last frame
+    audio: Vec<i16>,
+    /// Colour of the lit pixels from the overlay, none for white
+    mask: Option<Vec<u32>>,
+}
+
+impl Headless {
+    pub fn new(machine: InvadersMachine) -> Self {
+        let mut sound = InvadersSound::new();
+        sound.sync(machine.scheduler().cycles());
+        Self {
+            machine,
+            script: InputScript::new(),
+            frame: 0,
+            sound,
+            audio: Vec::new(),
+            mask: None,
+        }
+    }
+
+    /// The Invaders ROMs from `./res`, powered on
+    pub fn power_on(dip: InvadersDipSwitches) -> io::Result<Self> {
+        InvadersMachine::from_rom_dir("./res", dip).map(Self::new)
+    }
+
+    pub fn set_script(&mut self, script: InputScript) {
+        self.script = script;
+    }
+
+    /// Colour `screen` and everything made from it, the raw dump stays as the video RAM is
+    pub fn set_overlay(&mut self, overlay: &Overlay) {
+        self.mask = match overlay {
+            Overlay::None => None,
+            overlay => Some(overlay.mask()),
+        };
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Run one frame with the script's buttons for it
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        for &(button, pressed) in self.script.events(self.frame) {
+            self.machine.io.borrow_mut().set_button(button, pressed);
+        }
+        self.machine.run_frame()?;
+        self.end_frame();
+        Ok(())
+    }
+
+    /// Mix the frame's sound and count it
+    fn end_frame(&mut self) {
+        let writes = self.machine.io.borrow_mut().take_sound_writes();
+        self.audio = self.sound.mix(&writes, self.machine.scheduler().cycles());
+        self.frame += 1;
+    }
+
+    /// The sound of the last frame, mono at `SAMPLE_RATE`
+    pub fn audio(&self) -> &[i16] {
+        &self.audio
+    }
+
+    pub fn run(&mut self, frames: u64) -> Result<(), CpuError> {
+        for _ in 0..frames {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// The frame buffer, `SCREEN_WIDTH * SCREEN_HEIGHT` pixels of 0RGB
+    pub fn screen(&self) -> Vec<u32> {
+        match &self.mask {
+            Some(mask) => {
+                let mut buffer = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+                render_masked(&self.machine.video_arr.borrow(), &mut buffer, mask);
+                buffer
+            }
+            None => screen(&self.machine.video_arr.borrow()),
+        }
+    }
+
+    /// Pixels that are on
+    pub fn lit_pixels(&self) -> usize {
+        self.machine.video_arr.borrow().iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// The screen as a PNG, turned upright like in the cabinet
+    pub fn write_png(&self, path: &str) -> io::Result<()> {
+        write_png(path, &self.screen(), SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+
+    /// Video RAM as it is, 1 bit a pixel, each byte 8 pixels up a column from the bottom left
+    pub fn write_raw(&self, path: &str) -> io::Result<()> {
+        fs::write(path, &*self.machine.video_arr.borrow())
+    }
+
+    /// PNG, PBM or raw video RAM by the extension of `path`: `.png`, `.pbm`, anything else is raw
+    pub fn dump(&self, path: &str) -> io::Result<()> {
+        let lower = path.to_ascii_lowercase();
+        if lower.ends_with(".png") {
+            self.write_png(path)
+        } else if lower.ends_with(".pbm") {
+            self.write_pbm(path)
+        } else {
+            self.write_raw(path)
+        }
+    }
+
+    /// The screen as a binary PBM, which most image viewers open
+    pub fn write_pbm(&self, path: &str) -> io::Result<()> {
+        let mut data = format!("P4\n{} {}\n", SCREEN_WIDTH, SCREEN_HEIGHT).into_bytes();
+        for row in self.screen().chunks(SCREEN_WIDTH) {
+            for pixels in row.chunks(8) {
+                // PBM 里 1 是黑色
+                let bits = pixels.iter().enumerate().fold(0u8, |b, (i, p)| if *p == 0 { b | 0x80 >> i } else { b });
+                data.push(bits);
+            }
+        }
+        fs::write(path, data)
+    }
+}
+
+/// `rust8080 headless`: run a number of frames, dump screens and print the RAM hash.
+/// Exits with status 1 when something goes wrong, for CI
+pub struct HeadlessLaunch {
+    pub dip: InvadersDipSwitches,
+    pub frames: Option<u64>,
+    pub script: Option<String>,
+    /// Save state to start from instead of power on
+    pub resume: Option<String>,
+    /// Play this movie, checking its checkpoints
+    pub play: Option<String>,
+    /// Write the screen after these frames, see `Headless::dump` for the formats
+    pub dumps: Vec<(u64, String)>,
+    /// Record every frame, see `Recorder::create`
+    pub capture: Option<String>,
+    /// Directory with the sound samples, `0.wav` to `8.wav`
+    pub samples: Option<String>,
+    /// Write the sound to this WAV file
+    pub sound: Option<String>,
+    /// Synthesize the effects that have no sample
+    pub synth: bool,
+    pub overlay: Overlay,
+}
+
+impl Launch for HeadlessLaunch {
+    fn start(&self) {
+        match self.run() {
+            Ok(summary) => println!("{}", summary),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+impl HeadlessLaunch {
+    pub fn new(dip: InvadersDipSwitches) -> Self {
+        Self {
+            dip,
+            frames: None,
+            script: None,
+            resume: None,
+            play: None,
+            dumps: Vec::new(),
+            capture: None,
+            samples: None,
+            sound: None,
+            synth: true,
+            overlay: Overlay::None,
+        }
+    }
+
+    /// Run and return a summary line with the frame count and the RAM hash
+    pub fn run(&self) -> Result<String, String> {
+        let mut headless = Headless::power_on(self.dip).map_err(|e| format!("./res: {}", e))?;
+        if let Some(path) = &self.resume {
+            headless.machine.load_state_file(path).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        if let Some(path) = &self.script {
+            headless.set_script(InputScript::load(path)?);
+        }
+        let mut player = match &self.play {
+            Some(path) => {
+                let movie = Movie::load(path).map_err(|e| format!("{}: {}", path, e))?;
+                let mut player = MoviePlayer::new(movie);
+                player.start(&mut headless.machine).map_err(|e| format!("{}: {}", path, e))?;
+                Some(player)
+            }
+            None => None,
+        };
+        headless.set_overlay(&self.overlay);
+        headless.sound.set_synth(self.synth);
+        if let Some(dir) = &self.samples {
+            headless.sound.load_samples(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        }
+        // 读档和回放都会改变时钟
+        headless.sound.sync(headless.machine.scheduler().cycles());
+        let mut sink: Box<dyn AudioSink> = match &self.sound {
+            Some(path) => Box::new(WavSink::create(path).map_err(|e| format!("{}: {}", path, e))?),
+            None => Box::new(NullSink::new()),
+        };
+        let frames = match (self.frames, &player) {
+            (Some(frames), _) => frames,
+            (None, Some(player)) => player.movie().frames.len() as u64,
+            (None, None) => return Err(String::from("headless needs --frames or --play")),
+        };
+        if let Some((at, path)) = self.dumps.iter().find(|(at, _)| *at > frames) {
+            return Err(format!("{}: frame {} is after the last frame {}", path, at, frames));
+        }
+        // 第 0 帧是开始时的画面
+        self.dump_frame(&headless)?;
+        let mut recorder = match &self.capture {
+            Some(path) => Some(Recorder::create(path, SCREEN_WIDTH, SCREEN_HEIGHT).map_err(|e| format!("{}: {}", path, e))?),
+            None => None,
+        };
+        while headless.frame() < frames {
+            match player.as_mut() {
+                Some(player) => {
+                    if !player.step(&mut headless.machine).map_err(|e| e.to_string())? {
+                        return Err(format!("the movie ends after {} frames", player.frame()));
+                    }
+                    headless.end_frame();
+                }
+                None => headless.step().map_err(|e| e.to_string())?,
+            }
+            self.dump_frame(&headless)?;
+            sink.write(headless.audio()).map_err(|e| e.to_string())?;
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.audio(headless.audio()).map_err(|e| e.to_string())?;
+                recorder.frame(&headless.screen()).map_err(|e| e.to_string())?;
+            }
+        }
+        sink.finish().map_err(|e| e.to_string())?;
+        if let (Some(path), Some(recorder)) = (&self.capture, recorder) {
+            recorder.finish().map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(format!("{} frames, RAM hash {:016x}", frames, headless.machine.ram_hash()))
+    }
+
+    /// Write the dumps asked for at the current frame
+    fn dump_frame(&self, headless: &Headless) -> Result<(), String> {
+        for (_, path) in self.dumps.iter().filter(|(at, _)| *at == headless.frame()) {
+            headless.dump(path).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(())
+    }
+}
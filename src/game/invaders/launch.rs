@@ -1,135 +1,369 @@
-use std::{io, thread};
-use std::cell::RefCell;
-use std::fs::File;
-use std::io::Read;
-use std::rc::Rc;
-use std::sync::{Arc, RwLock};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::fs;
+use std::io;
+use std::io::{StdinLock, Stdout};
+use std::time::{Duration, Instant};
 
-use crate::cpu::Cpu;
-use crate::game::invaders::gameio::InvadersIO;
-use crate::game::invaders::display::Display;
+#[cfg(target_os = "linux")]
+use crate::audio::AlsaSink;
+use crate::audio::{AudioSink, NullSink, SAMPLE_RATE, WavSink};
+use crate::debugger::{Debugger, Monitor, MonitorExit, Stop};
+use crate::game::invaders::display::{Display, Screenshot};
 use crate::game::Launch;
-use crate::game::invaders::InvadersAddressBus;
+use crate::image::write_png;
+use crate::util::{Pacer, RateControl};
+use crate::game::invaders::{Artwork, CPU_HZ, CYCLES_PER_FRAME, InvadersDipSwitches, InvadersMachine, InvadersSound, Movie,
+                            MoviePlayer, Overlay, Recorder, Rewind, SCREEN_HEIGHT, SCREEN_WIDTH};
 
 
-pub struct InvadersLaunch {}
+type ConsoleMonitor<'a> = Monitor<StdinLock<'a>, Stdout>;
 
-impl Launch for InvadersLaunch {
-    fn start(&self) {
-        let gpu_ram = vec![0u8; 7168];
-
-        let video_arr = Rc::new(RefCell::new(gpu_ram));
-        let video_arr_cloned = video_arr.clone();
+pub struct InvadersLaunch {
+    pub dip: InvadersDipSwitches,
+    /// Break into the terminal monitor before the first instruction
+    pub monitor: bool,
+    pub window: bool,
+    /// Where F5 saves the state and F9 loads it from
+    pub state: String,
+    /// Memory for rewinding with Backspace, in bytes. 0 turns rewind off
+    pub rewind: usize,
+    /// Directory for F10 screenshots
+    pub screenshots: String,
+    /// Save state to start from instead of power on
+    pub resume: Option<String>,
+    /// Record the buttons to this movie file, written when the window closes
+    pub record: Option<String>,
+    /// Play this movie before handing over to the keyboard
+    pub play: Option<String>,
+    /// Record every frame shown, see `Recorder::create`
+    pub capture: Option<String>,
+    /// Directory with the sound samples, `0.wav` to `8.wav`
+    pub samples: Option<String>,
+    /// Write the sound to this WAV file
+    pub sound: Option<String>,
+    /// Synthesize the effects that have no sample
+    pub synth: bool,
+    /// Play the sound on the sound card
+    pub audio: bool,
+    /// Let the sound card's clock set the pace, speeding up or slowing down a little
+    pub audio_sync: bool,
+    /// Print the frames shown every 10 seconds
+    pub fps: bool,
+    /// Coloured gel over the screen
+    pub overlay: Overlay,
+    /// Layout file for the backdrop and the bezel, see `Artwork::parse`
+    pub artwork: Option<String>,
+}
 
-        let addressing = init_address(video_arr_cloned.clone()).unwrap();
+/// Sound card buffer in microseconds, and the samples to keep queued in it when following its clock
+const AUDIO_LATENCY: u32 = 100_000;
+const AUDIO_TARGET: usize = SAMPLE_RATE as usize / 20;
 
-        let io = Rc::new(RefCell::new(InvadersIO::new()));
-        let mut cpu = Cpu::new(Box::new(addressing), 0, io.clone());
-        let mut int_num: bool = false;
-        let mut time = get_mill_time();
-        let mut int_times = 0;
-        let cycle_max: u32 = 17476;
-        let max_fps: u8 = 60;
-        let mut fps_temp: u8 = 0;
-        let mut fps_timelinei128 = get_mill_time();
-        let mut video = Display::new(video_arr.clone());
-        //video.start();
-        let loop_io = io.clone();
-        loop {
-            let mut cycle_temp: u32 = 0;
-            loop {
-                let cycle = cpu.next();
-                cycle_temp += cycle as u32;
-                if cycle_temp > cycle_max {
-                    cycle_temp = 0;
-                    break;
+impl Launch for InvadersLaunch {
+    fn start(&self) {
+        let mut machine = InvadersMachine::from_rom_dir("./res", self.dip).unwrap();
+        if let Some(path) = &self.resume {
+            if let Err(e) = machine.load_state_file(path) {
+                eprintln!("{}: {}", path, e);
+                return;
+            }
+        }
+        let mut player = match &self.play {
+            Some(path) => {
+                let started = Movie::load(path).and_then(|movie| {
+                    let mut player = MoviePlayer::new(movie);
+                    player.start(&mut machine).map(|_| player)
+                });
+                match started {
+                    Ok(player) => Some(player),
+                    Err(e) => {
+                        eprintln!("{}: {}", path, e);
+                        return;
+                    }
                 }
             }
-            let result = cpu.interrupt(if int_num { 0x10 } else { 0x08 });
-            if result {
-                int_num = !int_num;
-                int_times += 1;
-                if (get_mill_time() - time) > 10000 {
-                    println!("10 sec : {} fps", int_times);
-                    time = get_mill_time();
-                    int_times = 0;
+            None => None,
+        };
+        let mut movie = match (&self.record, &self.resume) {
+            (Some(_), Some(_)) => Some(Movie::from_machine(self.dip, &machine)),
+            (Some(_), None) => Some(Movie::power_on(self.dip)),
+            (None, _) => None,
+        };
+        let artwork = match self.artwork.as_deref().map(Artwork::load).transpose() {
+            Ok(artwork) => artwork,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        let (width, height) = artwork.as_ref().map_or((SCREEN_WIDTH, SCREEN_HEIGHT), |a| (a.width(), a.height()));
+        let mut recorder = match &self.capture {
+            Some(path) => match Recorder::create(path, width, height) {
+                Ok(recorder) => Some(recorder),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    return;
+                }
+            },
+            None => None,
+        };
+        let mut sound = InvadersSound::new();
+        sound.set_synth(self.synth);
+        if let Some(dir) = &self.samples {
+            match sound.load_samples(dir) {
+                Ok(found) => println!("{} sound samples loaded from {}", found, dir),
+                Err(e) => {
+                    eprintln!("{}: {}", dir, e);
+                    return;
                 }
             }
-            if result {
-                loop {
-                    let cycle = cpu.next();
-                    cycle_temp += cycle as u32;
-                    if cycle_temp > cycle_max {
-                        cycle_temp = 0;
-                        break;
+        }
+        sound.sync(machine.scheduler().cycles());
+        let mut sink: Box<dyn AudioSink> = match &self.sound {
+            Some(path) => match WavSink::create(path) {
+                Ok(sink) => Box::new(sink),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    return;
+                }
+            },
+            None => Box::new(NullSink::new()),
+        };
+        let stdin = io::stdin();
+        let mut monitor = if self.monitor {
+            Some(Monitor::new(Debugger::attach(&mut machine.cpu), stdin.lock(), io::stdout()))
+        } else {
+            None
+        };
+        if !self.window {
+            if let Some(monitor) = monitor.as_mut() {
+                run_console(monitor, &mut machine);
+            }
+            return;
+        }
+        if let Some(monitor) = monitor.as_mut() {
+            if monitor.prompt(&mut machine).unwrap_or(MonitorExit::Quit) == MonitorExit::Quit {
+                return;
+            }
+        }
+        let mut speaker = match self.open_speaker() {
+            Ok(speaker) => speaker,
+            Err(e) => {
+                eprintln!("sound card: {}", e);
+                return;
+            }
+        };
+        let rate = RateControl::new(AUDIO_TARGET);
+        let mut pacer = Pacer::new(CPU_HZ);
+        // 模拟的时钟, 只往前走
+        let mut clock = 0u64;
+        let mut time = Instant::now();
+        let mut frames = 0;
+        let mut video = Display::new(machine.video_arr.clone(), artwork);
+        video.set_overlay(&self.overlay);
+        let mut rewind = Rewind::new(self.rewind);
+        if self.rewind > 0 {
+            rewind.push(machine.save_state());
+        }
+        while video.is_open() {
+            // 录像和回放时不能倒带或读档, 否则输入和结果对不上
+            let live = player.is_none() && movie.is_none();
+            let before = machine.scheduler().cycles();
+            if let Some(playing) = player.as_mut() {
+                match playing.step(&mut machine) {
+                    Ok(true) if self.rewind > 0 => rewind.push(machine.save_state()),
+                    Ok(true) => {}
+                    Ok(false) => {
+                        println!("movie over after {} frames", playing.frame());
+                        player = None;
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        player = None;
                     }
                 }
-                let key_option = video.update_cycle();
-                match key_option {
-                    Some(key) => loop_io.borrow_mut().set_input_temp(key),
-                    None => loop_io.borrow_mut().clean_temp()
+            } else if live && self.rewind > 0 && video.rewind_held() {
+                // 倒带时不执行, 每帧退回一个存档; 按键以倒带后实际按住的为准
+                if let Some(state) = rewind.pop() {
+                    if machine.load_state(&state).is_ok() {
+                        machine.io.borrow_mut().release_all();
+                        sound.sync(machine.scheduler().cycles());
+                    }
                 }
-
-                fps_temp += 1;
-                let time_now = get_mill_time();
-
-                let i = (time_now - fps_timelinei128) as u16;
-                if fps_temp >= 60 {
-                    if i < 1000 {
-                        let sleep = 1000 - i;
-                        println!("补充睡眠 {}ms", sleep);
-                        thread::sleep(Duration::from_micros(sleep as u64));
+            } else {
+                if !run_frame(&mut machine, monitor.as_mut()) {
+                    break;
+                }
+                if let Some(movie) = movie.as_mut() {
+                    movie.record(&machine);
+                }
+                if self.rewind > 0 {
+                    rewind.push(machine.save_state());
+                }
+            }
+            // 倒带时时钟往回跳, 也算一帧的时间
+            let ran = machine.scheduler().cycles().wrapping_sub(before);
+            clock += if ran > 0 && ran <= 2 * u64::from(CYCLES_PER_FRAME) { ran } else { u64::from(CYCLES_PER_FRAME) };
+            frames += 1;
+            if self.fps && time.elapsed() > Duration::from_secs(10) {
+                println!("10 sec : {} frames", frames);
+                time = Instant::now();
+                frames = 0;
+            }
+            for (button, pressed) in video.update_cycle() {
+                if player.is_none() {
+                    machine.io.borrow_mut().set_button(button, pressed);
+                }
+            }
+            let writes = machine.io.borrow_mut().take_sound_writes();
+            let audio = sound.mix(&writes, machine.scheduler().cycles());
+            if let Err(e) = sink.write(&audio) {
+                eprintln!("{}", e);
+                sink = Box::new(NullSink::new());
+            }
+            if let Some(capturing) = recorder.as_mut() {
+                // 写不进去就停止录制, 游戏继续
+                if let Err(e) = capturing.audio(&audio).and_then(|_| capturing.frame(video.buffer())) {
+                    eprintln!("{}", e);
+                    recorder = None;
+                }
+            }
+            if let Some(kind) = video.screenshot_requested() {
+                // 用模拟的帧号命名, 同一段录像每次截到的文件名一样
+                let name = format!("{}/invaders-{:06}", self.screenshots, machine.scheduler().frame());
+                let (width, height) = video.size();
+                let saved = match kind {
+                    Screenshot::Png => write_png(&format!("{}.png", name), video.buffer(), width, height)
+                        .map(|_| format!("{}.png", name)),
+                    Screenshot::Raw => fs::write(format!("{}.bin", name), &*machine.video_arr.borrow())
+                        .map(|_| format!("{}.bin", name)),
+                };
+                match saved {
+                    Ok(path) => println!("screenshot saved to {}", path),
+                    Err(e) => eprintln!("{}: {}", name, e),
+                }
+            }
+            if video.save_requested() {
+                match machine.save_state_file(&self.state) {
+                    Ok(()) => println!("state saved to {}", self.state),
+                    Err(e) => eprintln!("{}: {}", self.state, e),
+                }
+            }
+            if video.load_requested() && !live {
+                eprintln!("loading a state is off while a movie records or plays");
+            } else if video.load_requested() {
+                match machine.load_state_file(&self.state) {
+                    Ok(()) => {
+                        sound.sync(machine.scheduler().cycles());
+                        println!("state loaded from {}", self.state);
                     }
-                    println!("重置 {}", time_now);
-                    fps_temp = 0;
-                    fps_timelinei128 = time_now;
-                } else {
-                    let sleep = ((1000 as u16).saturating_sub(i)) / (60 - fps_temp) as u16;
-                    if sleep != 0 {
-                        //println!("睡眠 {}ms", sleep);
-                        thread::sleep(Duration::from_micros(sleep as u64));
+                    Err(e) => eprintln!("{}: {}", self.state, e),
+                }
+            }
+            if video.break_requested() {
+                if let Some(monitor) = monitor.as_mut() {
+                    if monitor.prompt(&mut machine).unwrap_or(MonitorExit::Quit) == MonitorExit::Quit {
+                        break;
                     }
                 }
-            } else {
-                println!("not")
+            }
+
+            if let Some(out) = speaker.as_mut() {
+                if let Err(e) = out.write(&audio) {
+                    eprintln!("sound card: {}", e);
+                    speaker = None;
+                }
+            }
+            // 跟着声卡的时钟走: 缓冲多了就稍微慢一点
+            if let Some(queued) = speaker.as_ref().filter(|_| self.audio_sync).and_then(|out| out.queued()) {
+                pacer.set_speed(rate.speed(queued), clock);
+            }
+            pacer.wait(clock);
+        }
+        if let Err(e) = sink.finish() {
+            eprintln!("{}", e);
+        }
+        if let (Some(path), Some(recorder)) = (&self.capture, recorder) {
+            let frames = recorder.frames();
+            match recorder.finish() {
+                Ok(()) => println!("{} frames captured to {}", frames, path),
+                Err(e) => eprintln!("{}: {}", path, e),
+            }
+        }
+        if let (Some(path), Some(movie)) = (&self.record, &movie) {
+            match movie.save(path) {
+                Ok(()) => println!("{} frames recorded to {}", movie.frames.len(), path),
+                Err(e) => eprintln!("{}: {}", path, e),
             }
         }
     }
 }
 
-fn get_mill_time() -> u128 {
-    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
+#[cfg(target_os = "linux")]
+fn open_sound_card() -> io::Result<Box<dyn AudioSink>> {
+    Ok(Box::new(AlsaSink::open(SAMPLE_RATE, AUDIO_LATENCY)?))
 }
 
-fn get_micro_time() -> u128 {
-    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros()
+#[cfg(not(target_os = "linux"))]
+fn open_sound_card() -> io::Result<Box<dyn AudioSink>> {
+    Err(io::Error::other("sound output is only supported on Linux"))
 }
 
-impl InvadersLaunch {
-    pub fn new() -> Self {
-        Self {}
+/// Run one frame, stopping in the monitor when the debugger hits something. False means quit.
+fn run_frame(machine: &mut InvadersMachine, monitor: Option<&mut ConsoleMonitor>) -> bool {
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => return machine.run_frame().map_err(|e| eprintln!("{}", e)).is_ok(),
+    };
+    let frame = machine.scheduler().frame();
+    let stop = monitor.debugger.run_until(machine, None, |m| m.scheduler().frame() != frame);
+    if stop == Stop::Done {
+        return true;
     }
+    monitor.report(stop).is_ok() && monitor.prompt(machine).unwrap_or(MonitorExit::Quit) == MonitorExit::Continue
 }
 
-fn init_address(video_arr: Rc<RefCell<Vec<u8>>>) -> io::Result<InvadersAddressBus> {
-    let mut arr_h = [0u8; 2048];
-    let mut h = File::open("./res/invaders.h")?;
-    h.read(&mut arr_h)?;
-
-    let mut arr_g = [0u8; 2048];
-    let mut g = File::open("./res/invaders.g")?;
-    g.read(&mut arr_g)?;
+/// The monitor without a window: `c` runs until the debugger stops again
+fn run_console(monitor: &mut ConsoleMonitor, machine: &mut InvadersMachine) {
+    while let Ok(MonitorExit::Continue) = monitor.prompt(machine) {
+        let stop = monitor.debugger.run_until(machine, None, |_| false);
+        if monitor.report(stop).is_err() {
+            return;
+        }
+    }
+}
 
-    let mut arr_f = [0u8; 2048];
-    let mut f = File::open("./res/invaders.f")?;
-    f.read(&mut arr_f)?;
 
-    let mut arr_e = [0u8; 2048];
-    let mut e = File::open("./res/invaders.e")?;
-    e.read(&mut arr_e)?;
+impl InvadersLaunch {
+    pub fn new(dip: InvadersDipSwitches) -> Self {
+        Self {
+            dip,
+            monitor: false,
+            window: true,
+            state: String::from("invaders.state"),
+            rewind: 32 << 20,
+            screenshots: String::from("."),
+            resume: None,
+            record: None,
+            play: None,
+            capture: None,
+            samples: None,
+            sound: None,
+            synth: true,
+            audio: false,
+            audio_sync: false,
+            fps: false,
+            overlay: Overlay::None,
+            artwork: None,
+        }
+    }
 
-    let addressing = InvadersAddressBus::new(
-        Box::new(arr_h), Box::new(arr_g), Box::new(arr_f), Box::new(arr_e), video_arr);
-    Ok(addressing)
-}
\ No newline at end of file
+    /// The sound card when playing sound, with the buffer half full of silence to start
+    fn open_speaker(&self) -> io::Result<Option<Box<dyn AudioSink>>> {
+        if !self.audio && !self.audio_sync {
+            return Ok(None);
+        }
+        let mut speaker = open_sound_card()?;
+        speaker.write(&[0; AUDIO_TARGET])?;
+        Ok(Some(speaker))
+    }
+}
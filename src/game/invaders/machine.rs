@@ -0,0 +1,152 @@
+use std::cell::RefCell;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::cpu::{Cpu, CpuError};
+use crate::debugger::Target;
+use crate::game::invaders::{InvadersAddressBus, InvadersDipSwitches, InvadersIO, Scheduler};
+use crate::util::{fnv1a, Snapshot, StateError, StateReader, StateWriter};
+
+/// Size of the video RAM at 0x2400
+pub const VIDEO_RAM_SIZE: usize = 7168;
+
+/// First bytes of an Invaders save state
+const STATE_MAGIC: &[u8; 8] = b"8080INVS";
+/// Bumped whenever the layout of the save state changes
+pub const STATE_VERSION: u16 = 1;
+/// Work RAM, video RAM and the RAM at 0x4000, everything the CPU can write
+const STATE_RAM: Range<u16> = 0x2000..0x4400;
+
+/// The whole Midway board: CPU, memory, IO and the beam scheduler
+pub struct InvadersMachine {
+    pub cpu: Cpu,
+    pub io: Rc<RefCell<InvadersIO>>,
+    pub video_arr: Rc<RefCell<Vec<u8>>>,
+    scheduler: Scheduler,
+}
+
+impl InvadersMachine {
+    pub fn new(addressing: InvadersAddressBus, video_arr: Rc<RefCell<Vec<u8>>>, dip: InvadersDipSwitches) -> Self {
+        let io = Rc::new(RefCell::new(InvadersIO::new()));
+        io.borrow_mut().set_dip_switches(dip);
+        let cpu = Cpu::new(Box::new(addressing), 0, io.clone());
+        Self {
+            cpu,
+            io,
+            video_arr,
+            scheduler: Scheduler::new(),
+        }
+    }
+
+    /// Load `invaders.h`, `.g`, `.f` and `.e` from `dir`
+    pub fn from_rom_dir(dir: &str, dip: InvadersDipSwitches) -> io::Result<Self> {
+        let video_arr = Rc::new(RefCell::new(vec![0u8; VIDEO_RAM_SIZE]));
+        let addressing = InvadersAddressBus::new(
+            read_rom(dir, "h")?, read_rom(dir, "g")?, read_rom(dir, "f")?, read_rom(dir, "e")?,
+            video_arr.clone());
+        Ok(Self::new(addressing, video_arr, dip))
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Execute one instruction and raise the interrupt the beam reached, if any
+    pub fn step(&mut self) -> Result<u8, CpuError> {
+        // 出错的指令也执行完了, 时钟照样要走
+        let result = self.cpu.next();
+        if let Some(beam) = self.scheduler.tick(u32::from(self.cpu.last_cycles())) {
+            // 新的中断覆盖还没响应的那个, 总线上的 RST 取决于当前的扫描线
+            self.cpu.interrupt(beam.rst());
+        }
+        self.io.borrow_mut().stamp_sound(self.scheduler.cycles());
+        result
+    }
+
+    /// Snapshot of everything that changes while the game runs: CPU, IO, beam position and RAM.
+    /// The ROMs and DIP switches are not included
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        w.bytes(STATE_MAGIC);
+        w.u16(STATE_VERSION);
+        self.cpu.save(&mut w);
+        self.io.borrow().save(&mut w);
+        self.scheduler.save(&mut w);
+        for addr in STATE_RAM {
+            w.u8(self.cpu.addring.get_mem(addr));
+        }
+        w.into_bytes()
+    }
+
+    /// Restore a `save_state`. Nothing changes when the state is rejected
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::new(state);
+        if r.bytes(STATE_MAGIC.len()).ok() != Some(&STATE_MAGIC[..]) {
+            return Err(StateError::BadMagic);
+        }
+        let version = r.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::Version(version));
+        }
+        // 格式是定长的, 先检查长度, 免得读到一半失败
+        let expected = self.save_state().len();
+        if state.len() != expected {
+            return Err(StateError::Length { expected, found: state.len() });
+        }
+        self.cpu.load(&mut r)?;
+        self.io.borrow_mut().load(&mut r)?;
+        self.scheduler.load(&mut r)?;
+        for addr in STATE_RAM {
+            self.cpu.addring.set_mem(addr, r.u8()?);
+        }
+        Ok(())
+    }
+
+    /// FNV-1a of all the RAM, the frame buffer included
+    pub fn ram_hash(&self) -> u64 {
+        let ram: Vec<u8> = STATE_RAM.map(|addr| self.cpu.addring.get_mem(addr)).collect();
+        fnv1a(&ram)
+    }
+
+    pub fn save_state_file(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.save_state())
+    }
+
+    pub fn load_state_file(&mut self, path: &str) -> Result<(), StateError> {
+        let state = fs::read(path)?;
+        self.load_state(&state)
+    }
+
+    /// Run until the beam wraps back to line 0
+    pub fn run_frame(&mut self) -> Result<(), CpuError> {
+        let frame = self.scheduler.frame();
+        while self.scheduler.frame() == frame {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+impl Target for InvadersMachine {
+    fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    fn step(&mut self) -> Result<u8, CpuError> {
+        InvadersMachine::step(self)
+    }
+}
+
+fn read_rom(dir: &str, name: &str) -> io::Result<Box<[u8; 2048]>> {
+    let mut arr = Box::new([0u8; 2048]);
+    File::open(format!("{}/invaders.{}", dir, name))?.read_exact(&mut arr[..])?;
+    Ok(arr)
+}
@@ -1,7 +1,34 @@
+mod artwork;
+mod capture;
 mod gameio;
 mod launch;
 mod display;
+mod dip;
+mod headless;
+mod machine;
+mod movie;
+mod overlay;
+mod rewind;
+mod scheduler;
+mod screen;
+mod shifter;
+mod sound;
+mod synth;
 pub mod siaddressing;
 
+pub use artwork::{Artwork, MAX_ARTWORK_SIZE, Rect};
+pub use capture::{Recorder, wav_path};
+pub use dip::{ExtraShip, InvadersDipSwitches};
+pub use gameio::{InvadersButton, InvadersIO, SoundWrite};
+pub use headless::{Headless, HeadlessLaunch, InputScript};
 pub use launch::InvadersLaunch;
+pub use machine::{InvadersMachine, STATE_VERSION, VIDEO_RAM_SIZE};
+pub use movie::{CHECKPOINT_INTERVAL, Movie, MOVIE_VERSION, MovieFrame, MoviePlayer, PlaybackError};
+pub use overlay::{blend, Overlay, Region};
+pub use rewind::Rewind;
+pub use scheduler::{Beam, Scheduler, CPU_HZ, CYCLES_PER_FRAME, FRAME_RATE};
+pub use screen::{render, render_masked, screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use shifter::ShiftRegister;
+pub use sound::{InvadersSound, SAMPLE_COUNT, sample_at};
+pub use synth::Synth;
 pub use siaddressing::InvadersAddressBus;
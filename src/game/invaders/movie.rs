@@ -0,0 +1,195 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::cpu::CpuError;
+use crate::game::invaders::{InvadersDipSwitches, InvadersMachine};
+use crate::util::{StateError, StateReader, StateWriter};
+
+/// First bytes of an Invaders movie
+const MOVIE_MAGIC: &[u8; 8] = b"8080INVM";
+/// Bumped whenever the layout of the movie changes
+pub const MOVIE_VERSION: u16 = 1;
+/// Frames between two RAM hashes
+pub const CHECKPOINT_INTERVAL: usize = 60;
+
+/// One recorded frame: what input ports 0-2 held while it ran
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub buttons: [u8; 3],
+    /// `InvadersMachine::ram_hash` after the frame, every `CHECKPOINT_INTERVAL` frames
+    pub hash: Option<u64>,
+}
+
+/// A recorded run: the DIP switches, where it started and the buttons of every frame.
+/// Played back on the same ROMs it gives the same game, frame for frame
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub dip: InvadersDipSwitches,
+    /// A save state, or `None` for power on
+    pub start: Option<Vec<u8>>,
+    pub frames: Vec<MovieFrame>,
+}
+
+/// Why playback stopped early
+#[derive(Debug)]
+pub enum PlaybackError {
+    Cpu(CpuError),
+    /// The RAM at a checkpoint is not what was recorded, the run went a different way
+    Desync { frame: usize, expected: u64, found: u64 },
+}
+
+impl fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlaybackError::Cpu(e) => write!(f, "{}", e),
+            PlaybackError::Desync { frame, expected, found } =>
+                write!(f, "desync at frame {}: RAM hash {:016x}, recorded {:016x}", frame, found, expected),
+        }
+    }
+}
+
+impl Error for PlaybackError {}
+
+impl Movie {
+    /// Start recording a machine that was just powered on
+    pub fn power_on(dip: InvadersDipSwitches) -> Self {
+        Self {
+            dip,
+            start: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Start recording `machine` from where it is now
+    pub fn from_machine(dip: InvadersDipSwitches, machine: &InvadersMachine) -> Self {
+        Self {
+            dip,
+            start: Some(machine.save_state()),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Record the frame `machine` just ran. Buttons only change between frames,
+    /// so what the ports hold now is what the frame saw
+    pub fn record(&mut self, machine: &InvadersMachine) {
+        let frame = self.frames.len() + 1;
+        self.frames.push(MovieFrame {
+            buttons: machine.io.borrow().buttons(),
+            hash: if frame.is_multiple_of(CHECKPOINT_INTERVAL) { Some(machine.ram_hash()) } else { None },
+        });
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        w.bytes(MOVIE_MAGIC);
+        w.u16(MOVIE_VERSION);
+        w.u8(self.dip.port2_bits());
+        match &self.start {
+            Some(state) => {
+                w.bool(true);
+                w.u32(state.len() as u32);
+                w.bytes(state);
+            }
+            None => w.bool(false),
+        }
+        w.u32(self.frames.len() as u32);
+        for frame in &self.frames {
+            w.bytes(&frame.buttons);
+            w.bool(frame.hash.is_some());
+            if let Some(hash) = frame.hash {
+                w.u64(hash);
+            }
+        }
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, StateError> {
+        let mut r = StateReader::new(data);
+        if r.bytes(MOVIE_MAGIC.len()).ok() != Some(&MOVIE_MAGIC[..]) {
+            return Err(StateError::BadMagic);
+        }
+        let version = r.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(StateError::Version(version));
+        }
+        let dip = InvadersDipSwitches::from_port2_bits(r.u8()?);
+        let start = if r.bool()? {
+            let len = r.u32()? as usize;
+            Some(r.bytes(len)?.to_vec())
+        } else {
+            None
+        };
+        let count = r.u32()? as usize;
+        let mut frames = Vec::with_capacity(count.min(r.remaining()));
+        for _ in 0..count {
+            let mut buttons = [0u8; 3];
+            buttons.copy_from_slice(r.bytes(3)?);
+            let hash = if r.bool()? { Some(r.u64()?) } else { None };
+            frames.push(MovieFrame { buttons, hash });
+        }
+        if r.remaining() != 0 {
+            return Err(StateError::Length { expected: data.len() - r.remaining(), found: data.len() });
+        }
+        Ok(Self { dip, start, frames })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &str) -> Result<Self, StateError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// Feeds a `Movie` into a machine frame by frame and checks the checkpoints
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        Self { movie, frame: 0 }
+    }
+
+    /// Set up the machine to play from the first frame.
+    /// A power on movie needs a machine that has not run yet
+    pub fn start(&mut self, machine: &mut InvadersMachine) -> Result<(), StateError> {
+        machine.io.borrow_mut().set_dip_switches(self.movie.dip);
+        if let Some(state) = &self.movie.start {
+            machine.load_state(state)?;
+        }
+        self.frame = 0;
+        Ok(())
+    }
+
+    /// Run the next recorded frame, `Ok(false)` when the movie is over
+    pub fn step(&mut self, machine: &mut InvadersMachine) -> Result<bool, PlaybackError> {
+        let recorded = match self.movie.frames.get(self.frame) {
+            Some(frame) => *frame,
+            None => return Ok(false),
+        };
+        machine.io.borrow_mut().set_buttons(recorded.buttons);
+        machine.run_frame().map_err(PlaybackError::Cpu)?;
+        self.frame += 1;
+        if let Some(expected) = recorded.hash {
+            let found = machine.ram_hash();
+            if found != expected {
+                return Err(PlaybackError::Desync { frame: self.frame, expected, found });
+            }
+        }
+        Ok(true)
+    }
+
+    /// Frames played so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs;
+
+use crate::game::invaders::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::image::read_png;
+
+const WHITE: u32 = 0x00ff_ffff;
+const RED: u32 = 0x00ff_2020;
+const GREEN: u32 = 0x0020_ff20;
+const CYAN: u32 = 0x0020_ffff;
+const MAGENTA: u32 = 0x00ff_20ff;
+const YELLOW: u32 = 0x00ff_ff20;
+
+/// A strip of coloured gel, in pixels from the top left of the upright screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// 0RGB
+    pub color: u32,
+}
+
+impl Region {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize, color: u32) -> Self {
+        Self { x, y, width, height, color }
+    }
+}
+
+/// The red UFO strip at the top and the green strip over the shields and the bases
+const MIDWAY: [Region; 3] = [
+    Region::new(0, 32, 224, 32, RED),
+    Region::new(0, 184, 224, 56, GREEN),
+    // 只盖住剩余的炮台, 右边的投币数还是白色
+    Region::new(16, 240, 118, 16, GREEN),
+];
+
+/// The Taito table has a band of colour for each part of the playfield
+const TAITO: [Region; 6] = [
+    Region::new(0, 0, 224, 32, CYAN),
+    Region::new(0, 32, 224, 32, RED),
+    Region::new(0, 64, 224, 56, MAGENTA),
+    Region::new(0, 120, 224, 64, YELLOW),
+    Region::new(0, 184, 224, 56, GREEN),
+    Region::new(0, 240, 224, 16, CYAN),
+];
+
+/// The cellophane on the monitor glass. The game only draws white, the gel gives each part
+/// of the screen its colour
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Overlay {
+    /// Black and white
+    #[default]
+    None,
+    /// The Midway upright cabinet
+    Midway,
+    /// The Taito cocktail table
+    Taito,
+    /// Strips of gel, later ones on top, white where there is none
+    Regions(Vec<Region>),
+    /// The colour of each pixel, `SCREEN_WIDTH * SCREEN_HEIGHT` of ARGB. Transparent is white
+    Image(Vec<u32>),
+}
+
+impl Overlay {
+    /// `none`, `midway`, `taito`, a `.png` of the screen's size, or a region list file
+    pub fn from_arg(arg: &str) -> Result<Self, String> {
+        match arg {
+            "none" => Ok(Overlay::None),
+            "midway" => Ok(Overlay::Midway),
+            "taito" => Ok(Overlay::Taito),
+            path if path.to_ascii_lowercase().ends_with(".png") => {
+                let image = read_png(path)?;
+                if (image.width, image.height) != (SCREEN_WIDTH, SCREEN_HEIGHT) {
+                    return Err(format!("{}: the overlay must be {}x{}", path, SCREEN_WIDTH, SCREEN_HEIGHT));
+                }
+                Ok(Overlay::Image(image.pixels))
+            }
+            path => {
+                let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+                Self::parse_regions(&text).map(Overlay::Regions).map_err(|e| format!("{}: {}", path, e))
+            }
+        }
+    }
+
+    /// One region per line: `X Y WIDTH HEIGHT RRGGBB`, `#` starts a comment
+    pub fn parse_regions(text: &str) -> Result<Vec<Region>, String> {
+        let mut regions = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let error = |what: &str| format!("line {}: {}", n + 1, what);
+            if words.len() != 5 {
+                return Err(error("expected X Y WIDTH HEIGHT RRGGBB"));
+            }
+            let mut numbers = [0usize; 4];
+            for (number, word) in numbers.iter_mut().zip(&words) {
+                *number = word.parse().map_err(|_| error("bad number"))?;
+            }
+            let color = u32::from_str_radix(words[4], 16)
+                .ok()
+                .filter(|_| words[4].len() == 6)
+                .ok_or_else(|| error("bad colour, expected RRGGBB"))?;
+            let [x, y, width, height] = numbers;
+            regions.push(Region::new(x, y, width, height, color));
+        }
+        Ok(regions)
+    }
+
+    /// The colour a lit pixel shows at each spot of the screen, 0RGB
+    pub fn mask(&self) -> Vec<u32> {
+        let mut mask = vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let regions: &[Region] = match self {
+            Overlay::None => &[],
+            Overlay::Midway => &MIDWAY,
+            Overlay::Taito => &TAITO,
+            Overlay::Regions(regions) => regions,
+            Overlay::Image(pixels) => {
+                for (m, &p) in mask.iter_mut().zip(pixels) {
+                    *m = blend(WHITE, p);
+                }
+                return mask;
+            }
+        };
+        for r in regions {
+            for y in r.y.min(SCREEN_HEIGHT)..r.y.saturating_add(r.height).min(SCREEN_HEIGHT) {
+                let row = &mut mask[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+                row[r.x.min(SCREEN_WIDTH)..r.x.saturating_add(r.width).min(SCREEN_WIDTH)].fill(r.color & WHITE);
+            }
+        }
+        mask
+    }
+}
+
+/// `over` in ARGB on top of the 0RGB `under`
+pub fn blend(under: u32, over: u32) -> u32 {
+    let alpha = over >> 24;
+    let channel = |shift: u32| {
+        let (a, b) = (under >> shift & 0xff, over >> shift & 0xff);
+        (a * (255 - alpha) + b * alpha) / 255
+    };
+    channel(16) << 16 | channel(8) << 8 | channel(0)
+}
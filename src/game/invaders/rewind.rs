@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+
+/// The last frames as a chain of save state deltas in a fixed memory budget.
+///
+/// Only the newest state is kept whole. Each older frame is the XOR with the frame after it,
+/// run-length encoded: most of the RAM does not change between two frames, so a delta is mostly
+/// zero runs. When the budget is full the oldest frames are dropped.
+pub struct Rewind {
+    head: Option<Vec<u8>>,
+    /// Oldest first, `deltas[i]` turns frame i+1 back into frame i
+    deltas: VecDeque<Vec<u8>>,
+    used: usize,
+    budget: usize,
+}
+
+impl Rewind {
+    /// `budget` bytes for the deltas
+    pub fn new(budget: usize) -> Self {
+        Self {
+            head: None,
+            deltas: VecDeque::new(),
+            used: 0,
+            budget,
+        }
+    }
+
+    /// Record the state of a new frame
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(head) = self.head.take() {
+            if head.len() == state.len() {
+                let delta = encode(&head, &state);
+                self.used += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                self.clear();
+            }
+        }
+        self.head = Some(state);
+        while self.used > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Step one frame back: the state before the newest one, which is dropped
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        self.used -= delta.len();
+        let head = self.head.as_mut()?;
+        decode(&delta, head);
+        Some(head.clone())
+    }
+
+    /// Frames `pop` can go back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Bytes the deltas take
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn clear(&mut self) {
+        self.head = None;
+        self.deltas.clear();
+        self.used = 0;
+    }
+}
+
+/// Zero runs shorter than this stay inside a literal run
+const MIN_ZERO_RUN: usize = 3;
+
+/// `old ^ new` as pairs of (zero run, literal run) lengths, each followed by the literal bytes
+fn encode(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = old.iter().zip(new).map(|(a, b)| a ^ b).collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < xor.len() {
+        let zeros = xor[i..].iter().take_while(|&&b| b == 0).count();
+        i += zeros;
+        let start = i;
+        while i < xor.len() && !xor[i..].iter().take(MIN_ZERO_RUN).all(|&b| b == 0) {
+            i += 1;
+        }
+        write_len(&mut out, zeros);
+        write_len(&mut out, i - start);
+        out.extend(&xor[start..i]);
+    }
+    out
+}
+
+/// XOR the delta back into `state`
+fn decode(delta: &[u8], state: &mut [u8]) {
+    let mut pos = 0;
+    let mut at = 0;
+    while pos < delta.len() {
+        at += read_len(delta, &mut pos);
+        let literal = read_len(delta, &mut pos);
+        for (b, x) in state[at..at + literal].iter_mut().zip(&delta[pos..pos + literal]) {
+            *b ^= x;
+        }
+        pos += literal;
+        at += literal;
+    }
+}
+
+/// LEB128, 7 bits a byte
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+}
+
+fn read_len(data: &[u8], pos: &mut usize) -> usize {
+    let mut len = 0;
+    let mut shift = 0;
+    loop {
+        let b = data[*pos];
+        *pos += 1;
+        len |= usize::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return len;
+        }
+        shift += 7;
+    }
+}
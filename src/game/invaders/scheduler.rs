@@ -0,0 +1,117 @@
+use crate::util::{Snapshot, StateError, StateReader, StateWriter};
+
+/// 8080 clock of the Midway board
+pub const CPU_HZ: u32 = 2_000_000;
+pub const FRAME_RATE: u32 = 60;
+pub const CYCLES_PER_FRAME: u32 = CPU_HZ / FRAME_RATE;
+/// 224 visible lines plus the vertical blank
+pub const LINES_PER_FRAME: u32 = 262;
+/// RST 1 fires when the beam reaches this line
+pub const MID_SCREEN_LINE: u32 = 96;
+/// RST 2 fires when the beam reaches this line
+pub const VBLANK_LINE: u32 = 224;
+
+/// RST 1
+pub const MID_SCREEN_RST: u8 = 0xcf;
+/// RST 2
+pub const VBLANK_RST: u8 = 0xd7;
+
+/// Where the beam is when an interrupt is raised
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Beam {
+    MidScreen,
+    VBlank,
+}
+
+impl Beam {
+    /// The instruction the board puts on the data bus
+    pub fn rst(self) -> u8 {
+        match self {
+            Beam::MidScreen => MID_SCREEN_RST,
+            Beam::VBlank => VBLANK_RST,
+        }
+    }
+}
+
+/// Scanline clock driven by the CPU cycle count.
+///
+/// Cycles past the end of a frame carry into the next one, so the interrupt
+/// points only depend on what the CPU executed, never on the host.
+pub struct Scheduler {
+    /// Cycle position within the current frame
+    cycle: u32,
+    frame: u64,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            cycle: 0,
+            frame: 0,
+        }
+    }
+
+    /// The first cycle of `line`
+    pub fn line_cycle(line: u32) -> u32 {
+        CYCLES_PER_FRAME * line / LINES_PER_FRAME
+    }
+
+    pub fn line(&self) -> u32 {
+        self.cycle * LINES_PER_FRAME / CYCLES_PER_FRAME
+    }
+
+    pub fn cycle(&self) -> u32 {
+        self.cycle
+    }
+
+    /// Cycles since power on
+    pub fn cycles(&self) -> u64 {
+        self.frame * u64::from(CYCLES_PER_FRAME) + u64::from(self.cycle)
+    }
+
+    /// Frames completed since power on
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Advance the beam by `cycles`, returning the interrupt raised on the way if any
+    pub fn tick(&mut self, cycles: u32) -> Option<Beam> {
+        let before = self.cycle;
+        self.cycle += cycles;
+        let crossed = |line: u32| {
+            let at = Self::line_cycle(line);
+            before < at && self.cycle >= at
+        };
+        let beam = if crossed(MID_SCREEN_LINE) {
+            Some(Beam::MidScreen)
+        } else if crossed(VBLANK_LINE) {
+            Some(Beam::VBlank)
+        } else {
+            None
+        };
+        if self.cycle >= CYCLES_PER_FRAME {
+            self.cycle -= CYCLES_PER_FRAME;
+            self.frame += 1;
+        }
+        beam
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for Scheduler {
+    fn save(&self, w: &mut StateWriter) {
+        w.u32(self.cycle);
+        w.u64(self.frame);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.cycle = r.u32()? % CYCLES_PER_FRAME;
+        self.frame = r.u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+/// The monitor is turned 90° counter-clockwise in the cabinet: 224 pixels wide, 256 high
+pub const SCREEN_WIDTH: usize = 224;
+pub const SCREEN_HEIGHT: usize = 256;
+
+const ON: u32 = 0x00ff_ffff;
+const OFF: u32 = 0;
+
+/// Draw video RAM into `buffer`, `SCREEN_WIDTH * SCREEN_HEIGHT` pixels of 0RGB, white on black
+pub fn render(video: &[u8], buffer: &mut [u32]) {
+    draw(video, buffer, |_| ON);
+}
+
+/// Draw video RAM with each lit pixel taking its colour from `mask`, see `Overlay::mask`
+pub fn render_masked(video: &[u8], buffer: &mut [u32], mask: &[u32]) {
+    draw(video, buffer, |index| mask[index]);
+}
+
+fn draw(video: &[u8], buffer: &mut [u32], lit: impl Fn(usize) -> u32) {
+    for (i, byte) in video.iter().enumerate() {
+        // 显存按列存放, 每列从屏幕底部往上
+        for bit in 0..8 {
+            let point = i * 8 + bit;
+            let x = point / SCREEN_HEIGHT;
+            let y = SCREEN_HEIGHT - 1 - (point % SCREEN_HEIGHT);
+            let index = y * SCREEN_WIDTH + x;
+            buffer[index] = if byte & (1 << bit) != 0 { lit(index) } else { OFF };
+        }
+    }
+}
+
+/// The screen as a new buffer
+pub fn screen(video: &[u8]) -> Vec<u32> {
+    let mut buffer = vec![OFF; SCREEN_WIDTH * SCREEN_HEIGHT];
+    render(video, &mut buffer);
+    buffer
+}
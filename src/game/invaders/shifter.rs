@@ -0,0 +1,45 @@
+use crate::util::{Snapshot, StateError, StateReader, StateWriter};
+
+/// The 16-bit hardware shift register (MB14241) on the Midway board.
+///
+/// `OUT 4` pushes a byte into the high half, `OUT 2` sets the shift amount
+/// and `IN 3` reads 8 bits starting at that offset.
+#[derive(Default)]
+pub struct ShiftRegister {
+    value: u16,
+    offset: u8,
+}
+
+impl ShiftRegister {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// OUT 4: the new byte becomes the high byte, the old high byte moves to the low byte
+    pub fn push(&mut self, data: u8) {
+        self.value = (self.value >> 8) | (u16::from(data) << 8);
+    }
+
+    /// OUT 2: only the low 3 bits are wired
+    pub fn set_offset(&mut self, offset: u8) {
+        self.offset = offset & 0b0000_0111;
+    }
+
+    /// IN 3
+    pub fn read(&self) -> u8 {
+        (self.value >> (8 - self.offset)) as u8
+    }
+}
+
+impl Snapshot for ShiftRegister {
+    fn save(&self, w: &mut StateWriter) {
+        w.u16(self.value);
+        w.u8(self.offset);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.value = r.u16()?;
+        self.set_offset(r.u8()?);
+        Ok(())
+    }
+}
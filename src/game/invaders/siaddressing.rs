@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::memory::{AddressBus, Memory, ReadOnly, Video, Work};
+use crate::memory::{AddressBus, BusError, Memory, ReadOnly, Video, Work};
 
 pub struct InvadersAddressBus {
     read_only_h: ReadOnly,
@@ -14,21 +14,29 @@ pub struct InvadersAddressBus {
 }
 
 impl AddressBus for InvadersAddressBus {
+    /// 未映射的地址读到 0xFF
     fn get_mem(&self, addr: u16) -> u8 {
-        let value = match addr {
+        self.read(addr).unwrap_or(0xff)
+    }
+
+    fn set_mem(&mut self, addr: u16, val: u8) {
+        let _ = self.write(addr, val);
+    }
+
+    fn read(&self, addr: u16) -> Result<u8, BusError> {
+        match addr {
             0x0000..=0x07ff => self.read_only_h.get(addr),
             0x0800..=0x0fff => self.read_only_g.get(addr),
             0x1000..=0x17ff => self.read_only_f.get(addr),
             0x1800..=0x1fff => self.read_only_e.get(addr),
             0x2000..=0x23ff => self.work_ram.get(addr),
             0x2400..=0x3fff => self.video_ram.get(addr),
-            0x4000..=0xFFFF => self.work_ram2.get(addr),
-            _n => panic!("address not support.")
-        };
-        value
+            0x4000..=0x43ff => self.work_ram2.get(addr),
+            _ => Err(BusError::Unmapped { addr }),
+        }
     }
 
-    fn set_mem(&mut self, addr: u16, val: u8) {
+    fn write(&mut self, addr: u16, val: u8) -> Result<(), BusError> {
         match addr {
             0x0000..=0x07ff => self.read_only_h.set(addr, val),
             0x0800..=0x0fff => self.read_only_g.set(addr, val),
@@ -36,8 +44,8 @@ impl AddressBus for InvadersAddressBus {
             0x1800..=0x1fff => self.read_only_e.set(addr, val),
             0x2000..=0x23ff => self.work_ram.set(addr, val),
             0x2400..=0x3fff => self.video_ram.set(addr, val),
-            0x4000..=0xFFFF => self.work_ram2.set(addr, val),
-            _n => println!("Unsupport Address {:X}", _n)
+            0x4000..=0x43ff => self.work_ram2.set(addr, val),
+            _ => Err(BusError::Unmapped { addr }),
         }
     }
 }
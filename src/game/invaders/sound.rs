@@ -0,0 +1,194 @@
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+use crate::audio::{AudioSink, SAMPLE_RATE, Wav};
+use crate::game::invaders::{CYCLES_PER_FRAME, FRAME_RATE, SoundWrite, Synth};
+
+/// Samples in the usual set, `0.wav` to `8.wav`, plus `9.wav` for the extra life if there is one
+pub const SAMPLE_COUNT: usize = 10;
+
+/// Port 3 bit 5 turns the amplifier on
+const AMP_ENABLE: u8 = 0b0010_0000;
+
+/// Which sample each bit of ports 3 and 5 starts. Only the UFO keeps going while its bit is set
+const PORT3_SAMPLES: [Option<usize>; 8] = [Some(0), Some(1), Some(2), Some(3), Some(9), None, None, None];
+const PORT5_SAMPLES: [Option<usize>; 8] = [Some(4), Some(5), Some(6), Some(7), Some(8), None, None, None];
+const UFO: usize = 0;
+
+/// The sample at `cycle`. A frame is exactly 1/60 s of sound, so audio and video never drift apart
+pub fn sample_at(cycle: u64) -> u64 {
+    cycle * u64::from(SAMPLE_RATE) / (u64::from(CYCLES_PER_FRAME) * u64::from(FRAME_RATE))
+}
+
+#[derive(Clone, Debug)]
+enum Source {
+    Sample { data: Rc<[i16]>, pos: usize },
+    Synth(Synth),
+}
+
+#[derive(Clone, Debug)]
+struct Voice {
+    effect: usize,
+    source: Source,
+    looping: bool,
+}
+
+impl Voice {
+    /// The next sample, `None` once a voice that does not loop is over
+    fn next_sample(&mut self) -> Option<i16> {
+        match &mut self.source {
+            Source::Sample { data, pos } => {
+                if *pos == data.len() {
+                    if !self.looping || data.is_empty() {
+                        return None;
+                    }
+                    *pos = 0;
+                }
+                *pos += 1;
+                Some(data[*pos - 1])
+            }
+            Source::Synth(synth) => synth.next_sample(),
+        }
+    }
+}
+
+/// The cabinet's sound effects, started and stopped by the edges of the bits the game writes to
+/// ports 3 and 5. Each effect plays its sample, or is synthesized when it has none and synthesis is on
+pub struct InvadersSound {
+    samples: Vec<Option<Rc<[i16]>>>,
+    synth: bool,
+    voices: Vec<Voice>,
+    /// 端口 3 和 5 上次写入的值
+    latches: [u8; 2],
+    /// Cycle the sound has been mixed up to
+    cycle: u64,
+}
+
+impl InvadersSound {
+    /// No samples yet, every effect is silent
+    pub fn new() -> Self {
+        Self {
+            samples: vec![None; SAMPLE_COUNT],
+            synth: false,
+            voices: Vec::new(),
+            latches: [0; 2],
+            cycle: 0,
+        }
+    }
+
+    /// Load `0.wav`... from `dir`, returning how many were found. Missing ones stay silent
+    pub fn load_samples(&mut self, dir: &str) -> io::Result<usize> {
+        fs::read_dir(dir)?;
+        let mut found = 0;
+        for index in 0..SAMPLE_COUNT {
+            let path = format!("{}/{}.wav", dir, index);
+            let data = match fs::read(&path) {
+                Ok(data) => data,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let wav = Wav::parse(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+            self.set_sample(index, wav.mono(SAMPLE_RATE));
+            found += 1;
+        }
+        Ok(found)
+    }
+
+    /// Mono samples at `SAMPLE_RATE` for effect `index`
+    pub fn set_sample(&mut self, index: usize, samples: Vec<i16>) {
+        self.samples[index] = Some(samples.into());
+    }
+
+    /// Make up the effects that have no sample, see `Synth`
+    pub fn set_synth(&mut self, on: bool) {
+        self.synth = on;
+    }
+
+    /// Effects playing right now
+    pub fn voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Start over at `cycle` with nothing playing, after a state load or a rewind
+    pub fn sync(&mut self, cycle: u64) {
+        self.voices.clear();
+        self.cycle = cycle;
+    }
+
+    /// Mix the sound up to `cycle`, applying `writes` where they happened
+    pub fn mix(&mut self, writes: &[SoundWrite], cycle: u64) -> Vec<i16> {
+        let mut out = Vec::new();
+        for write in writes {
+            self.mix_until(write.cycle.min(cycle), &mut out);
+            self.write(write.port, write.value);
+        }
+        self.mix_until(cycle, &mut out);
+        out
+    }
+
+    /// `mix` straight into a sink
+    pub fn run(&mut self, writes: &[SoundWrite], cycle: u64, sink: &mut dyn AudioSink) -> io::Result<()> {
+        let out = self.mix(writes, cycle);
+        sink.write(&out)
+    }
+
+    fn mix_until(&mut self, cycle: u64, out: &mut Vec<i16>) {
+        if cycle <= self.cycle {
+            return;
+        }
+        let count = (sample_at(cycle) - sample_at(self.cycle)) as usize;
+        self.cycle = cycle;
+        let muted = self.latches[0] & AMP_ENABLE == 0;
+        let start = out.len();
+        out.resize(start + count, 0);
+        self.voices.retain_mut(|voice| {
+            for slot in &mut out[start..] {
+                match voice.next_sample() {
+                    Some(sample) if !muted => *slot = slot.saturating_add(sample),
+                    Some(_) => {}
+                    None => return false,
+                }
+            }
+            true
+        });
+    }
+
+    /// Latch a port write: rising edges start their sample, the UFO stops when its bit falls
+    fn write(&mut self, port: u8, value: u8) {
+        let (latch, table) = match port {
+            3 => (0, &PORT3_SAMPLES),
+            5 => (1, &PORT5_SAMPLES),
+            _ => return,
+        };
+        let rising = value & !self.latches[latch];
+        let falling = !value & self.latches[latch];
+        self.latches[latch] = value;
+        for (bit, sample) in table.iter().enumerate() {
+            let sample = match sample {
+                Some(sample) => *sample,
+                None => continue,
+            };
+            if falling & 1 << bit != 0 && sample == UFO {
+                self.voices.retain(|v| v.effect != UFO);
+            }
+            if rising & 1 << bit == 0 {
+                continue;
+            }
+            let source = match &self.samples[sample] {
+                Some(data) => Source::Sample { data: data.clone(), pos: 0 },
+                None if self.synth => Source::Synth(Synth::new(sample)),
+                None => continue,
+            };
+            // 重新触发就从头放, 不叠加
+            self.voices.retain(|v| v.effect != sample);
+            self.voices.push(Voice { effect: sample, source, looping: sample == UFO });
+        }
+    }
+}
+
+impl Default for InvadersSound {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,87 @@
+use crate::audio::SAMPLE_RATE;
+
+/// One effect made up as it plays, a rough stand-in for the discrete circuit behind its bit.
+/// Effects are numbered like the samples: 0 UFO, 1 shot, 2 player hit, 3 invader hit,
+/// 4-7 fleet march, 8 UFO hit, 9 extra life
+#[derive(Clone, Debug)]
+pub struct Synth {
+    effect: usize,
+    /// Samples played so far and how many there are, `None` for the UFO
+    t: u32,
+    end: Option<u32>,
+    /// 振荡器相位, 0..1
+    phase: f32,
+    noise: u16,
+    /// Low pass filtered noise
+    filtered: f32,
+}
+
+/// The march notes in Hz, one for each fleet bit
+const FLEET_NOTES: [f32; 4] = [94.0, 84.0, 75.0, 67.0];
+
+impl Synth {
+    pub fn new(effect: usize) -> Self {
+        let end = Self::length(effect).map(|secs| (secs * SAMPLE_RATE as f32).round() as u32);
+        Self { effect, t: 0, end, phase: 0.0, noise: 0xace1, filtered: 0.0 }
+    }
+
+    /// Seconds an effect lasts, `None` for the UFO which goes on until its bit falls
+    pub fn length(effect: usize) -> Option<f32> {
+        match effect {
+            0 => None,
+            1 => Some(0.4),
+            2 => Some(1.2),
+            3 => Some(0.3),
+            4..=7 => Some(0.12),
+            8 => Some(0.9),
+            _ => Some(0.8),
+        }
+    }
+
+    /// The next sample, `None` once the effect is over
+    pub fn next_sample(&mut self) -> Option<i16> {
+        if self.end.is_some_and(|end| self.t >= end) {
+            return None;
+        }
+        let time = self.t as f32 / SAMPLE_RATE as f32;
+        let fade = self.end.map_or(1.0, |end| 1.0 - self.t as f32 / end as f32);
+        self.t += 1;
+        let value = match self.effect {
+            // 频率在 300 和 700 Hz 之间每秒来回 8 次
+            0 => 0.2 * self.square(500.0 + 200.0 * triangle(time * 8.0)),
+            // 下降的音调加一点噪声
+            1 => fade * fade * (0.3 * self.square(1000.0 - 1800.0 * time) + 0.15 * self.rumble(0.5)),
+            2 => fade * self.rumble(0.08) * 0.9,
+            3 => fade * fade * (0.5 * self.rumble(0.3) + 0.2 * self.square(400.0 - 800.0 * time)),
+            4..=7 => fade * 0.5 * self.square(FLEET_NOTES[self.effect - 4]),
+            // 快速颤音
+            8 => fade * 0.35 * self.square(800.0 + 300.0 * triangle(time * 15.0)),
+            // 哔哔声, 每秒 8 次
+            _ => if (time * 8.0).fract() < 0.5 { 0.3 * self.square(1000.0) } else { 0.0 },
+        };
+        Some((value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+    }
+
+    /// A square wave at `freq`, +-1
+    fn square(&mut self, freq: f32) -> f32 {
+        self.phase = (self.phase + freq.max(20.0) / SAMPLE_RATE as f32).fract();
+        if self.phase < 0.5 { 1.0 } else { -1.0 }
+    }
+
+    /// White noise through a one pole low pass, smaller `cutoff` is darker. Louder than the plain noise
+    /// for the low ones, so explosions keep their weight
+    fn rumble(&mut self, cutoff: f32) -> f32 {
+        // 16 位 LFSR, 抽头 16 14 13 11
+        let bit = (self.noise ^ self.noise >> 2 ^ self.noise >> 3 ^ self.noise >> 5) & 1;
+        self.noise = self.noise >> 1 | bit << 15;
+        let white = if self.noise & 1 == 0 { 1.0 } else { -1.0 };
+        self.filtered += cutoff * (white - self.filtered);
+        self.filtered / cutoff.sqrt()
+    }
+}
+
+/// 0..1..0 over each whole number
+fn triangle(x: f32) -> f32 {
+    let f = x.fract();
+    if f < 0.5 { f * 2.0 } else { 2.0 - f * 2.0 }
+}
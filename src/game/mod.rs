@@ -2,4 +2,6 @@ pub use invaders::InvadersLaunch;
 pub use launch::Launch;
 
 mod launch;
-mod invaders;
\ No newline at end of file
+pub mod altair;
+pub mod cpm;
+pub mod invaders;
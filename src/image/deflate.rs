@@ -0,0 +1,134 @@
+/// Match lengths 3..=258 by length code 257..=285: base and extra bits
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+                                131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+/// Distances 1..=32768 by distance code
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+                              2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+                              13];
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+/// Deflate bits go out least significant first
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are defined most significant bit first
+    fn code(&mut self, code: u32, len: u32) {
+        let reversed = (0..len).fold(0, |r, i| r | ((code >> i) & 1) << (len - 1 - i));
+        self.bits(reversed, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+/// The fixed literal/length code of RFC 1951 3.2.6
+fn literal(w: &mut BitWriter, symbol: u16) {
+    let symbol = u32::from(symbol);
+    match symbol {
+        0..=143 => w.code(0x30 + symbol, 8),
+        144..=255 => w.code(0x190 + symbol - 144, 9),
+        256..=279 => w.code(symbol - 256, 7),
+        _ => w.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn length(w: &mut BitWriter, len: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| usize::from(base) <= len).unwrap();
+    literal(w, 257 + code as u16);
+    w.bits((len - usize::from(LENGTH_BASE[code])) as u32, u32::from(LENGTH_EXTRA[code]));
+}
+
+fn distance(w: &mut BitWriter, dist: usize) {
+    let code = DIST_BASE.iter().rposition(|&base| usize::from(base) <= dist).unwrap();
+    w.code(code as u32, 5);
+    w.bits((dist - usize::from(DIST_BASE[code])) as u32, u32::from(DIST_EXTRA[code]));
+}
+
+fn hash(data: &[u8]) -> usize {
+    let v = u32::from(data[0]) << 16 | u32::from(data[1]) << 8 | u32::from(data[2]);
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Raw deflate in one block with the fixed Huffman codes and greedy LZ77 matching.
+/// Far from the best ratio, but the screens it is meant for are mostly runs
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    // BFINAL, BTYPE = 01
+    w.bits(0b011, 3);
+    let mut last = vec![usize::MAX; 1 << HASH_BITS];
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = 0;
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(&data[i..]);
+            let candidate = last[h];
+            last[h] = i;
+            if candidate != usize::MAX && i - candidate <= WINDOW {
+                let max = (data.len() - i).min(MAX_MATCH);
+                best = (0..max).take_while(|&k| data[candidate + k] == data[i + k]).count();
+                if best >= MIN_MATCH {
+                    length(&mut w, best);
+                    distance(&mut w, i - candidate);
+                }
+            }
+        }
+        if best >= MIN_MATCH {
+            // 匹配中间的位置也进哈希表, 后面的重复才找得到
+            for k in i + 1..(i + best).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+                last[hash(&data[k..])] = k;
+            }
+            i += best;
+        } else {
+            literal(&mut w, u16::from(data[i]));
+            i += 1;
+        }
+    }
+    literal(&mut w, 256);
+    w.finish()
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// A zlib stream: header, deflate data, Adler-32
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(&adler32(data).to_be_bytes());
+    out
+}
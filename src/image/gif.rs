@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, HashMap};
+use std::collections::btree_map::Entry;
+use std::io;
+use std::io::Write;
+
+/// GIF codes are at most 12 bits
+const MAX_CODES: u16 = 4096;
+
+/// Animated GIF89a that loops forever. Each frame carries its own colour table, so frames
+/// with different colours need no shared palette
+pub struct GifWriter<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Write the header for `width * height` frames
+    pub fn new(mut out: W, width: u16, height: u16) -> io::Result<Self> {
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // 没有全局调色板
+        out.write_all(&[0, 0, 0])?;
+        // NETSCAPE2.0 扩展, 循环 0 = 无限
+        out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Self { out, width, height })
+    }
+
+    /// Add a frame of 0RGB pixels shown for `delay` hundredths of a second.
+    /// Past 256 colours the frame is cut down to 3-3-2 bit RGB
+    pub fn frame(&mut self, pixels: &[u32], delay: u16) -> io::Result<()> {
+        assert_eq!(pixels.len(), self.width as usize * self.height as usize, "the pixels do not fill the frame");
+        let (palette, indices) = index_colors(pixels);
+        let bits = (palette.len().max(2) - 1).ilog2() as u8 + 1;
+        // 图形控制扩展: 不透明, 不处理
+        self.out.write_all(&[0x21, 0xf9, 4, 0])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0, 0])?;
+        // 图像描述符, 带局部调色板
+        self.out.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0x80 | (bits - 1)])?;
+        let mut table = vec![0u8; 3 << bits];
+        for (i, color) in palette.iter().enumerate() {
+            table[i * 3..i * 3 + 3].copy_from_slice(&color.to_be_bytes()[1..]);
+        }
+        self.out.write_all(&table)?;
+        let min_code_size = bits.max(2);
+        self.out.write_all(&[min_code_size])?;
+        for block in lzw(&indices, min_code_size).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+
+    /// Write the trailer and hand back the output
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// The colours of `pixels` in the order they show up and each pixel's index
+fn index_colors(pixels: &[u32]) -> (Vec<u32>, Vec<u8>) {
+    let mut colors = BTreeMap::new();
+    let mut palette = Vec::new();
+    for &p in pixels {
+        let p = p & 0x00ff_ffff;
+        if let Entry::Vacant(entry) = colors.entry(p) {
+            if palette.len() == 256 {
+                return reduce_colors(pixels);
+            }
+            entry.insert(palette.len() as u8);
+            palette.push(p);
+        }
+    }
+    (palette, pixels.iter().map(|p| colors[&(p & 0x00ff_ffff)]).collect())
+}
+
+fn reduce_colors(pixels: &[u32]) -> (Vec<u32>, Vec<u8>) {
+    let palette = (0..=255u32).map(|i| {
+        let (r, g, b) = (i >> 5, i >> 2 & 7, i & 3);
+        (r * 255 / 7) << 16 | (g * 255 / 7) << 8 | (b * 255 / 3)
+    }).collect();
+    let indices = pixels.iter().map(|p| ((p >> 16 & 0xe0) | (p >> 11 & 0x1c) | (p >> 6 & 3)) as u8).collect();
+    (palette, indices)
+}
+
+/// GIF flavoured LZW: variable width codes packed least significant bit first,
+/// starting over with a clear code when the table is full
+pub fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut out = Vec::new();
+    let mut bits = 0u32;
+    let mut count = 0u32;
+    let mut width = u32::from(min_code_size) + 1;
+    let mut emit = |code: u16, width: u32| {
+        bits |= u32::from(code) << count;
+        count += width;
+        while count >= 8 {
+            out.push(bits as u8);
+            bits >>= 8;
+            count -= 8;
+        }
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    emit(clear, width);
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        let code = match prefix {
+            None => {
+                prefix = Some(u16::from(index));
+                continue;
+            }
+            Some(code) => code,
+        };
+        if let Some(&longer) = table.get(&(code, index)) {
+            prefix = Some(longer);
+            continue;
+        }
+        emit(code, width);
+        if next == MAX_CODES {
+            emit(clear, width);
+            table.clear();
+            next = end + 1;
+            width = u32::from(min_code_size) + 1;
+        } else {
+            table.insert((code, index), next);
+            // 解码器的表比编码器晚一项, 要等它也加满这一宽度才加宽
+            if u32::from(next) == 1 << width && width < 12 {
+                width += 1;
+            }
+            next += 1;
+        }
+        prefix = Some(u16::from(index));
+    }
+    if let Some(code) = prefix {
+        emit(code, width);
+        // 解码器读到最后一个码还会加一项, 结束码可能要宽一位
+        if !table.is_empty() && u32::from(next) == 1 << width && width < 12 {
+            width += 1;
+        }
+    }
+    emit(end, width);
+    if count > 0 {
+        out.push(bits as u8);
+    }
+    out
+}
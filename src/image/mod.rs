@@ -0,0 +1,11 @@
+mod deflate;
+mod gif;
+mod inflate;
+mod png;
+mod y4m;
+
+pub use deflate::{adler32, deflate, zlib};
+pub use gif::{GifWriter, lzw};
+pub use inflate::{inflate, unzlib};
+pub use png::{crc32, decode_png, encode_png, Image, read_png, write_png};
+pub use y4m::{rgb_to_yuv, Y4mWriter};
@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use crate::image::deflate::zlib;
+use crate::image::inflate::unzlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+
+/// Encode `width * height` pixels of 0RGB as a PNG. Up to 256 colours are stored with a palette,
+/// 1 bit deep for two colours, more need RGB
+pub fn encode_png(pixels: &[u32], width: usize, height: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height, "the pixels do not fill the image");
+    let mut colors = BTreeMap::new();
+    for &p in pixels {
+        let next = colors.len();
+        colors.entry(p & 0x00ff_ffff).or_insert(next);
+        if colors.len() > 256 {
+            break;
+        }
+    }
+    let mut raw = Vec::new();
+    let (depth, color_type) = if colors.len() <= 2 {
+        for row in pixels.chunks(width) {
+            raw.push(0);
+            for byte in row.chunks(8) {
+                raw.push(byte.iter().enumerate().fold(0u8, |b, (i, p)| b | (colors[&(p & 0x00ff_ffff)] as u8) << (7 - i)));
+            }
+        }
+        (1, COLOR_PALETTE)
+    } else if colors.len() <= 256 {
+        for row in pixels.chunks(width) {
+            raw.push(0);
+            raw.extend(row.iter().map(|p| colors[&(p & 0x00ff_ffff)] as u8));
+        }
+        (8, COLOR_PALETTE)
+    } else {
+        for row in pixels.chunks(width) {
+            raw.push(0);
+            for p in row {
+                raw.extend(&[(p >> 16) as u8, (p >> 8) as u8, *p as u8]);
+            }
+        }
+        (8, COLOR_RGB)
+    };
+    let mut png = SIGNATURE.to_vec();
+    let mut ihdr = Vec::new();
+    ihdr.extend(&(width as u32).to_be_bytes());
+    ihdr.extend(&(height as u32).to_be_bytes());
+    // 压缩, 过滤, 隔行扫描方式都是 0
+    ihdr.extend(&[depth, color_type, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &ihdr);
+    if color_type == COLOR_PALETTE {
+        let mut palette = vec![0u8; colors.len() * 3];
+        for (color, index) in &colors {
+            palette[index * 3..index * 3 + 3].copy_from_slice(&color.to_be_bytes()[1..]);
+        }
+        chunk(&mut png, b"PLTE", &palette);
+    }
+    chunk(&mut png, b"IDAT", &zlib(&raw));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub fn write_png(path: &str, pixels: &[u32], width: usize, height: usize) -> io::Result<()> {
+    fs::write(path, encode_png(pixels, width, height))
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(&crc.to_be_bytes());
+}
+
+/// CRC-32 of PNG chunks and gzip
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ u32::from(b), |c, _| if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 })
+    })
+}
+
+/// A decoded image, `width * height` pixels of ARGB. Alpha 0xff is opaque
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Image {
+    /// The pixel at `x`, `y`
+    pub fn get(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+pub fn read_png(path: &str) -> Result<Image, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    decode_png(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Decode a PNG of any colour type and bit depth, without interlacing
+pub fn decode_png(data: &[u8]) -> Result<Image, String> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err(String::from("not a PNG file"));
+    }
+    let mut header = None;
+    let mut palette: Vec<u32> = Vec::new();
+    let mut transparent: Option<Vec<u8>> = None;
+    let mut compressed = Vec::new();
+    let mut pos = 8;
+    while pos + 12 <= data.len() {
+        let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let end = pos + 12 + len;
+        if end > data.len() {
+            return Err(String::from("a chunk runs past the end of the file"));
+        }
+        let kind = &data[pos + 4..pos + 8];
+        let body = &data[pos + 8..pos + 8 + len];
+        if crc32(&data[pos + 4..pos + 8 + len]).to_be_bytes() != data[pos + 8 + len..end] {
+            return Err(format!("bad CRC in the {} chunk", String::from_utf8_lossy(kind)));
+        }
+        match kind {
+            b"IHDR" if len == 13 => {
+                let width = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+                let height = u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize;
+                if body[12] != 0 {
+                    return Err(String::from("interlaced PNGs are not supported"));
+                }
+                header = Some((width, height, body[8], body[9]));
+            }
+            b"PLTE" => palette = body.chunks_exact(3)
+                .map(|c| 0xff00_0000 | u32::from(c[0]) << 16 | u32::from(c[1]) << 8 | u32::from(c[2]))
+                .collect(),
+            b"tRNS" => transparent = Some(body.to_vec()),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos = end;
+    }
+    let (width, height, depth, color_type) = header.ok_or("no IHDR chunk")?;
+    let channels = match (color_type, depth) {
+        (0, 1) | (0, 2) | (0, 4) | (0, 8) | (0, 16) | (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (2, 8) | (2, 16) => 3,
+        (4, 8) | (4, 16) => 2,
+        (6, 8) | (6, 16) => 4,
+        _ => return Err(format!("colour type {} at {} bits is not valid", color_type, depth)),
+    };
+    if let (3, Some(alpha)) = (color_type, &transparent) {
+        for (color, &a) in palette.iter_mut().zip(alpha) {
+            *color = (*color & 0x00ff_ffff) | u32::from(a) << 24;
+        }
+    }
+    let bits = channels * usize::from(depth);
+    let stride = (width * bits).div_ceil(8);
+    let raw = unzlib(&compressed)?;
+    if raw.len() < (stride + 1) * height {
+        return Err(String::from("the image data is too short"));
+    }
+    let rows = unfilter(&raw, stride, height, bits.div_ceil(8))?;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in rows.chunks(stride.max(1)).take(height) {
+        for x in 0..width {
+            // 通道值, 16 位的取高字节
+            let sample = |c: usize| -> u32 {
+                match depth {
+                    16 => u32::from(row[(x * channels + c) * 2]),
+                    8 => u32::from(row[x * channels + c]),
+                    _ => {
+                        let bit = x * usize::from(depth);
+                        u32::from(row[bit / 8] >> (8 - usize::from(depth) - bit % 8) & ((1 << depth) - 1))
+                    }
+                }
+            };
+            let raw16 = |c: usize| -> u16 {
+                match depth {
+                    16 => u16::from_be_bytes([row[(x * channels + c) * 2], row[(x * channels + c) * 2 + 1]]),
+                    _ => sample(c) as u16,
+                }
+            };
+            let key = |c: usize| transparent.as_ref().map(|t| u16::from_be_bytes([t[c * 2], t[c * 2 + 1]]));
+            let pixel = match color_type {
+                0 => {
+                    let v = if depth < 8 { sample(0) * 255 / ((1 << depth) - 1) } else { sample(0) };
+                    let alpha = if transparent.as_ref().is_some_and(|t| t.len() >= 2) && key(0) == Some(raw16(0)) { 0 } else { 0xff };
+                    alpha << 24 | v << 16 | v << 8 | v
+                }
+                2 => {
+                    let opaque = transparent.as_ref().is_none_or(|t| t.len() < 6)
+                        || (0..3).any(|c| key(c) != Some(raw16(c)));
+                    let alpha = if opaque { 0xff } else { 0 };
+                    alpha << 24 | sample(0) << 16 | sample(1) << 8 | sample(2)
+                }
+                3 => *palette.get(sample(0) as usize).ok_or("a pixel is past the end of the palette")?,
+                4 => sample(1) << 24 | sample(0) << 16 | sample(0) << 8 | sample(0),
+                _ => sample(3) << 24 | sample(0) << 16 | sample(1) << 8 | sample(2),
+            };
+            pixels.push(pixel);
+        }
+    }
+    Ok(Image { width, height, pixels })
+}
+
+/// Undo the per row filters, `bpp` is bytes per pixel rounded up
+fn unfilter(raw: &[u8], stride: usize, height: usize, bpp: usize) -> Result<Vec<u8>, String> {
+    let mut out = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        for x in 0..stride {
+            let a = if x >= bpp { out[y * stride + x - bpp] } else { 0 };
+            let b = if y > 0 { out[(y - 1) * stride + x] } else { 0 };
+            let c = if x >= bpp && y > 0 { out[(y - 1) * stride + x - bpp] } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("unknown filter type {}", filter)),
+            };
+            out[y * stride + x] = line[x].wrapping_add(predicted);
+        }
+    }
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = ((p - i16::from(a)).abs(), (p - i16::from(b)).abs(), (p - i16::from(c)).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
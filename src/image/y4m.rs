@@ -0,0 +1,47 @@
+use std::io;
+use std::io::Write;
+
+/// Uncompressed YUV4MPEG2 video, 4:4:4 so coloured pixels keep their colour.
+/// ffmpeg and mpv read it as is
+pub struct Y4mWriter<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    planes: Vec<u8>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Write the stream header. The frame rate is `rate_num / rate_den` per second
+    pub fn new(mut out: W, width: usize, height: usize, rate_num: u32, rate_den: u32) -> io::Result<Self> {
+        writeln!(out, "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444", width, height, rate_num, rate_den)?;
+        Ok(Self { out, width, height, planes: vec![0; width * height * 3] })
+    }
+
+    /// Add a frame of 0RGB pixels
+    pub fn frame(&mut self, pixels: &[u32]) -> io::Result<()> {
+        let size = self.width * self.height;
+        assert_eq!(pixels.len(), size, "the pixels do not fill the frame");
+        for (i, &p) in pixels.iter().enumerate() {
+            let (y, u, v) = rgb_to_yuv(p);
+            self.planes[i] = y;
+            self.planes[size + i] = u;
+            self.planes[size * 2 + i] = v;
+        }
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&self.planes)
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// BT.601 in studio range, which is what players assume for Y4M
+pub fn rgb_to_yuv(pixel: u32) -> (u8, u8, u8) {
+    let (r, g, b) = ((pixel >> 16 & 0xff) as i32, (pixel >> 8 & 0xff) as i32, (pixel & 0xff) as i32);
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+    (y as u8, u as u8, v as u8)
+}
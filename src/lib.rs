@@ -1,10 +1,15 @@
-mod game;
-
 pub use cpu::Cpu;
 pub use cpu::Register;
+pub use cpu::TestIO;
 pub use memory::TestAddressing;
 
+pub mod asm;
+pub mod audio;
 pub mod cpu;
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+pub mod game;
+pub mod image;
 pub mod memory;
 pub mod util;
-
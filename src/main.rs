@@ -1,24 +1,253 @@
-use std::cell::RefCell;
-use std::fs::File;
-use std::io;
-use std::io::Read;
-use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::env;
+use std::process;
 
-use minifb::{Key, Window, WindowOptions};
+use rust8080::game::{InvadersLaunch, Launch};
+use rust8080::game::altair::AltairLaunch;
+use rust8080::game::cpm::{CCP_64K, CpmLaunch, DiskFormat};
+use rust8080::game::invaders::{HeadlessLaunch, InvadersDipSwitches, Overlay};
+use rust8080::util::parse_hex;
 
-use crate::cpu::Cpu;
-use crate::memory::{AddressBus, TestAddressing};
-use crate::game::{InvadersLaunch, Launch};
+const USAGE: &str = "usage: rust8080 [--config FILE] [--ships 3-6] [--bonus 1000|1500] [--coin-info on|off] \
+                     [--monitor] [--no-window] [--state FILE] [--rewind MB]
+                     [--resume STATE] [--record MOVIE | --play MOVIE] [--shots DIR]
+                     [--capture FILE.gif|FILE.y4m] [--samples DIR] [--sound FILE.wav] [--no-synth]
+                     [--audio | --audio-sync] [--fps] [--overlay none|midway|taito|FILE] [--artwork LAYOUT]
+                     F10 saves a PNG screenshot to DIR, Shift-F10 the raw video RAM
+                     F5 saves the state to FILE, invaders.state by default, F9 loads it
+                     hold Backspace to rewind, --rewind sets its memory (32 MB, 0 turns it off)
+                     --capture records every frame to a GIF, or to Y4M video with the sound in a WAV beside it
+                     --samples mixes the sound effects from 0.wav-8.wav in DIR, --sound writes them to a WAV file
+                     effects without a sample are synthesized unless --no-synth
+                     --audio plays the sound on the sound card, --audio-sync also follows its clock
+                     --fps prints the frames shown every 10 seconds
+                     --overlay colours the screen like the cabinet's gel, FILE is a 224x256 PNG or lines of X Y W H RRGGBB
+                     --artwork shows the screen over a backdrop inside a bezel, LAYOUT has the lines
+                     backdrop FILE.png, bezel FILE.png, screen X Y W H and optionally size W H
+       rust8080 headless [--ships 3-6] [--bonus 1000|1500] [--coin-info on|off] [--frames N] \
+                     [--script FILE] [--resume STATE] [--play MOVIE] [--dump FRAME:FILE]... \
+                     [--capture FILE.gif|FILE.y4m] [--samples DIR] [--sound FILE.wav] [--no-synth] \
+                     [--overlay none|midway|taito|FILE]
+                     runs without a window and prints the RAM hash, script lines are FRAME press|release|tap BUTTON
+                     a dump is PNG or PBM by its extension, raw video RAM otherwise
+       rust8080 cpm [--ccp ADDR] [--format sssd|TRACKS,SECTORS,RESERVED,BLOCK,DIRS,SKEW] DISK...
+                     drive A boots, --format applies to the disks after it, Ctrl-] quits
+       rust8080 altair [--ram KB] [--tcp PORT] [--switches BYTE] [--load FILE[@ADDR]] [--tape FILE] \
+                     [--disk FILE] [--feed FILE] [--run ADDR]
+                     --disk mounts 88-DCDD drives 0, 1..., boot them with the loader PROM: --load DBL@FF00 --run FF00
+                     Ctrl-E stops the machine at the front panel, Ctrl-] quits";
 
-mod util;
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let launch: Result<Box<dyn Launch>, String> = match args.first().map(|a| a.as_str()) {
+        Some("cpm") => parse_cpm_args(&args[1..]).map(|l| Box::new(l) as Box<dyn Launch>),
+        Some("headless") => parse_headless_args(&args[1..]).map(|l| Box::new(l) as Box<dyn Launch>),
+        Some("altair") => parse_altair_args(&args[1..]).map(|l| Box::new(l) as Box<dyn Launch>),
+        _ => parse_args(args).map(|l| Box::new(l) as Box<dyn Launch>),
+    };
+    let launch = launch.unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    launch.start();
+}
 
-mod cpu;
-mod memory;
-mod game;
+/// `--config` is applied first so that the other flags override the file
+fn parse_args(args: Vec<String>) -> Result<InvadersLaunch, String> {
+    let mut dip = InvadersDipSwitches::default();
+    if let Some(i) = args.iter().position(|a| a == "--config") {
+        let path = args.get(i + 1).ok_or("--config needs a file")?;
+        dip = InvadersDipSwitches::from_file(path).map_err(|e| e.to_string())?;
+    }
+    let mut launch = InvadersLaunch::new(dip);
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let key = match flag.as_str() {
+            "--config" => {
+                iter.next();
+                continue;
+            }
+            "--ships" => "ships",
+            "--bonus" => "bonus",
+            "--coin-info" => "coin_info",
+            "--monitor" => {
+                launch.monitor = true;
+                continue;
+            }
+            "--no-window" => {
+                launch.window = false;
+                continue;
+            }
+            "--no-synth" => {
+                launch.synth = false;
+                continue;
+            }
+            "--audio" => {
+                launch.audio = true;
+                continue;
+            }
+            "--audio-sync" => {
+                launch.audio_sync = true;
+                continue;
+            }
+            "--fps" => {
+                launch.fps = true;
+                continue;
+            }
+            "--rewind" => {
+                let mb: usize = iter.next().ok_or("--rewind needs a size in MB")?.parse()
+                    .map_err(|_| "--rewind needs a size in MB")?;
+                launch.rewind = mb << 20;
+                continue;
+            }
+            "--shots" => {
+                launch.screenshots = iter.next().ok_or("--shots needs a directory")?.clone();
+                continue;
+            }
+            "--resume" | "--record" | "--play" | "--capture" | "--samples" | "--sound" | "--artwork" => {
+                let path = Some(iter.next().ok_or(format!("{} needs a file", flag))?.clone());
+                match flag.as_str() {
+                    "--resume" => launch.resume = path,
+                    "--record" => launch.record = path,
+                    "--capture" => launch.capture = path,
+                    "--samples" => launch.samples = path,
+                    "--sound" => launch.sound = path,
+                    "--artwork" => launch.artwork = path,
+                    _ => launch.play = path,
+                }
+                continue;
+            }
+            "--overlay" => {
+                launch.overlay = Overlay::from_arg(iter.next().ok_or("--overlay needs a name or a file")?)?;
+                continue;
+            }
+            "--state" => {
+                launch.state = iter.next().ok_or("--state needs a file")?.clone();
+                continue;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown argument '{}'", flag)),
+        };
+        let value = iter.next().ok_or(format!("{} needs a value", flag))?;
+        launch.dip.set(key, value)?;
+    }
+    if launch.record.is_some() && launch.play.is_some() {
+        return Err(String::from("--record and --play do not go together"));
+    }
+    if !launch.window && !launch.monitor {
+        return Err(String::from("--no-window needs --monitor"));
+    }
+    Ok(launch)
+}
 
+fn parse_headless_args(args: &[String]) -> Result<HeadlessLaunch, String> {
+    let mut launch = HeadlessLaunch::new(InvadersDipSwitches::default());
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--no-synth" {
+            launch.synth = false;
+            continue;
+        }
+        let value = iter.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--ships" => launch.dip.set("ships", value?)?,
+            "--bonus" => launch.dip.set("bonus", value?)?,
+            "--coin-info" => launch.dip.set("coin_info", value?)?,
+            "--frames" => launch.frames = Some(value?.parse().map_err(|_| "--frames needs a number")?),
+            "--script" => launch.script = Some(value?.clone()),
+            "--resume" => launch.resume = Some(value?.clone()),
+            "--play" => launch.play = Some(value?.clone()),
+            "--capture" => launch.capture = Some(value?.clone()),
+            "--samples" => launch.samples = Some(value?.clone()),
+            "--sound" => launch.sound = Some(value?.clone()),
+            "--overlay" => launch.overlay = Overlay::from_arg(value?)?,
+            "--dump" => {
+                let value = value?;
+                let (frame, path) = value.split_once(':').ok_or("--dump needs FRAME:FILE")?;
+                let frame = frame.parse().map_err(|_| "--dump needs FRAME:FILE")?;
+                launch.dumps.push((frame, String::from(path)));
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            flag => return Err(format!("unknown argument '{}'", flag)),
+        }
+    }
+    if let Some(frames) = launch.frames {
+        if let Some((at, _)) = launch.dumps.iter().find(|(at, _)| *at > frames) {
+            return Err(format!("--dump {} is after --frames {}", at, frames));
+        }
+    }
+    Ok(launch)
+}
 
-fn main() {
-    let launch = InvadersLaunch::new();
-    launch.start();
+fn parse_cpm_args(args: &[String]) -> Result<CpmLaunch, String> {
+    let mut launch = CpmLaunch::new(Vec::new(), CCP_64K);
+    let mut format = DiskFormat::SSSD;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--ccp" => launch.ccp = parse_hex(value()?)?,
+            "--format" => format = DiskFormat::parse(value()?)?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown argument '{}'", flag)),
+            disk => launch.disks.push((String::from(disk), format)),
+        }
+    }
+    if launch.disks.is_empty() {
+        return Err(String::from("cpm needs a system disk for drive A"));
+    }
+    Ok(launch)
+}
+
+/// Numbers are hex like everywhere else, except the RAM size in KB and the TCP port
+fn parse_altair_args(args: &[String]) -> Result<AltairLaunch, String> {
+    let mut launch = AltairLaunch::new(0x10000);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = iter.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--ram" => {
+                let kb: usize = value?.parse().map_err(|_| "--ram needs a size in KB")?;
+                if kb == 0 || kb > 64 {
+                    return Err(String::from("--ram takes 1 to 64 KB"));
+                }
+                launch.ram = kb * 1024;
+            }
+            "--tcp" => launch.tcp = Some(value?.parse().map_err(|_| "--tcp needs a port number")?),
+            "--switches" => {
+                let switches = parse_hex(value?)?;
+                if switches > 0xff {
+                    return Err(String::from("--switches takes a byte"));
+                }
+                launch.switches = switches as u8;
+            }
+            "--load" => {
+                let value = value?;
+                let (path, addr) = match value.rfind('@') {
+                    Some(i) => (&value[..i], parse_hex(&value[i + 1..])?),
+                    None => (value.as_str(), 0),
+                };
+                launch.loads.push((String::from(path), addr));
+            }
+            "--tape" => launch.tapes.push(value?.clone()),
+            "--disk" if launch.disks.len() < 16 => launch.disks.push(value?.clone()),
+            "--disk" => return Err(String::from("the 88-DCDD has 16 drives")),
+            "--feed" => launch.feeds.push(value?.clone()),
+            "--run" => launch.run = Some(parse_hex(value?)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            flag => return Err(format!("unknown argument '{}'", flag)),
+        }
+    }
+    Ok(launch)
 }
@@ -1,3 +1,5 @@
+use crate::memory::BusError;
+
 /// Address Bus
 pub trait AddressBus {
     fn get_mem(&self, addr: u16) -> u8;
@@ -5,11 +7,22 @@ pub trait AddressBus {
     fn set_mem(&mut self, addr: u16, val: u8);
 
     fn get_word(&self, addr: u16) -> u16 {
-        u16::from(self.get_mem(addr)) | (u16::from(self.get_mem(addr + 1)) << 8)
+        u16::from(self.get_mem(addr)) | (u16::from(self.get_mem(addr.wrapping_add(1))) << 8)
     }
 
     fn set_word(&mut self, addr: u16, value: u16) {
         self.set_mem(addr, (value & 0xFF) as u8);
-        self.set_mem(addr + 1, (value >> 8) as u8)
+        self.set_mem(addr.wrapping_add(1), (value >> 8) as u8)
+    }
+
+    /// Like `get_mem`, but reports faults. Buses that can fault override this.
+    fn read(&self, addr: u16) -> Result<u8, BusError> {
+        Ok(self.get_mem(addr))
+    }
+
+    /// Like `set_mem`, but reports faults. Buses that can fault override this.
+    fn write(&mut self, addr: u16, val: u8) -> Result<(), BusError> {
+        self.set_mem(addr, val);
+        Ok(())
     }
-}
\ No newline at end of file
+}
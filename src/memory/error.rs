@@ -0,0 +1,22 @@
+use std::error::Error;
+use std::fmt;
+
+/// A memory access the bus can't complete
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusError {
+    /// Nothing is mapped at the address
+    Unmapped { addr: u16 },
+    /// Write to read-only memory
+    RomWrite { addr: u16, val: u8 },
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BusError::Unmapped { addr } => write!(f, "unmapped address {:#06X}", addr),
+            BusError::RomWrite { addr, val } => write!(f, "write {:#04X} to ROM at {:#06X}", val, addr),
+        }
+    }
+}
+
+impl Error for BusError {}
@@ -1,7 +1,8 @@
+use crate::memory::BusError;
+
 pub trait Memory {
-    fn get(&self, addr: u16) -> u8;
+    fn get(&self, addr: u16) -> Result<u8, BusError>;
 
     /// Change the value of the address
-    fn set(&mut self, addr: u16, val: u8);
+    fn set(&mut self, addr: u16, val: u8) -> Result<(), BusError>;
 }
-
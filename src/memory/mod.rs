@@ -3,12 +3,16 @@ mod video;
 mod work;
 mod readonly;
 mod memory;
+mod ram;
+mod error;
 pub mod address;
 
 
 pub use memory::Memory;
 pub use readonly::ReadOnly;
 pub use work::Work;
+pub use ram::Ram;
 pub use video::Video;
 pub use address::AddressBus;
+pub use error::BusError;
 pub use testadd::TestAddressing;
@@ -0,0 +1,47 @@
+use crate::memory::{AddressBus, BusError};
+
+/// RAM from address 0 up to `size`, nothing is mapped above it
+pub struct Ram {
+    data: Vec<u8>,
+}
+
+impl AddressBus for Ram {
+    fn get_mem(&self, addr: u16) -> u8 {
+        self.read(addr).unwrap_or(0xff)
+    }
+
+    fn set_mem(&mut self, addr: u16, val: u8) {
+        let _ = self.write(addr, val);
+    }
+
+    fn read(&self, addr: u16) -> Result<u8, BusError> {
+        self.data.get(addr as usize).copied().ok_or(BusError::Unmapped { addr })
+    }
+
+    fn write(&mut self, addr: u16, val: u8) -> Result<(), BusError> {
+        let cell = self.data.get_mut(addr as usize).ok_or(BusError::Unmapped { addr })?;
+        *cell = val;
+        Ok(())
+    }
+}
+
+impl Ram {
+    /// `size` bytes of zeroed RAM, at most 64K
+    pub fn new(size: usize) -> Self {
+        assert!(size <= 0x10000, "the 8080 addresses 64K");
+        Self {
+            data: vec![0u8; size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Copy `bytes` to `addr`, whatever does not fit is dropped
+    pub fn load(&mut self, addr: u16, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            self.set_mem(addr.wrapping_add(i as u16), *b);
+        }
+    }
+}
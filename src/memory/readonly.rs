@@ -1,4 +1,4 @@
-use crate::memory::{ Memory};
+use crate::memory::{BusError, Memory};
 
 /// 只读内存
 pub struct ReadOnly {
@@ -9,12 +9,17 @@ pub struct ReadOnly {
 }
 
 impl Memory for ReadOnly {
-    fn get(&self, addr: u16) -> u8 {
-        self.data[(addr - self.ofs) as usize]
+    fn get(&self, addr: u16) -> Result<u8, BusError> {
+        addr.checked_sub(self.ofs)
+            .and_then(|i| self.data.get(i as usize))
+            .copied()
+            .ok_or(BusError::Unmapped { addr })
     }
 
     /// ReadOnly 所以不允许修改
-    fn set(&mut self, _addr: u16, _val: u8) {}
+    fn set(&mut self, addr: u16, val: u8) -> Result<(), BusError> {
+        Err(BusError::RomWrite { addr, val })
+    }
 }
 
 impl ReadOnly {
@@ -24,8 +29,4 @@ impl ReadOnly {
             ofs,
         }
     }
-
-
 }
-
-
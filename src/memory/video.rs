@@ -1,10 +1,7 @@
-use std::sync::{Arc, RwLock};
-
-use crate::memory::Memory;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-const RAM_SIZE: usize = 7168;
+use crate::memory::{BusError, Memory};
 
 // Video RAM
 pub struct Video {
@@ -14,12 +11,19 @@ pub struct Video {
 }
 
 impl Memory for Video {
-    fn get(&self, addr: u16) -> u8 {
-        self.data.borrow()[(addr - self.ofs) as usize]
+    fn get(&self, addr: u16) -> Result<u8, BusError> {
+        addr.checked_sub(self.ofs)
+            .and_then(|i| self.data.borrow().get(i as usize).copied())
+            .ok_or(BusError::Unmapped { addr })
     }
 
-    fn set(&mut self, addr: u16, val: u8) {
-        self.data.borrow_mut()[(addr - self.ofs) as usize] = val;
+    fn set(&mut self, addr: u16, val: u8) -> Result<(), BusError> {
+        let mut data = self.data.borrow_mut();
+        let cell = addr.checked_sub(self.ofs)
+            .and_then(|i| data.get_mut(i as usize))
+            .ok_or(BusError::Unmapped { addr })?;
+        *cell = val;
+        Ok(())
     }
 }
 
@@ -30,4 +34,4 @@ impl Video {
             ofs,
         }
     }
-}
\ No newline at end of file
+}
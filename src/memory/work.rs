@@ -1,4 +1,4 @@
-use crate::memory::Memory;
+use crate::memory::{BusError, Memory};
 
 /// 工作内存
 pub struct Work {
@@ -9,12 +9,19 @@ pub struct Work {
 }
 
 impl Memory for Work {
-    fn get(&self, addr: u16) -> u8 {
-        self.data[(addr - self.ofs) as usize]
+    fn get(&self, addr: u16) -> Result<u8, BusError> {
+        addr.checked_sub(self.ofs)
+            .and_then(|i| self.data.get(i as usize))
+            .copied()
+            .ok_or(BusError::Unmapped { addr })
     }
 
-    fn set(&mut self, addr: u16, val: u8) {
-        self.data[(addr - self.ofs) as usize] = val
+    fn set(&mut self, addr: u16, val: u8) -> Result<(), BusError> {
+        let cell = addr.checked_sub(self.ofs)
+            .and_then(|i| self.data.get_mut(i as usize))
+            .ok_or(BusError::Unmapped { addr })?;
+        *cell = val;
+        Ok(())
     }
 }
 
@@ -25,4 +32,4 @@ impl Work {
             ofs,
         }
     }
-}
\ No newline at end of file
+}
@@ -1,2 +1,8 @@
+mod hash;
 mod num;
-pub use num::U16Util;
\ No newline at end of file
+mod pacer;
+mod state;
+pub use hash::fnv1a;
+pub use num::{parse_hex, U16Util};
+pub use pacer::{Pacer, RateControl};
+pub use state::{Snapshot, StateError, StateReader, StateWriter};
@@ -4,4 +4,10 @@ impl U16Util {
     pub fn from_le_bytes(f: u8, s: u8) -> u16 {
         (f as u16) | ((s as u16) << 8)
     }
-}
\ No newline at end of file
+}
+
+/// Hex with an optional `0x` prefix or `H` suffix
+pub fn parse_hex(arg: &str) -> Result<u16, String> {
+    let digits = arg.trim_start_matches("0x").trim_end_matches(['h', 'H']);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a hex number", arg))
+}
@@ -0,0 +1,94 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Give up on catching up when this far behind, after a pause or a slow host
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Keeps emulation at the speed of the real machine. The deadline of every point in emulated time
+/// comes from the cycle count and the clock rate, so rounding never adds up to drift
+pub struct Pacer {
+    hz: u32,
+    speed: f64,
+    epoch: Instant,
+    /// The cycle count that was due at `origin`, counted from `epoch`
+    base: u64,
+    origin: Duration,
+}
+
+impl Pacer {
+    pub fn new(hz: u32) -> Self {
+        Self {
+            hz,
+            speed: 1.0,
+            epoch: Instant::now(),
+            base: 0,
+            origin: Duration::ZERO,
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Run at `speed` times the real clock from `cycles` on, without a jump in the deadlines
+    pub fn set_speed(&mut self, speed: f64, cycles: u64) {
+        let now = self.due(cycles);
+        self.rebase(cycles, now);
+        self.speed = speed;
+    }
+
+    /// Count the deadlines from `cycles` being due at `now`
+    pub fn rebase(&mut self, cycles: u64, now: Duration) {
+        self.base = cycles;
+        self.origin = now;
+    }
+
+    /// Host time since the pacer was made when `cycles` is due
+    pub fn due(&self, cycles: u64) -> Duration {
+        let elapsed = cycles.saturating_sub(self.base) as f64 / (f64::from(self.hz) * self.speed);
+        self.origin + Duration::from_secs_f64(elapsed)
+    }
+
+    /// How long to wait at `now` for `cycles` to be due. Far behind, or asked about a cycle
+    /// before the last rebase, it starts counting again from here
+    pub fn delay_at(&mut self, cycles: u64, now: Duration) -> Duration {
+        if cycles < self.base || now > self.due(cycles) + MAX_LAG {
+            self.rebase(cycles, now);
+        }
+        self.due(cycles).saturating_sub(now)
+    }
+
+    /// Sleep until `cycles` is due
+    pub fn wait(&mut self, cycles: u64) {
+        let delay = self.delay_at(cycles, self.epoch.elapsed());
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// Audio clock driven speed: nudges the emulation speed so an audio buffer stays at `target`
+/// samples. The change is small enough not to hear
+pub struct RateControl {
+    target: usize,
+    max_delta: f64,
+}
+
+impl RateControl {
+    /// At most 0.5% faster or slower
+    pub fn new(target: usize) -> Self {
+        Self { target, max_delta: 0.005 }
+    }
+
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// The speed to run at with `queued` samples waiting to be played
+    pub fn speed(&self, queued: usize) -> f64 {
+        let target = self.target.max(1) as f64;
+        // 缓冲多了就慢一点, 少了就快一点
+        let error = ((queued as f64 - target) / target).clamp(-1.0, 1.0);
+        1.0 - self.max_delta * error
+    }
+}
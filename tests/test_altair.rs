@@ -0,0 +1,195 @@
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use rust8080::asm::assemble;
+use rust8080::device::{BufferConsole, Console, TcpConsole};
+use rust8080::game::altair::{AltairMachine, AltairStop, FrontPanel, load_tape};
+
+fn machine(source: &str, input: &str) -> (AltairMachine, Rc<RefCell<BufferConsole>>) {
+    let console = Rc::new(RefCell::new(BufferConsole::new(input)));
+    let mut machine = AltairMachine::new(0x10000, Box::new(console.clone()));
+    let (start, image) = assemble(source).unwrap().to_binary();
+    machine.load(start, &image);
+    (machine, console)
+}
+
+fn output(console: &Rc<RefCell<BufferConsole>>) -> String {
+    String::from_utf8(console.borrow().output.clone()).unwrap()
+}
+
+#[test]
+fn test_2sio_echo() {
+    // 2SIO 的 RDRF 是高有效
+    let (mut machine, console) = machine("
+        MVI A,3         ; master reset
+        OUT 10H
+LOOP:   IN 10H
+        RRC
+        JNC LOOP
+        IN 11H
+        CPI '.'
+        JZ DONE
+        ORI 80H         ; parity bit, not sent to the terminal
+        OUT 11H
+        JMP LOOP
+DONE:   HLT
+", "hi.");
+    assert_eq!(machine.run(Some(10000)).unwrap(), AltairStop::Halted);
+    assert_eq!(output(&console), "hi");
+}
+
+#[test]
+fn test_sio_status_and_switches() {
+    let (mut machine, console) = machine("
+        IN 0            ; nothing typed yet
+        MOV B,A
+        IN 0FFH
+        MOV C,A
+        HLT
+", "");
+    machine.io.borrow_mut().switches = 0x42;
+    assert_eq!(machine.run(Some(100)).unwrap(), AltairStop::Halted);
+    assert_eq!(machine.cpu.register.b, 0x7f);
+    assert_eq!(machine.cpu.register.c, 0x42);
+    // 88-SIO 的状态位是低有效
+    console.borrow_mut().input.push_back(b'x');
+    assert_eq!(machine.io.borrow_mut().serial.sio_status(), 0x7e);
+    assert_eq!(machine.io.borrow_mut().serial.read(), b'x');
+}
+
+#[test]
+fn test_break_and_ram_size() {
+    let console = Rc::new(RefCell::new(BufferConsole::new("\x05")));
+    let mut machine = AltairMachine::new(0x1000, Box::new(console.clone()));
+    machine.load(0, &[0xc3, 0x00, 0x00]);
+    assert_eq!(machine.run(Some(100000)).unwrap(), AltairStop::Break);
+    assert!(console.borrow().output.is_empty());
+    // 串口在别处时, 面板的终端上也能停下来, 其他键不给程序
+    let mut panel = BufferConsole::new("ab\x05c");
+    assert_eq!(machine.run_watching(Some(100000), Some(&mut panel)).unwrap(), AltairStop::Break);
+    assert_eq!(panel.input, b"c");
+    assert_eq!(machine.run_watching(Some(100000), Some(&mut panel)).unwrap(), AltairStop::Limit);
+    assert!(panel.input.is_empty());
+    // 4K 以上没有内存
+    machine.load(0x1000, &[0x12]);
+    assert_eq!(machine.cpu.addring.get_mem(0x1000), 0xff);
+    assert_eq!(machine.ram_size(), 0x1000);
+}
+
+#[test]
+fn test_panel() {
+    let console = Rc::new(RefCell::new(BufferConsole::new("\
+x 100\r
+d 3e\rdn 7\rdn 76\r
+x 100\r
+s\r
+xn\r
+r\r
+sw 81\r
+q\r")));
+    let machine = AltairMachine::new(0x10000, Box::new(BufferConsole::new("")));
+    let mut panel = FrontPanel::new(machine, Box::new(console.clone()), false);
+    panel.prompt();
+    let cpu = &panel.machine.cpu;
+    assert_eq!(cpu.register.a, 7);
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.addring.get_mem(0x101), 7);
+    assert_eq!(panel.machine.io.borrow().switches, 0x81);
+    let text = output(&console);
+    assert!(text.contains("A .... ...* .... .... 0100   D ..** ***. 3E"), "{}", text);
+    assert!(text.contains("halted at 0103"), "{}", text);
+    assert!(text.contains("HLTA"), "{}", text);
+}
+
+#[test]
+fn test_tape() {
+    // 引导程序之后是两条加载记录和结束记录
+    let mut tape = vec![0u8, 0, 0xae, 0x3c, 0xd3, 0x01];
+    tape.extend(&[0x3c, 2, 0x00, 0x01, 0x3e, 0x55, 0x94]);
+    tape.extend(&[0x3c, 1, 0x02, 0x01, 0x76, 0x79]);
+    tape.extend(&[0x78, 0x00, 0x01]);
+    let (mut machine, _) = machine("NOP", "");
+    assert_eq!(load_tape(&mut *machine.cpu.addring, &tape), Ok(Some(0x100)));
+    machine.cpu.register.pc = 0x100;
+    assert_eq!(machine.run(Some(10)).unwrap(), AltairStop::Halted);
+    assert_eq!(machine.cpu.register.a, 0x55);
+    tape[17] ^= 1;
+    assert!(load_tape(&mut *machine.cpu.addring, &tape).is_err());
+}
+
+#[test]
+fn test_tcp_console() {
+    let mut console = TcpConsole::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(console.local_addr().unwrap()).unwrap();
+    // telnet 协商和 CR LF 都要滤掉
+    client.write_all(b"\xff\xfb\x01ok\r\n").unwrap();
+    assert_eq!(console.read(), Some(b'o'));
+    assert_eq!(console.read(), Some(b'k'));
+    assert_eq!(console.read(), Some(b'\r'));
+    console.write(b'!');
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0u8; 1];
+    client.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"!");
+    assert!(!console.status());
+}
+
+#[test]
+fn test_tcp_console_telnet() {
+    let mut console = TcpConsole::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(console.local_addr().unwrap()).unwrap();
+    // IAC IAC, IAC NOP, IAC GA, IAC DO ECHO, 窗口大小和终端类型的子协商, 其中还有转义的 0xff
+    client.write_all(b"a\xff\xffb\xff\xf1c\xff\xf9d\xff\xfd\x01e").unwrap();
+    client.write_all(b"\xff\xfa\x1f\x00\x50\xff\xff\x18\xff\xf0f").unwrap();
+    client.write_all(b"\xff\xfa\x18\x00VT100\xff\xf0g").unwrap();
+    let mut input = Vec::new();
+    while input.len() < 8 {
+        input.push(console.read().unwrap());
+    }
+    assert_eq!(input, b"a\xffbcdefg");
+    assert!(!console.status());
+}
+
+#[test]
+fn test_tcp_console_backlog() {
+    let mut console = TcpConsole::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(console.local_addr().unwrap()).unwrap();
+    assert!(!console.status());
+    // 比内核的发送缓冲大, 客户端慢慢读也不能断开
+    let text: Vec<u8> = (0..400_000u32).map(|i| b"0123456789ABCDE\n"[i as usize % 16]).collect();
+    let expected = text.clone();
+    let reader = std::thread::spawn(move || {
+        client.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let mut received = vec![0u8; expected.len()];
+        std::thread::sleep(Duration::from_millis(50));
+        client.read_exact(&mut received).unwrap();
+        assert!(received == expected);
+        client.write_all(b"x").unwrap();
+    });
+    for c in text {
+        console.write(c);
+    }
+    assert_eq!(console.read(), Some(b'x'));
+    reader.join().unwrap();
+}
+
+/// Altair BASIC is not shipped with the repo, put a binary image that loads at 0 in `res/altair`
+/// and run with `cargo test -- --ignored`
+#[test]
+#[ignore = "needs res/altair/4kbas32.bin"]
+fn test_basic() {
+    let path = "res/altair/4kbas32.bin";
+    assert!(Path::new(path).exists(), "{} not found", path);
+    // 内存大小和行宽回车取默认值, 关掉 SIN/COS/RND
+    let console = Rc::new(RefCell::new(BufferConsole::new("\r\r\rY\rPRINT 2+3\r")));
+    let mut machine = AltairMachine::new(0x10000, Box::new(console.clone()));
+    machine.load(0, &std::fs::read(path).unwrap());
+    machine.run(Some(50_000_000)).unwrap();
+    let text = output(&console);
+    assert!(text.contains("BYTES FREE"), "{}", text);
+    assert!(text.contains(" 5 "), "{}", text);
+}
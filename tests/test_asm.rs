@@ -0,0 +1,101 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rust8080::{Cpu, TestAddressing, TestIO};
+use rust8080::asm::{assemble, AsmErrorKind, Segment};
+use rust8080::disasm::decode_bytes;
+
+#[test]
+fn test_instructions() {
+    let program = assemble("
+        ORG 100H
+START:  MVI A,'A'       ; 3E 41
+        MOV M,B
+        LXI SP,STACK
+        push psw
+        JNZ START
+        RST 7
+        ADI -1
+        DAD SP
+STACK   EQU 2400H
+").unwrap();
+    assert_eq!(program.segments, vec![Segment {
+        addr: 0x100,
+        bytes: vec![0x3e, 0x41, 0x70, 0x31, 0x00, 0x24, 0xf5, 0xc2, 0x00, 0x01, 0xff, 0xc6, 0xff, 0x39],
+    }]);
+    assert_eq!(program.symbols["START"], 0x100);
+}
+
+#[test]
+fn test_round_trip() {
+    // every documented opcode assembles back from its disassembly
+    for op in 0..=255u8 {
+        let instruction = decode_bytes(&[op, 0x34, 0x12]).unwrap();
+        if instruction.undocumented {
+            continue;
+        }
+        let program = assemble(&format!(" {}", instruction)).unwrap();
+        assert_eq!(program.segments[0].bytes, [op, 0x34, 0x12][..instruction.len as usize].to_vec(), "{}", instruction);
+    }
+}
+
+#[test]
+fn test_data_and_expressions() {
+    let program = assemble("
+COUNT   EQU END-TABLE       ; forward reference
+        ORG 10H
+TABLE:  DB 'Hi',0DH,0AH,'$'
+        DW TABLE, $, 1010B SHL 2
+        DS 2
+        DB HIGH 1234H, LOW 1234H, 17O, (3+4)*2 MOD 5, NOT 0 AND 0FH, COUNT
+END:
+").unwrap();
+    assert_eq!(program.symbols["COUNT"], 0x13);
+    assert_eq!(program.segments.len(), 2);
+    assert_eq!(program.segments[0].bytes, vec![b'H', b'i', 0x0d, 0x0a, b'$', 0x10, 0x00, 0x15, 0x00, 0x28, 0x00]);
+    assert_eq!(program.segments[1], Segment { addr: 0x1d, bytes: vec![0x12, 0x34, 0o17, 4, 0x0f, 0x13] });
+    let (start, image) = program.to_binary();
+    assert_eq!((start, image.len()), (0x10, 19));
+    assert_eq!(&image[11..13], &[0, 0]);
+}
+
+#[test]
+fn test_intel_hex() {
+    let program = assemble(" ORG 1000H\n DB 1,2,3\n").unwrap();
+    assert_eq!(program.to_intel_hex(), ":03100000010203E7\n:00000001FF\n");
+}
+
+#[test]
+fn test_errors() {
+    let error = assemble(" NOP\n FOO A\n").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.kind, AsmErrorKind::UnknownMnemonic(String::from("FOO")));
+    assert_eq!(assemble(" JMP NOWHERE").unwrap_err().kind, AsmErrorKind::Undefined(String::from("NOWHERE")));
+    assert_eq!(assemble(" MVI A,256").unwrap_err().kind, AsmErrorKind::OutOfRange(256));
+    assert_eq!(assemble("X: NOP\nX: NOP").unwrap_err().kind, AsmErrorKind::Redefined(String::from("X")));
+    assert!(assemble(" MOV M,M").is_err());
+    assert!(assemble(" MOV A").is_err());
+    assert!(assemble(" DB 'abc").is_err());
+}
+
+#[test]
+fn test_run_assembled() {
+    let program = assemble("
+        LXI H,DATA
+        MVI B,4
+        XRA A
+LOOP:   ADD M
+        INX H
+        DCR B
+        JNZ LOOP
+        HLT
+DATA:   DB 1,2,3,4
+").unwrap();
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    program.load(&mut mem.borrow_mut());
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem)), 0, Rc::new(RefCell::new(TestIO::new())));
+    while !cpu.is_halted() {
+        cpu.next().unwrap();
+    }
+    assert_eq!(cpu.register.a, 10);
+}
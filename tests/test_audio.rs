@@ -0,0 +1,103 @@
+use std::io::Cursor;
+
+use rust8080::audio::{AudioSink, NullSink, SAMPLE_RATE, Wav, WavSink, WavWriter};
+
+#[test]
+fn test_wav_header() {
+    let mut wav = WavWriter::new(Cursor::new(Vec::new()), 44100, 1).unwrap();
+    assert!(wav.is_empty());
+    wav.write(&[0, 1, -1, i16::MAX]).unwrap();
+    wav.write(&[i16::MIN]).unwrap();
+    assert_eq!(wav.len(), 5);
+    let data = wav.finish().unwrap().into_inner();
+    assert_eq!(data.len(), 44 + 10);
+    assert_eq!(&data[..4], b"RIFF");
+    assert_eq!(u32::from_le_bytes([data[4], data[5], data[6], data[7]]), 46);
+    assert_eq!(&data[8..16], b"WAVEfmt ");
+    // PCM, 单声道, 44100 Hz, 每秒 88200 字节, 块 2 字节, 16 位
+    assert_eq!(&data[20..36], &[1, 0, 1, 0, 0x44, 0xac, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0]);
+    assert_eq!(&data[36..40], b"data");
+    assert_eq!(u32::from_le_bytes([data[40], data[41], data[42], data[43]]), 10);
+    assert_eq!(&data[44..], &[0, 0, 1, 0, 0xff, 0xff, 0xff, 0x7f, 0, 0x80]);
+}
+
+fn wav_file(rate: u32, channels: u16, bits: u16, data: &[u8]) -> Vec<u8> {
+    let mut file = b"RIFF\0\0\0\0WAVE".to_vec();
+    // 不认识的块要跳过, 奇数长度补一个字节
+    file.extend(b"LIST\x03\0\0\0abc\0");
+    file.extend(b"fmt \x10\0\0\0\x01\0");
+    file.extend(&channels.to_le_bytes());
+    file.extend(&rate.to_le_bytes());
+    file.extend(&(rate * u32::from(channels * bits / 8)).to_le_bytes());
+    file.extend(&(channels * bits / 8).to_le_bytes());
+    file.extend(&bits.to_le_bytes());
+    file.extend(b"data");
+    file.extend(&(data.len() as u32).to_le_bytes());
+    file.extend(data);
+    file
+}
+
+#[test]
+fn test_wav_parse() {
+    let wav = Wav::parse(&wav_file(11025, 1, 8, &[128, 255, 0])).unwrap();
+    assert_eq!((wav.rate, wav.channels), (11025, 1));
+    assert_eq!(wav.samples, [0, 127 << 8, -128 << 8]);
+
+    let wav = Wav::parse(&wav_file(22050, 2, 16, &[0x10, 0, 0x30, 0, 0xff, 0xff, 0xfd, 0xff])).unwrap();
+    assert_eq!(wav.samples, [0x10, 0x30, -1, -3]);
+    assert_eq!(wav.mono(22050), [0x20, -2]);
+
+    assert!(Wav::parse(b"RIFF\0\0\0\0WAVE").is_err());
+    assert!(Wav::parse(b"not a wav file").is_err());
+    assert!(Wav::parse(&wav_file(8000, 1, 24, &[0; 6])).is_err());
+}
+
+#[test]
+fn test_wav_resample() {
+    let wav = Wav { rate: 11025, channels: 1, samples: vec![0, 400, 800, 1200] };
+    let up = wav.mono(44100);
+    assert_eq!(up.len(), 16);
+    assert_eq!(&up[..6], &[0, 100, 200, 300, 400, 500]);
+    // 最后一个样本之后保持不变
+    assert_eq!(up[15], 1200);
+    let down = Wav { rate: 44100, channels: 1, samples: (0..100).collect() }.mono(11025);
+    assert_eq!(down.len(), 25);
+    assert_eq!(down[3], 12);
+}
+
+#[test]
+fn test_sinks() {
+    let mut null = NullSink::new();
+    null.write(&[1, 2, 3]).unwrap();
+    null.write(&[]).unwrap();
+    assert_eq!(null.samples(), 3);
+
+    let path = std::env::temp_dir().join(format!("rust8080-sink-{}.wav", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut sink = WavSink::create(path).unwrap();
+    sink.write(&[5, -5]).unwrap();
+    sink.finish().unwrap();
+    assert!(sink.write(&[1]).is_err());
+    let wav = Wav::parse(&std::fs::read(path).unwrap()).unwrap();
+    assert_eq!((wav.rate, wav.channels), (SAMPLE_RATE, 1));
+    assert_eq!(wav.samples, [5, -5]);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_alsa_null_device() {
+    // ALSA 的 null 设备不需要声卡, 只有没装 libasound 时才跳过
+    let mut sink = match rust8080::audio::AlsaSink::open_device("null", SAMPLE_RATE, 100_000) {
+        Ok(sink) => sink,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("{}, skipped", e);
+            return;
+        }
+        Err(e) => panic!("ALSA null device: {}", e),
+    };
+    sink.write(&[0; 4410]).unwrap();
+    assert!(sink.queued().is_some());
+    sink.finish().unwrap();
+    assert_eq!(NullSink::new().queued(), None);
+}
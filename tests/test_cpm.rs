@@ -0,0 +1,247 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+
+use rust8080::asm::assemble;
+use rust8080::device::BufferConsole;
+use rust8080::game::cpm::{CCP_64K, CpmExit, CpmMachine, CpmShim, Disk, DiskFormat, SECTOR_SIZE, SYSTEM_SIZE, TPA};
+
+/// The exercisers are not shipped with the repo, put them in `res/cpm` and run these tests with
+/// `cargo test --release -- --ignored`
+fn run_exerciser(name: &str, expected: &str) {
+    let path = format!("res/cpm/{}", name);
+    assert!(Path::new(&path).exists(), "{} not found", path);
+    let mut shim = CpmShim::from_file(&path).unwrap();
+    shim.set_echo(true);
+    assert!(shim.run(None).unwrap());
+    let console = shim.console();
+    assert!(console.contains(expected), "{}", console);
+    assert!(!console.contains("ERROR"), "{}", console);
+}
+
+#[test]
+fn test_shim_console() {
+    let program = assemble("
+        ORG 100H
+        MVI C,9
+        LXI D,HELLO
+        CALL 5
+        MVI C,2
+        MVI E,'!'
+        CALL 5
+        LHLD 6          ; top of the TPA
+        SPHL
+        JMP 0
+HELLO:  DB 'Hello, CP/M$'
+").unwrap();
+    let (start, image) = program.to_binary();
+    assert_eq!(start, TPA);
+    let mut shim = CpmShim::new(&image);
+    assert!(shim.run(Some(1000)).unwrap());
+    assert_eq!(shim.console(), "Hello, CP/M!");
+    assert_eq!(shim.cpu.register.sp, 0xfe00);
+}
+
+#[test]
+fn test_shim_ret_exits() {
+    // a program may also end with RET to the CCP
+    let mut shim = CpmShim::new(&[0xc9]);
+    assert!(shim.run(Some(10)).unwrap());
+    let mut shim = CpmShim::new(&[0xc3, 0x00, 0x01]);
+    assert!(!shim.run(Some(10)).unwrap());
+}
+
+#[test]
+fn test_shim_string_without_end() {
+    // 没有 '$' 的字符串整个内存打印一遍就返回
+    let program = assemble("
+        ORG 100H
+        MVI C,9
+        LXI D,TEXT
+        CALL 5
+        JMP 0
+TEXT:   DB 'NO END'
+").unwrap();
+    let (_, image) = program.to_binary();
+    let mut shim = CpmShim::new(&image);
+    assert!(shim.run(Some(1000)).unwrap());
+    let console = shim.console();
+    assert!(console.starts_with("NO END"));
+    assert_eq!(console.matches("NO END").count(), 1);
+}
+
+#[test]
+#[ignore = "needs res/cpm/TST8080.COM"]
+fn test_tst8080() {
+    run_exerciser("TST8080.COM", "CPU IS OPERATIONAL");
+}
+
+#[test]
+#[ignore = "needs res/cpm/8080PRE.COM"]
+fn test_8080pre() {
+    run_exerciser("8080PRE.COM", "8080 Preliminary tests complete");
+}
+
+/// Takes a while
+#[test]
+#[ignore = "needs res/cpm/CPUTEST.COM"]
+fn test_cputest() {
+    run_exerciser("CPUTEST.COM", "CPU TESTS OK");
+}
+
+/// Takes a few minutes even in release
+#[test]
+#[ignore = "needs res/cpm/8080EXM.COM"]
+fn test_8080exm() {
+    run_exerciser("8080EXM.COM", "Tests complete");
+}
+
+/// A file in the temp directory holding `bytes`
+fn temp_image(name: &str, bytes: &[u8]) -> String {
+    let path = env::temp_dir().join(format!("rust8080-{}-{}.dsk", name, process::id()));
+    fs::write(&path, bytes).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_disk_format() {
+    let format = DiskFormat::SSSD;
+    assert_eq!(format.dpb(), [26, 0, 3, 7, 0, 242, 0, 63, 0, 0xc0, 0x00, 16, 0, 2, 0]);
+    assert_eq!(&format.skew_table()[..8], &[1, 7, 13, 19, 25, 5, 11, 17]);
+    assert_eq!(DiskFormat::parse("sssd"), Ok(format));
+    let hd = DiskFormat::parse("255,128,0,4096,1024,0").unwrap();
+    assert_eq!(hd.dpb()[2..7], [5, 31, 1, 0xfb, 0x03]);
+    assert_eq!(hd.skew_table()[..3], [1, 2, 3]);
+    assert!(DiskFormat::parse("77,26,2,1000,64,6").is_err());
+    assert!(DiskFormat::parse("77,26").is_err());
+    assert_eq!(DiskFormat::parse("200,32,2,1024,64,0"), Err(String::from("1K blocks allow at most 256 blocks")));
+    assert_eq!(DiskFormat::parse("77,26,2,1024,0,6"), Err(String::from("bad disk size or directory size")));
+}
+
+/// A stand-in for the CCP that talks to the BIOS directly:
+/// echoes keys, `D` reads and bumps a byte on drive B, `W` warm boots
+const FAKE_CCP: &str = "
+BIOS    EQU CCP+1600H
+CONIN   EQU BIOS+9
+CONOUT  EQU BIOS+12
+SELDSK  EQU BIOS+27
+SETTRK  EQU BIOS+30
+SETSEC  EQU BIOS+33
+SETDMA  EQU BIOS+36
+READ    EQU BIOS+39
+WRITE   EQU BIOS+42
+SECTRAN EQU BIOS+48
+        ORG 0E400H
+CCP:    MOV A,C
+        ADI 'A'
+        MOV C,A
+        CALL CONOUT
+        MVI C,'>'
+        CALL CONOUT
+LOOP:   CALL CONIN
+        CPI 'W'
+        JZ 0
+        CPI 'D'
+        JZ DISK
+        MOV C,A
+        CALL CONOUT
+        JMP LOOP
+DISK:   MVI C,1
+        CALL SELDSK
+        SHLD 40H
+        LXI B,2
+        CALL SETTRK
+        LXI B,0
+        CALL SECTRAN
+        MOV B,H
+        MOV C,L
+        CALL SETSEC
+        LXI B,80H
+        CALL SETDMA
+        CALL READ
+        STA 42H
+        LDA 80H
+        MOV C,A
+        CALL CONOUT
+        LXI H,80H
+        INR M
+        CALL WRITE
+        STA 43H
+        JMP LOOP
+";
+
+fn fake_system() -> Vec<u8> {
+    let (start, ccp) = assemble(FAKE_CCP).unwrap().to_binary();
+    assert_eq!(start, CCP_64K);
+    let mut image = vec![0xe5u8; SECTOR_SIZE];
+    image.extend(ccp);
+    image
+}
+
+#[test]
+fn test_cpm_machine() {
+    let system = temp_image("a", &fake_system());
+    let mut data = vec![0xe5u8; 77 * 26 * SECTOR_SIZE];
+    data[2 * 26 * SECTOR_SIZE] = b'Q';
+    let data = temp_image("b", &data);
+    let console = Rc::new(RefCell::new(BufferConsole::new("hiDWx")));
+    let disks = vec![Disk::open(&system, DiskFormat::SSSD).unwrap(), Disk::open(&data, DiskFormat::SSSD).unwrap()];
+    let mut machine = CpmMachine::new(Box::new(console.clone()), disks, CCP_64K).unwrap();
+    machine.boot().unwrap();
+    assert_eq!(machine.run(Some(100_000)).unwrap(), CpmExit::ConsoleClosed);
+    assert_eq!(String::from_utf8_lossy(&console.borrow().output), "A>hiQA>x");
+
+    let bus = &machine.cpu.addring;
+    assert_eq!((bus.get_mem(0x42), bus.get_mem(0x43)), (0, 0));
+    // page zero points at the BIOS and BDOS
+    assert_eq!(bus.get_word(0x0001), CCP_64K + SYSTEM_SIZE + 3);
+    assert_eq!(bus.get_word(0x0006), CCP_64K + 0x0806);
+    // the DPH of drive B points at its DPB
+    let dph = bus.get_word(0x40);
+    let dpb: Vec<u8> = (0..15).map(|i| bus.get_mem(bus.get_word(dph + 10) + i)).collect();
+    assert_eq!(dpb, DiskFormat::SSSD.dpb().to_vec());
+    drop(machine);
+    assert_eq!(fs::read(&data).unwrap()[2 * 26 * SECTOR_SIZE], b'R');
+    fs::remove_file(system).unwrap();
+    fs::remove_file(data).unwrap();
+}
+
+#[test]
+fn test_cpm_write_protect() {
+    let system = temp_image("wp", &fake_system());
+    let file = fs::File::open(&system).unwrap();
+    let console = Rc::new(RefCell::new(BufferConsole::new("D")));
+    let disks = vec![Disk::open(&system, DiskFormat::SSSD).unwrap(), Disk::read_only(file, DiskFormat::SSSD)];
+    let mut machine = CpmMachine::new(Box::new(console), disks, CCP_64K).unwrap();
+    machine.boot().unwrap();
+    machine.run(Some(100_000)).unwrap();
+    assert_eq!(machine.cpu.addring.get_mem(0x43), 1);
+    fs::remove_file(system).unwrap();
+}
+
+#[test]
+fn test_cpm_layout() {
+    let disks: Vec<Disk> = Vec::new();
+    assert!(CpmMachine::new(Box::new(BufferConsole::new("")), disks, CCP_64K).is_err());
+}
+
+/// Boots a real CP/M 2.2 system disk for a 64K system if one is in `res/cpm/cpm22.dsk`
+#[test]
+fn test_cpm22_dir() {
+    let path = "res/cpm/cpm22.dsk";
+    if !Path::new(path).exists() {
+        eprintln!("{} not found, skipped", path);
+        return;
+    }
+    let file = fs::File::open(path).unwrap();
+    let console = Rc::new(RefCell::new(BufferConsole::new("DIR\r")));
+    let disks = vec![Disk::read_only(file, DiskFormat::SSSD)];
+    let mut machine = CpmMachine::new(Box::new(console.clone()), disks, CCP_64K).unwrap();
+    machine.boot().unwrap();
+    assert_eq!(machine.run(Some(50_000_000)).unwrap(), CpmExit::ConsoleClosed);
+    let output = String::from_utf8_lossy(&console.borrow().output).into_owned();
+    assert!(output.matches("A>").count() >= 2, "{}", output);
+}
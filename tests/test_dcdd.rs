@@ -0,0 +1,174 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+
+use rust8080::asm::assemble;
+use rust8080::cpu::{Cpu, IO, TestIO};
+use rust8080::device::{BufferConsole, Dcdd, DCDD_SECTOR_SIZE, DcddDrive};
+use rust8080::game::altair::{AltairMachine, AltairStop};
+use rust8080::memory::Ram;
+
+/// Track 2 sector 5 holds 0, 1, 2...
+fn image(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("rust8080-{}-{}.dsk", name, process::id()));
+    let mut bytes = vec![0xe5u8; 2 * 32 * DCDD_SECTOR_SIZE];
+    bytes.extend(vec![0u8; 5 * DCDD_SECTOR_SIZE]);
+    bytes.extend((0..DCDD_SECTOR_SIZE).map(|i| i as u8));
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// Select drive 0, load the head, step to track 2 and wait for sector 5, then run `transfer`
+fn disk_machine(disk: DcddDrive, transfer: &str) -> AltairMachine {
+    let source = format!("
+        ORG 100H
+        XRA A
+        OUT 8
+        MVI A,4         ; head load
+        OUT 9
+        MVI B,2
+STEP:   IN 8
+        ANI 2           ; move head, active low
+        JNZ STEP
+        MVI A,1         ; step in
+        OUT 9
+        DCR B
+        JNZ STEP
+SECT:   IN 9
+        RAR             ; sector true, active low
+        JC SECT
+        ANI 1FH
+        CPI 5
+        JNZ SECT
+        LXI H,1000H
+        MVI C,137
+{}
+        HLT
+", transfer);
+    let mut machine = AltairMachine::new(0x10000, Box::new(BufferConsole::new("")));
+    let (start, image) = assemble(&source).unwrap().to_binary();
+    machine.load(start, &image);
+    machine.cpu.register.pc = start;
+    machine.io.borrow_mut().dcdd.attach(0, disk);
+    machine
+}
+
+const READ: &str = "
+READ:   IN 8
+        ORA A           ; new read data available, active low
+        JM READ
+        IN 0AH
+        MOV M,A
+        INX H
+        DCR C
+        JNZ READ";
+
+const WRITE: &str = "
+        MVI A,80H       ; write enable
+        OUT 9
+WRITE:  IN 8
+        RAR             ; enter new write data, active low
+        JC WRITE
+        MOV A,C
+        OUT 0AH
+        DCR C
+        JNZ WRITE";
+
+#[test]
+fn test_dcdd_read() {
+    let path = image("read");
+    let mut machine = disk_machine(DcddDrive::open(path.to_str().unwrap()).unwrap(), READ);
+    assert_eq!(machine.run(Some(1_000_000)).unwrap(), AltairStop::Halted);
+    for i in 0..DCDD_SECTOR_SIZE {
+        assert_eq!(machine.cpu.addring.get_mem(0x1000 + i as u16), i as u8);
+    }
+    assert_eq!(machine.io.borrow().dcdd.drive(0).unwrap().track(), 2);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_dcdd_write() {
+    let path = image("write");
+    let mut machine = disk_machine(DcddDrive::open(path.to_str().unwrap()).unwrap(), WRITE);
+    assert_eq!(machine.run(Some(1_000_000)).unwrap(), AltairStop::Halted);
+    let bytes = fs::read(&path).unwrap();
+    let sector = &bytes[(2 * 32 + 5) * DCDD_SECTOR_SIZE..][..DCDD_SECTOR_SIZE];
+    assert_eq!(sector[0], 137);
+    assert_eq!(sector[136], 1);
+    assert_eq!(bytes.len(), (2 * 32 + 6) * DCDD_SECTOR_SIZE);
+    assert_eq!(bytes[(2 * 32 + 4) * DCDD_SECTOR_SIZE], 0);
+    // 写保护的盘不改镜像
+    let mut reader = disk_machine(DcddDrive::read_only(File::open(&path).unwrap()), READ);
+    assert_eq!(reader.run(Some(1_000_000)).unwrap(), AltairStop::Halted);
+    assert_eq!(reader.cpu.addring.get_mem(0x1000), 137);
+    let protected = image("protected");
+    let mut writer = disk_machine(DcddDrive::read_only(File::open(&protected).unwrap()), WRITE);
+    assert!(writer.io.borrow().dcdd.drive(0).unwrap().is_read_only());
+    assert_eq!(writer.run(Some(1_000_000)).unwrap(), AltairStop::Halted);
+    assert_eq!(fs::read(&protected).unwrap()[(2 * 32 + 5) * DCDD_SECTOR_SIZE], 0);
+    fs::remove_file(path).unwrap();
+    fs::remove_file(protected).unwrap();
+}
+
+#[test]
+fn test_dcdd_status() {
+    let mut dcdd = Dcdd::new();
+    let path = image("status");
+    dcdd.attach(1, DcddDrive::open(path.to_str().unwrap()).unwrap());
+    // 没有选中驱动器
+    assert_eq!(inp(&mut dcdd, 8), 0xff);
+    out(&mut dcdd, 8, 0);
+    assert_eq!(dcdd.selected(), None);
+    out(&mut dcdd, 8, 1);
+    assert_eq!(dcdd.selected(), Some(1));
+    // move head and track 0, head not loaded
+    assert_eq!(inp(&mut dcdd, 8), 0xa5);
+    assert_eq!(inp(&mut dcdd, 9), 0xff);
+    out(&mut dcdd, 9, 0x02);
+    out(&mut dcdd, 9, 0x05);
+    assert_eq!(inp(&mut dcdd, 8), 0x61);
+    assert_eq!(inp(&mut dcdd, 9), 0xc0);
+    dcdd.tick(100);
+    assert_eq!(inp(&mut dcdd, 9), 0xc1);
+    dcdd.tick(10_416 * 33);
+    assert_eq!(inp(&mut dcdd, 9), 0xc3);
+    out(&mut dcdd, 9, 0x08);
+    assert_eq!(inp(&mut dcdd, 9), 0xff);
+    out(&mut dcdd, 8, 0x80);
+    assert_eq!(inp(&mut dcdd, 8), 0xff);
+    fs::remove_file(path).unwrap();
+}
+
+fn out(dcdd: &mut Dcdd, port: u8, a: u8) {
+    let mut cpu = Cpu::new(Box::new(Ram::new(0)), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.a = a;
+    dcdd.output(&mut cpu, port);
+}
+
+fn inp(dcdd: &mut Dcdd, port: u8) -> u8 {
+    let mut cpu = Cpu::new(Box::new(Ram::new(0)), 0, Rc::new(RefCell::new(TestIO::new())));
+    dcdd.input(&mut cpu, port);
+    cpu.register.a
+}
+
+/// Boot Altair CP/M with the disk boot loader PROM. Neither is shipped with the repo,
+/// put `dbl.bin` and `cpm.dsk` in `res/altair` and run with `cargo test -- --ignored`
+#[test]
+#[ignore = "needs res/altair/dbl.bin and cpm.dsk"]
+fn test_dcdd_boot() {
+    let (dbl, dsk) = ("res/altair/dbl.bin", "res/altair/cpm.dsk");
+    assert!(Path::new(dbl).exists() && Path::new(dsk).exists(), "{} or {} not found", dbl, dsk);
+    let console = Rc::new(RefCell::new(BufferConsole::new("")));
+    let mut machine = AltairMachine::new(0x10000, Box::new(console.clone()));
+    machine.load(0xff00, &fs::read(dbl).unwrap());
+    machine.cpu.register.pc = 0xff00;
+    let disk = DcddDrive::read_only(File::open(dsk).unwrap());
+    machine.io.borrow_mut().dcdd.attach(0, disk);
+    machine.run(Some(20_000_000)).unwrap();
+    let text = String::from_utf8_lossy(&console.borrow().output).into_owned();
+    assert!(text.contains("CP/M"), "{}", text);
+}
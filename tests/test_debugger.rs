@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+use rust8080::{Cpu, TestAddressing, TestIO};
+use rust8080::debugger::{Debugger, Monitor, MonitorExit, Stop, Watch, WatchHit};
+
+/// A CPU with `program` at 0x0000 and SP at 0x1000
+fn cpu_with(program: &[u8]) -> Cpu {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    mem.borrow_mut()[..program.len()].copy_from_slice(program);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem)), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.sp = 0x1000;
+    cpu
+}
+
+const COUNT_CALL: [u8; 12] = [
+    0x06, 0x05,       // MVI B,5
+    0xcd, 0x08, 0x00, // CALL 0x0008
+    0xc3, 0x02, 0x00, // JMP 0x0002
+    0x04,             // INR B
+    0x32, 0x00, 0x20, // STA 0x2000, 之后是 NOP 直到 RET
+];
+
+fn count_call() -> Cpu {
+    let mut cpu = cpu_with(&COUNT_CALL);
+    cpu.addring.set_mem(0x000c, 0xc9); // RET
+    cpu
+}
+
+#[test]
+fn test_breakpoint() {
+    let mut cpu = count_call();
+    let mut debugger = Debugger::attach(&mut cpu);
+    debugger.add_breakpoint(0x0008);
+    assert_eq!(debugger.run_until(&mut cpu, Some(100), |_| false), Stop::Breakpoint(0x0008));
+    assert_eq!(cpu.register.b, 5);
+    assert_eq!(debugger.step(&mut cpu), Stop::Step);
+    assert_eq!(cpu.register.b, 6);
+    assert!(debugger.remove_breakpoint(0x0008));
+    assert_eq!(debugger.run_until(&mut cpu, Some(10), |_| false), Stop::Limit);
+}
+
+#[test]
+fn test_watchpoints() {
+    let mut cpu = count_call();
+    let mut debugger = Debugger::attach(&mut cpu);
+    debugger.add_watch(0x2000, Watch::Write);
+    cpu.register.a = 0x42;
+    let stop = debugger.run_until(&mut cpu, Some(100), |_| false);
+    assert_eq!(stop, Stop::Watch(WatchHit::Write { addr: 0x2000, old: 0, val: 0x42 }));
+    assert_eq!(cpu.register.pc, 0x000c);
+    // RET reads the return address back from the stack
+    debugger.add_watch(0x0ffe, Watch::Read);
+    let stop = debugger.run_until(&mut cpu, Some(100), |_| false);
+    assert_eq!(stop, Stop::Watch(WatchHit::Read { addr: 0x0ffe, val: 0x05 }));
+}
+
+#[test]
+fn test_step_over() {
+    let mut cpu = count_call();
+    let mut debugger = Debugger::attach(&mut cpu);
+    debugger.step(&mut cpu);
+    assert_eq!(debugger.step_over(&mut cpu), Stop::Done);
+    assert_eq!(cpu.register.pc, 0x0005);
+    assert_eq!(cpu.register.b, 6);
+    // not a call, same as step
+    assert_eq!(debugger.step_over(&mut cpu), Stop::Step);
+    assert_eq!(cpu.register.pc, 0x0002);
+}
+
+#[test]
+fn test_halted() {
+    let mut cpu = cpu_with(&[0xf3, 0x76]); // DI; HLT
+    let mut debugger = Debugger::attach(&mut cpu);
+    assert_eq!(debugger.run_until(&mut cpu, Some(10), |_| false), Stop::Halted);
+}
+
+fn run_monitor(cpu: &mut Cpu, script: &str) -> (MonitorExit, String) {
+    let debugger = Debugger::attach(cpu);
+    let mut output = Vec::new();
+    let exit = Monitor::new(debugger, Cursor::new(script), &mut output).prompt(cpu).unwrap();
+    (exit, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn test_monitor_memory() {
+    let mut cpu = count_call();
+    let (exit, out) = run_monitor(&mut cpu, "e 2000 48 69 0\nm 2000 4\nq\n");
+    assert_eq!(exit, MonitorExit::Quit);
+    assert_eq!(cpu.addring.get_mem(0x2001), 0x69);
+    assert!(out.contains("2000  48 69 00 00"), "{}", out);
+    assert!(out.contains("Hi.."), "{}", out);
+}
+
+#[test]
+fn test_monitor_run() {
+    let mut cpu = count_call();
+    let (exit, out) = run_monitor(&mut cpu, "b 8\nc\n");
+    assert_eq!(exit, MonitorExit::Continue);
+    assert!(out.contains("0000  MVI B,05H"), "{}", out);
+
+    let (_, out) = run_monitor(&mut cpu, "u 8\ns 2\nr a 7f\nr\nd 2 2\nx\n");
+    assert!(out.contains("0008  INR B"), "{}", out);
+    assert!(out.contains("PC=000C SP=0FFE A=7F B=06"), "{}", out);
+    assert!(out.contains("  0002  CD 08 00  CALL 0008H"), "{}", out);
+    assert!(out.contains("unknown command 'x'"), "{}", out);
+}
@@ -0,0 +1,100 @@
+use std::cell::RefCell;
+use std::process::Command;
+use std::rc::Rc;
+
+use rust8080::TestAddressing;
+use rust8080::disasm::{decode, decode_bytes, Operand, Symbols};
+
+#[test]
+fn test_decode_bytes() {
+    let mvi = decode_bytes(&[0x36, 0xfe]).unwrap();
+    assert_eq!(mvi.mnemonic, "MVI");
+    assert_eq!(mvi.operands, vec![Operand::Register("M"), Operand::Byte(0xfe)]);
+    assert_eq!((mvi.len, mvi.cycles), (2, 10));
+    assert_eq!(mvi.to_string(), "MVI M,0FEH");
+
+    assert_eq!(decode_bytes(&[0xf5]).unwrap().to_string(), "PUSH PSW");
+    assert_eq!(decode_bytes(&[0x31, 0x00, 0x24]).unwrap().to_string(), "LXI SP,2400H");
+    assert_eq!(decode_bytes(&[0x78]).unwrap().to_string(), "MOV A,B");
+    assert_eq!(decode_bytes(&[0x9e]).unwrap().to_string(), "SBB M");
+    assert_eq!(decode_bytes(&[0xef]).unwrap().to_string(), "RST 5");
+    assert_eq!(decode_bytes(&[0xfe, 0x10]).unwrap().to_string(), "CPI 10H");
+    assert_eq!(decode_bytes(&[0xc3, 0x34]), None);
+    assert_eq!(decode_bytes(&[]), None);
+}
+
+#[test]
+fn test_cycles() {
+    let cnz = decode_bytes(&[0xc4, 0x00, 0x10]).unwrap();
+    assert_eq!((cnz.cycles, cnz.cycles_taken), (11, 17));
+    let rz = decode_bytes(&[0xc8]).unwrap();
+    assert_eq!((rz.cycles, rz.cycles_taken), (5, 11));
+    let jz = decode_bytes(&[0xca, 0x00, 0x10]).unwrap();
+    assert_eq!((jz.cycles, jz.cycles_taken), (10, 10));
+    assert!(decode_bytes(&[0xdd, 0x00, 0x10]).unwrap().undocumented);
+    assert!(!decode_bytes(&[0xcd, 0x00, 0x10]).unwrap().undocumented);
+}
+
+#[test]
+fn test_decode_bus() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    mem.borrow_mut()[0xffff] = 0xcd;
+    mem.borrow_mut()[0x0000] = 0x34;
+    mem.borrow_mut()[0x0001] = 0x12;
+    let bus = TestAddressing::new(mem);
+    let call = decode(&bus, 0xffff);
+    assert_eq!(call.to_string(), "CALL 1234H");
+    assert_eq!(call.word(), Some(0x1234));
+}
+
+#[test]
+fn test_symbols() {
+    let mut symbols = Symbols::new();
+    symbols.parse("; Invaders\n0000 RESET\nSTART: EQU 18D4H\n").unwrap();
+    assert_eq!(symbols.get(0x18d4), Some("START"));
+    assert_eq!(symbols.iter().count(), 2);
+    let jmp = decode_bytes(&[0xc3, 0xd4, 0x18]).unwrap();
+    assert_eq!(jmp.format(&symbols), "JMP START");
+    // only word operands are named
+    assert_eq!(decode_bytes(&[0x3e, 0x00]).unwrap().format(&symbols), "MVI A,00H");
+    assert!(symbols.parse("0000").unwrap_err().starts_with("line 1"));
+    assert!(symbols.parse("\nXYZ RESET").unwrap_err().starts_with("line 2"));
+}
+
+#[test]
+fn test_all_opcodes_decode() {
+    for op in 0..=255u8 {
+        let instruction = decode_bytes(&[op, 0, 0]).unwrap();
+        let expected = match instruction.operands.last() {
+            Some(Operand::Byte(_)) => 2,
+            Some(Operand::Word(_)) => 3,
+            _ => 1,
+        };
+        assert_eq!(instruction.len, expected, "{:02X}", op);
+    }
+}
+
+#[test]
+fn test_disasm_range_arguments() {
+    let path = std::env::temp_dir().join(format!("rust8080-disasm-{}.bin", std::process::id()));
+    std::fs::write(&path, [0x00, 0x3e, 0x12, 0xc9]).unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rust8080-disasm")).args(args).arg(&path).output().unwrap()
+    };
+    let output = run(&["--org", "100", "--from", "101", "--to", "102"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+    let output = run(&["--org", "100", "--from", "200"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let rejected: [(&[&str], &str); 2] = [
+        (&["--org", "100", "--to", "50"], "--to is below --org"),
+        (&["--org", "100", "--from", "103", "--to", "101"], "--from is after --to"),
+    ];
+    for (args, error) in rejected.iter() {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with(error));
+    }
+    std::fs::remove_file(path).unwrap();
+}
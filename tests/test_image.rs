@@ -0,0 +1,228 @@
+use rust8080::image::{adler32, crc32, decode_png, deflate, encode_png, GifWriter, inflate, lzw, rgb_to_yuv, unzlib,
+                      Y4mWriter, zlib};
+
+/// (type, data) of each chunk, checking the CRCs on the way
+fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut i = 8;
+    while i < png.len() {
+        let len = u32::from_be_bytes([png[i], png[i + 1], png[i + 2], png[i + 3]]) as usize;
+        let body = &png[i + 4..i + 8 + len];
+        let crc = &png[i + 8 + len..i + 12 + len];
+        assert_eq!(crc32(body).to_be_bytes(), crc);
+        chunks.push((String::from_utf8(body[..4].to_vec()).unwrap(), body[4..].to_vec()));
+        i += 12 + len;
+    }
+    chunks
+}
+
+#[test]
+fn test_checksums() {
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    assert_eq!(adler32(b""), 1);
+}
+
+#[test]
+fn test_zlib_framing() {
+    let data: Vec<u8> = (0..4096).map(|i| (i % 7) as u8).collect();
+    let z = zlib(&data);
+    assert_eq!((u16::from(z[0]) << 8 | u16::from(z[1])) % 31, 0);
+    assert_eq!(&z[z.len() - 4..], &adler32(&data).to_be_bytes());
+    // 重复的数据应该被压缩
+    assert!(z.len() < 200, "{}", z.len());
+}
+
+#[test]
+fn test_png_two_colours() {
+    let pixels: Vec<u32> = (0..16 * 4).map(|i| if i % 3 == 0 { 0x00ff_ffff } else { 0 }).collect();
+    let chunks = chunks(&encode_png(&pixels, 16, 4));
+    let names: Vec<&str> = chunks.iter().map(|c| c.0.as_str()).collect();
+    assert_eq!(names, ["IHDR", "PLTE", "IDAT", "IEND"]);
+    let ihdr = &chunks[0].1;
+    assert_eq!(&ihdr[..8], &[0, 0, 0, 16, 0, 0, 0, 4]);
+    // 1 bit, palette
+    assert_eq!(&ihdr[8..10], &[1, 3]);
+    assert_eq!(chunks[1].1.len(), 6);
+}
+
+#[test]
+fn test_png_truecolour() {
+    let pixels: Vec<u32> = (0..300).map(|i| i * 0x0001_0203).collect();
+    let chunks = chunks(&encode_png(&pixels, 20, 15));
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(&chunks[0].1[8..10], &[8, 2]);
+}
+
+/// Decode GIF LZW codes back to indices, growing the code width the way decoders do
+fn unlzw(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let reset = || -> Vec<Vec<u8>> { (0..clear + 2).map(|i| vec![i as u8]).collect() };
+    let mut table = reset();
+    let (mut bits, mut count, mut pos) = (0u32, 0u32, 0);
+    let mut width = u32::from(min_code_size) + 1;
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = Vec::new();
+    loop {
+        while count < width {
+            bits |= u32::from(data[pos]) << count;
+            pos += 1;
+            count += 8;
+        }
+        let code = (bits & ((1 << width) - 1)) as usize;
+        bits >>= width;
+        count -= width;
+        if code == clear {
+            table = reset();
+            width = u32::from(min_code_size) + 1;
+            prev = None;
+            continue;
+        }
+        if code == clear + 1 {
+            return out;
+        }
+        let entry = match &prev {
+            None => table[code].clone(),
+            Some(prev) => {
+                let entry = if code < table.len() {
+                    table[code].clone()
+                } else {
+                    assert_eq!(code, table.len());
+                    let mut e = prev.clone();
+                    e.push(prev[0]);
+                    e
+                };
+                if table.len() < 4096 {
+                    let mut e = prev.clone();
+                    e.push(entry[0]);
+                    table.push(e);
+                }
+                if table.len() == 1 << width && width < 12 {
+                    width += 1;
+                }
+                entry
+            }
+        };
+        out.extend(&entry);
+        prev = Some(entry);
+    }
+}
+
+#[test]
+fn test_lzw_round_trip() {
+    // 伪随机数据会填满码表, 测试清表和码宽的边界
+    let mut seed = 12345u32;
+    let mut noise = Vec::new();
+    for _ in 0..20000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        noise.push((seed >> 16) as u8 & 0x0f);
+    }
+    for size in [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 255, 256, 257, 1000, 20000] {
+        let data = &noise[..size];
+        assert_eq!(unlzw(&lzw(data, 4), 4), data, "{} bytes", size);
+    }
+    let flat = vec![1u8; 50000];
+    let packed = lzw(&flat, 2);
+    assert!(packed.len() < 1000, "{}", packed.len());
+    assert_eq!(unlzw(&packed, 2), flat);
+    let bytes: Vec<u8> = (0..70000u32).map(|i| (i * 7 % 251) as u8).collect();
+    assert_eq!(unlzw(&lzw(&bytes, 8), 8), bytes);
+}
+
+#[test]
+fn test_gif_frames() {
+    let mut gif = GifWriter::new(Vec::new(), 4, 2).unwrap();
+    gif.frame(&[0, 0xff0000, 0, 0, 0, 0, 0x00ff00, 0], 3).unwrap();
+    gif.frame(&[0; 8], 4).unwrap();
+    let data = gif.finish().unwrap();
+    assert!(data.starts_with(b"GIF89a\x04\x00\x02\x00"));
+    assert_eq!(*data.last().unwrap(), 0x3b);
+    // 第一帧: 控制扩展里的延时, 然后是 3 种颜色的 4 项局部调色板
+    let first = data.windows(4).position(|w| w == [0x21, 0xf9, 4, 0]).unwrap();
+    assert_eq!(data[first + 4], 3);
+    assert_eq!(data[first + 8 + 9], 0x81);
+    assert_eq!(&data[first + 8 + 10..first + 8 + 22], &[0, 0, 0, 0xff, 0, 0, 0, 0xff, 0, 0, 0, 0]);
+    assert_eq!(data.windows(4).filter(|w| *w == [0x21, 0xf9, 4, 0]).count(), 2);
+}
+
+#[test]
+fn test_y4m() {
+    assert_eq!(rgb_to_yuv(0), (16, 128, 128));
+    assert_eq!(rgb_to_yuv(0xffffff), (235, 128, 128));
+    let (_, u, v) = rgb_to_yuv(0xff0000);
+    assert!(v > 200 && u < 128);
+
+    let mut y4m = Y4mWriter::new(Vec::new(), 2, 2, 60, 1).unwrap();
+    y4m.frame(&[0, 0xffffff, 0xffffff, 0]).unwrap();
+    y4m.frame(&[0; 4]).unwrap();
+    let data = y4m.finish().unwrap();
+    let header = b"YUV4MPEG2 W2 H2 F60:1 Ip A1:1 C444\n";
+    assert!(data.starts_with(header));
+    let frame = &data[header.len()..];
+    assert_eq!(&frame[..6], b"FRAME\n");
+    assert_eq!(&frame[6..18], &[16, 235, 235, 16, 128, 128, 128, 128, 128, 128, 128, 128]);
+    assert_eq!(data.len(), header.len() + 2 * (6 + 12));
+}
+
+fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+}
+
+#[test]
+fn test_inflate() {
+    // zlib -9 的输出, 动态 Huffman 块
+    let dynamic = unhex("78da1d8fc109444108435bb135c1c008328213fbdffcbd2862629e8c81d7db40e171087b33af175c9c86010182af\
+                         dab2e3c2013cd07299a7c32732e80696194c63c0161ba474fba44f80a95285361978744f576a80642911ee8eee7b7acb\
+                         0675986b644a39f28796150becfa75f3323e51c9c6bd8c7285b817d98215c9e1920e218abedc8d17f045dc0feac0f591\
+                         c652ff22ae6f32233f50bc3a39465cfbb289f8b3da1105f0038ff67619");
+    let text = unzlib(&dynamic).unwrap();
+    assert_eq!(text.len(), 300);
+    assert_eq!(crc32(&text), 751_335_359);
+    assert!(text.starts_with(b"tdrealsudelestrte srrsouetuehoe eedetets"));
+
+    let mut seed = 7u32;
+    let data: Vec<u8> = (0..50_000).map(|i| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        if i % 100 < 60 { b"abcdefgh"[i % 8] } else { (seed >> 16) as u8 }
+    }).collect();
+    assert_eq!(unzlib(&zlib(&data)).unwrap(), data);
+    assert_eq!(inflate(&deflate(b"")).unwrap(), b"");
+
+    let mut bad = zlib(b"hello hello hello");
+    let last = bad.len() - 1;
+    bad[last] ^= 1;
+    assert_eq!(unzlib(&bad), Err(String::from("zlib checksum mismatch")));
+    assert!(unzlib(b"\x78\x01\x03").is_err());
+    assert!(unzlib(b"hello world").is_err());
+}
+
+#[test]
+fn test_png_decode() {
+    // Python 写的 7x6 RGBA, 每行用不同的过滤器, 数据存成未压缩块
+    let png = unhex("89504e470d0a1a0a0000000d49484452000000070000000608060000000f0e8476000000b94944415478da01ae0051ff00\
+                     4420823cfde6f1c26b30f90ec7dd01e4887534a20f0b0d04c36ed80e0171e0fd773f967374e4956548cb8c687779ca5e\
+                     e7276876647d05c363024adf2f6987dd59d266041be33e321aac9ca5cbe5d0e7a3f60d34f0ea030c7b06866ad0611fdc\
+                     9ad079001c8a4acce4f40d7c9eb9529bf4185a04205e42badaeb82a1eef03696664ef05c91b0a1d39fcbc10250aadc590\
+                     00a7309cb4a1252e4da70e6720fcaa4da1e98406c189c24279e9851d5b9ca54cc6de06f790000000049454e44ae426082");
+    let image = decode_png(&png).unwrap();
+    assert_eq!((image.width, image.height), (7, 6));
+    assert_eq!(&image.pixels[..3], &[0x3c44_2082, 0xc2fd_e6f1, 0x0e6b_30f9]);
+    assert_eq!(image.get(0, 1), 0x7771_e0fd);
+
+    // 编码后再解码, 1 位调色板, 8 位调色板, RGB
+    let two: Vec<u32> = (0..30 * 5).map(|i| if i % 7 == 0 { 0x00ff_ffff } else { 0 }).collect();
+    let many: Vec<u32> = (0..30 * 5).map(|i| i * 0x0001_0101 % 0x00ff_ffff).collect();
+    let rgb: Vec<u32> = (0..30 * 20).map(|i| i * 0x0000_3f1b).map(|p| p & 0x00ff_ffff).collect();
+    for (pixels, height) in [(two, 5), (many, 5), (rgb, 20)] {
+        let image = decode_png(&encode_png(&pixels, 30, height)).unwrap();
+        let opaque: Vec<u32> = pixels.iter().map(|p| p | 0xff00_0000).collect();
+        assert_eq!(image.pixels, opaque);
+    }
+
+    let mut broken = encode_png(&[0; 4], 2, 2);
+    broken[20] ^= 1;
+    assert_eq!(decode_png(&broken), Err(String::from("bad CRC in the IHDR chunk")));
+    assert!(decode_png(b"GIF89a").is_err());
+}
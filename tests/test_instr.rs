@@ -3,28 +3,29 @@ use std::fs::File;
 use std::io::Read;
 use std::rc::Rc;
 
-use rust8080::{Cpu, TestAddressing};
+use rust8080::{Cpu, TestAddressing, TestIO};
+use rust8080::cpu::{CpuError, FaultPolicy};
 
 #[test]
 fn test_inr() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
     mem.clone().borrow_mut()[0x0000] = 0x0c;
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.c = 0x99;
 
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.c, 0x9a);
 }
 
 #[test]
 fn test_dcr() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.h = 0x3a;
     cpu.register.l = 0x7c;
     mem.borrow_mut()[0x3a7c] = 0x40;
     mem.borrow_mut()[0x0000] = 0x35;
-    cpu.next();
+    cpu.next().unwrap();
     println!("{}", mem.borrow()[0x3a7c]);
     assert_eq!(mem.borrow()[0x3a7c], 0x3f);
 }
@@ -33,10 +34,10 @@ fn test_dcr() {
 fn test_cma() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
     mem.borrow_mut()[0x0000] = 0x2f;
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x51;
 
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xae);
 }
 
@@ -45,10 +46,10 @@ fn test_cma() {
 fn test_daa() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
     mem.clone().borrow_mut()[0x0000] = 0x27;
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x9b;
 
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 1);
     assert!(cpu.register.flag_ac);
     assert!(cpu.register.flag_cy);
@@ -58,47 +59,47 @@ fn test_daa() {
 fn test_mov() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
     mem.borrow_mut()[0x0000] = 0x77;
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xff;
     cpu.register.h = 0x2b;
     cpu.register.l = 0xe9;
 
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(mem.borrow()[0x2be9], 0xff);
 }
 
 #[test]
 fn test_stax() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xff;
     cpu.register.b = 0x3f;
     cpu.register.c = 0x16;
     mem.borrow_mut()[0x0000] = 0x02;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(mem.borrow()[0x3f16], 0xff);
 }
 
 #[test]
 fn test_ldax() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.d = 0x93;
     cpu.register.e = 0x8b;
     mem.borrow_mut()[0x938b] = 0xff;
     mem.borrow_mut()[0x0000] = 0x1a;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xff);
 }
 
 #[test]
 fn test_add_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.d = 0x2e;
     cpu.register.a = 0x6c;
     mem.borrow_mut()[0x0000] = 0x82;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x9a);
     assert_eq!(cpu.register.flag_s, true);
     assert_eq!(cpu.register.flag_z, false);
@@ -110,21 +111,21 @@ fn test_add_1() {
 #[test]
 fn test_add_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x01;
     mem.borrow_mut()[0x0000] = 0x87;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x02);
 }
 
 #[test]
 fn test_adc_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x42;
     cpu.register.c = 0x3d;
     mem.borrow_mut()[0x0000] = 0x89;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x7f);
     assert_eq!(cpu.register.flag_s, false);
     assert_eq!(cpu.register.flag_z, false);
@@ -136,12 +137,12 @@ fn test_adc_1() {
 #[test]
 fn test_adc_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x42;
     cpu.register.c = 0x3d;
     cpu.register.flag_cy = true;
     mem.borrow_mut()[0x0000] = 0x89;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x80);
     assert_eq!(cpu.register.flag_s, true);
     assert_eq!(cpu.register.flag_z, false);
@@ -154,11 +155,11 @@ fn test_adc_2() {
 #[test]
 fn test_adc_3() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x3f;
     cpu.register.set_flags(0xd3);
     mem.borrow_mut()[0x0000] = 0x8f;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x7f);
     assert_eq!(cpu.register.get_flags(), 0x12);
 }
@@ -166,10 +167,10 @@ fn test_adc_3() {
 #[test]
 fn test_sub() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x3e;
     mem.borrow_mut()[0x0000] = 0x97;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x00);
     assert_eq!(cpu.register.flag_s, false);
     assert_eq!(cpu.register.flag_z, true);
@@ -181,12 +182,12 @@ fn test_sub() {
 #[test]
 fn test_sbb() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.l = 0x02;
     cpu.register.a = 0x04;
     cpu.register.flag_cy = true;
     mem.borrow_mut()[0x0000] = 0x9d;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x01);
     assert_eq!(cpu.register.flag_s, false);
     assert_eq!(cpu.register.flag_z, false);
@@ -198,27 +199,27 @@ fn test_sbb() {
 #[test]
 fn test_ana() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xfc;
     cpu.register.c = 0x0f;
     mem.borrow_mut()[0x0000] = 0xa1;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x0c);
 }
 
 #[test]
 fn test_xra_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x0a;
     cpu.register.b = 0x0b;
     cpu.register.c = 0x0c;
     mem.borrow_mut()[0x0000] = 0xaf;
     mem.borrow_mut()[0x0001] = 0x47;
     mem.borrow_mut()[0x0002] = 0x4f;
-    cpu.next();
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x00);
     assert_eq!(cpu.register.b, 0x00);
     assert_eq!(cpu.register.c, 0x00);
@@ -227,11 +228,11 @@ fn test_xra_1() {
 #[test]
 fn test_xra_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xff;
     cpu.register.b = 0b1010_1010;
     mem.borrow_mut()[0x0000] = 0xa8;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0b0101_0101);
 }
 
@@ -241,22 +242,22 @@ fn test_xra_3() {}
 #[test]
 fn test_ora() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x33;
     cpu.register.c = 0x0f;
     mem.borrow_mut()[0x0000] = 0xb1;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x3f);
 }
 
 #[test]
 fn test_cmp_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x0a;
     cpu.register.e = 0x05;
     mem.borrow_mut()[0x0000] = 0xbb;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x0a);
     assert_eq!(cpu.register.e, 0x05);
     assert_eq!(cpu.register.flag_z, false);
@@ -266,11 +267,11 @@ fn test_cmp_1() {
 #[test]
 fn test_cmp_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x02;
     cpu.register.e = 0x05;
     mem.borrow_mut()[0x0000] = 0xbb;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x02);
     assert_eq!(cpu.register.e, 0x05);
     assert_eq!(cpu.register.flag_z, false);
@@ -280,11 +281,11 @@ fn test_cmp_2() {
 #[test]
 fn test_cmp_3() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xe5;
     cpu.register.e = 0x05;
     mem.borrow_mut()[0x0000] = 0xbb;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xe5);
     assert_eq!(cpu.register.e, 0x05);
     assert_eq!(cpu.register.flag_z, false);
@@ -294,10 +295,10 @@ fn test_cmp_3() {
 #[test]
 fn test_rlc() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xf2;
     mem.borrow_mut()[0x0000] = 0x07;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xe5);
     assert_eq!(cpu.register.flag_cy, true);
 }
@@ -305,10 +306,10 @@ fn test_rlc() {
 #[test]
 fn test_rrc() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xf2;
     mem.borrow_mut()[0x0000] = 0x0f;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x79);
     assert_eq!(cpu.register.flag_cy, false);
 }
@@ -316,10 +317,10 @@ fn test_rrc() {
 #[test]
 fn test_ral() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xb5;
     mem.borrow_mut()[0x0000] = 0x17;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x6a);
     assert_eq!(cpu.register.flag_cy, true);
 }
@@ -327,11 +328,11 @@ fn test_ral() {
 #[test]
 fn test_rar() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x6a;
     cpu.register.flag_cy = true;
     mem.borrow_mut()[0x0000] = 0x1f;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xb5);
     assert_eq!(cpu.register.flag_cy, false);
 }
@@ -339,12 +340,12 @@ fn test_rar() {
 #[test]
 fn test_stack_push_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.d = 0x8f;
     cpu.register.e = 0x9d;
     cpu.register.sp = 0x3a2c;
     mem.borrow_mut()[0x0000] = 0xd5;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(mem.borrow()[0x3a2b], 0x8f);
     assert_eq!(mem.borrow()[0x3a2a], 0x9d);
     assert_eq!(cpu.register.sp, 0x3a2a);
@@ -353,14 +354,14 @@ fn test_stack_push_1() {
 #[test]
 fn test_stack_push_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x1f;
     cpu.register.sp = 0x502a;
     cpu.register.flag_cy = true;
     cpu.register.flag_z = true;
     cpu.register.flag_p = true;
     mem.borrow_mut()[0x0000] = 0xf5;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(mem.borrow()[0x5029], 0x1f);
     assert_eq!(mem.borrow()[0x5028], 0x47);
     assert_eq!(cpu.register.sp, 0x5028);
@@ -369,12 +370,12 @@ fn test_stack_push_2() {
 #[test]
 fn test_stack_pop_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x1239] = 0x3d;
     mem.borrow_mut()[0x123a] = 0x93;
     cpu.register.sp = 0x1239;
     mem.borrow_mut()[0x0000] = 0xe1;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.l, 0x3d);
     assert_eq!(cpu.register.h, 0x93);
     assert_eq!(cpu.register.sp, 0x123b);
@@ -383,12 +384,12 @@ fn test_stack_pop_1() {
 #[test]
 fn test_stack_pop_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x2c00] = 0xc3;
     mem.borrow_mut()[0x2c01] = 0xff;
     cpu.register.sp = 0x2c00;
     mem.borrow_mut()[0x0000] = 0xf1;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xff);
     assert_eq!(cpu.register.get_flags(), 0xc3);
     assert_eq!(cpu.register.flag_s, true);
@@ -401,13 +402,13 @@ fn test_stack_pop_2() {
 #[test]
 fn test_dad_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.b = 0x33;
     cpu.register.c = 0x9f;
     cpu.register.h = 0xa1;
     cpu.register.l = 0x7b;
     mem.borrow_mut()[0x0000] = 0x09;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.h, 0xd5);
     assert_eq!(cpu.register.l, 0x1a);
     assert_eq!(cpu.register.flag_cy, false);
@@ -416,22 +417,22 @@ fn test_dad_1() {
 #[test]
 fn test_dad_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.h = 0xa1;
     cpu.register.l = 0x7b;
     mem.borrow_mut()[0x0000] = 0x29;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.get_hl(), 0xa17b << 1);
 }
 
 #[test]
 fn test_inx_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.d = 0x38;
     cpu.register.e = 0xff;
     mem.borrow_mut()[0x0000] = 0x13;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.d, 0x39);
     assert_eq!(cpu.register.e, 0x00);
 }
@@ -439,21 +440,21 @@ fn test_inx_1() {
 #[test]
 fn test_inx_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.sp = 0xffff;
     mem.borrow_mut()[0x0000] = 0x33;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.sp, 0x0000);
 }
 
 #[test]
 fn test_dcx() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.h = 0x98;
     cpu.register.l = 0x00;
     mem.borrow_mut()[0x0000] = 0x2b;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.h, 0x97);
     assert_eq!(cpu.register.l, 0xff);
 }
@@ -461,13 +462,13 @@ fn test_dcx() {
 #[test]
 fn test_xchg() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.h = 0x00;
     cpu.register.l = 0xff;
     cpu.register.d = 0x33;
     cpu.register.e = 0x55;
     mem.borrow_mut()[0x0000] = 0xeb;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.h, 0x33);
     assert_eq!(cpu.register.l, 0x55);
     assert_eq!(cpu.register.d, 0x00);
@@ -477,14 +478,14 @@ fn test_xchg() {
 #[test]
 fn test_xthl() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.sp = 0x10ad;
     cpu.register.h = 0x0b;
     cpu.register.l = 0x3c;
     mem.borrow_mut()[0x10ad] = 0xf0;
     mem.borrow_mut()[0x10ae] = 0x0d;
     mem.borrow_mut()[0x0000] = 0xe3;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.h, 0x0d);
     assert_eq!(cpu.register.l, 0xf0);
     assert_eq!(mem.borrow()[0x10ad], 0x3c);
@@ -494,27 +495,27 @@ fn test_xthl() {
 #[test]
 fn test_sphl() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.h = 0x50;
     cpu.register.l = 0x6c;
     mem.borrow_mut()[0x0000] = 0xf9;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.sp, 0x506c);
 }
 
 #[test]
 fn test_mvi() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x0000] = 0x26;
     mem.borrow_mut()[0x0001] = 0x3c;
     mem.borrow_mut()[0x0002] = 0x2e;
     mem.borrow_mut()[0x0003] = 0xf4;
     mem.borrow_mut()[0x0004] = 0x36;
     mem.borrow_mut()[0x0005] = 0xff;
-    cpu.next();
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.h, 0x3c);
     assert_eq!(cpu.register.l, 0xf4);
     assert_eq!(mem.borrow()[0x3cf4], 0xff);
@@ -523,16 +524,16 @@ fn test_mvi() {
 #[test]
 fn test_adi() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x0000] = 0x3e;
     mem.borrow_mut()[0x0001] = 0x14;
     mem.borrow_mut()[0x0002] = 0xc6;
     mem.borrow_mut()[0x0003] = 0x42;
     mem.borrow_mut()[0x0004] = 0xc6;
     mem.borrow_mut()[0x0005] = 0xbe;
-    cpu.next();
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x14);
     assert_eq!(cpu.register.flag_s, false);
     assert_eq!(cpu.register.flag_z, false);
@@ -544,29 +545,29 @@ fn test_adi() {
 #[test]
 fn test_aci() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x0000] = 0x3e;
     mem.borrow_mut()[0x0001] = 0x56;
     mem.borrow_mut()[0x0002] = 0xce;
     mem.borrow_mut()[0x0003] = 0xbe;
     mem.borrow_mut()[0x0004] = 0xce;
     mem.borrow_mut()[0x0005] = 0x42;
-    cpu.next();
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x57);
 }
 
 #[test]
 fn test_sui() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x0000] = 0x3e;
     mem.borrow_mut()[0x0001] = 0x00;
     mem.borrow_mut()[0x0002] = 0xd6;
     mem.borrow_mut()[0x0003] = 0x01;
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xff);
     assert_eq!(cpu.register.flag_s, true);
     assert_eq!(cpu.register.flag_z, false);
@@ -578,10 +579,10 @@ fn test_sui() {
 #[test]
 fn test_sbi_1() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x0000] = 0xde;
     mem.borrow_mut()[0x0001] = 0x01;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xff);
     assert_eq!(cpu.register.flag_s, true);
     assert_eq!(cpu.register.flag_z, false);
@@ -593,11 +594,11 @@ fn test_sbi_1() {
 #[test]
 fn test_sbi_2() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.flag_cy = true;
     mem.borrow_mut()[0x0000] = 0xde;
     mem.borrow_mut()[0x0001] = 0x01;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xfe);
     assert_eq!(cpu.register.flag_s, true);
     assert_eq!(cpu.register.flag_z, false);
@@ -609,24 +610,24 @@ fn test_sbi_2() {
 #[test]
 fn test_ani() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.c = 0x3a;
     mem.borrow_mut()[0x0000] = 0x79;
     mem.borrow_mut()[0x0001] = 0xe6;
     mem.borrow_mut()[0x0002] = 0x0f;
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x0a);
 }
 
 #[test]
 fn test_xri() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0x3b;
     mem.borrow_mut()[0x0000] = 0xee;
     mem.borrow_mut()[0x0001] = 0x81;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xba);
     assert_eq!(cpu.register.flag_cy, false);
 }
@@ -634,13 +635,13 @@ fn test_xri() {
 #[test]
 fn test_ori() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.c = 0xb5;
     mem.borrow_mut()[0x0000] = 0x79;
     mem.borrow_mut()[0x0001] = 0xf6;
     mem.borrow_mut()[0x0002] = 0x0f;
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xbf);
     assert_eq!(cpu.register.flag_cy, false);
 }
@@ -648,13 +649,13 @@ fn test_ori() {
 #[test]
 fn test_cpi() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x0000] = 0x3e;
     mem.borrow_mut()[0x0001] = 0x4a;
     mem.borrow_mut()[0x0002] = 0xfe;
     mem.borrow_mut()[0x0003] = 0x40;
-    cpu.next();
-    cpu.next();
+    cpu.next().unwrap();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0x4a);
     assert_eq!(cpu.register.flag_z, false);
     assert_eq!(cpu.register.flag_cy, false);
@@ -663,37 +664,37 @@ fn test_cpi() {
 #[test]
 fn test_sta() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.a = 0xff;
     mem.borrow_mut()[0x0000] = 0x32;
     mem.borrow_mut()[0x0001] = 0xb3;
     mem.borrow_mut()[0x0002] = 0x05;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(mem.borrow()[0x05b3], 0xff);
 }
 
 #[test]
 fn test_lda() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x0300] = 0xff;
     mem.borrow_mut()[0x0000] = 0x3a;
     mem.borrow_mut()[0x0001] = 0x00;
     mem.borrow_mut()[0x0002] = 0x03;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.a, 0xff);
 }
 
 #[test]
 fn test_shld() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.h = 0xae;
     cpu.register.l = 0x29;
     mem.borrow_mut()[0x0000] = 0x22;
     mem.borrow_mut()[0x0001] = 0x0a;
     mem.borrow_mut()[0x0002] = 0x01;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(mem.borrow()[0x010a], 0x29);
     assert_eq!(mem.borrow()[0x010b], 0xae);
 }
@@ -702,13 +703,13 @@ fn test_shld() {
 #[test]
 fn test_lhld() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     mem.borrow_mut()[0x025b] = 0xff;
     mem.borrow_mut()[0x025c] = 0x03;
     mem.borrow_mut()[0x0000] = 0x2a;
     mem.borrow_mut()[0x0001] = 0x5b;
     mem.borrow_mut()[0x0002] = 0x02;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.l, 0xff);
     assert_eq!(cpu.register.h, 0x03);
 }
@@ -716,10 +717,182 @@ fn test_lhld() {
 #[test]
 fn test_pchl() {
     let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
-    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
     cpu.register.h = 0x41;
     cpu.register.l = 0x3e;
     mem.borrow_mut()[0x0000] = 0xe9;
-    cpu.next();
+    cpu.next().unwrap();
     assert_eq!(cpu.register.pc, 0x413e);
 }
+
+#[test]
+fn test_interrupt_pending_until_ei() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.sp = 0x1000;
+    mem.borrow_mut()[0x0000] = 0x00;
+    mem.borrow_mut()[0x0001] = 0xfb;
+    mem.borrow_mut()[0x0002] = 0x00;
+    mem.borrow_mut()[0x0003] = 0x00;
+    cpu.interrupt(0xd7);
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.pc, 0x0001);
+    assert_eq!(cpu.interrupt_pending(), Some(0xd7));
+    cpu.next().unwrap();
+    assert!(cpu.interrupt_enabled());
+    // EI takes effect after the next instruction
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.pc, 0x0003);
+    assert_eq!(cpu.next().unwrap(), 11);
+    assert_eq!(cpu.register.pc, 0x0010);
+    assert_eq!(mem.borrow()[0x0ffe], 0x03);
+    assert!(!cpu.interrupt_enabled());
+    assert_eq!(cpu.interrupt_pending(), None);
+}
+
+#[test]
+fn test_interrupt_ei_ret() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0x0100, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.sp = 0x0ffe;
+    mem.borrow_mut()[0x0ffe] = 0x34;
+    mem.borrow_mut()[0x0fff] = 0x12;
+    mem.borrow_mut()[0x0100] = 0xfb;
+    mem.borrow_mut()[0x0101] = 0xc9;
+    cpu.next().unwrap();
+    cpu.interrupt(0xcf);
+    cpu.next().unwrap();
+    // RET completes before the interrupt is taken, so the handler returns to the caller
+    assert_eq!(cpu.register.pc, 0x1234);
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.pc, 0x0008);
+    assert_eq!(mem.borrow()[0x0ffe], 0x34);
+    assert_eq!(mem.borrow()[0x0fff], 0x12);
+}
+
+#[test]
+fn test_interrupt_any_instruction() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    mem.borrow_mut()[0x0000] = 0xfb;
+    cpu.next().unwrap();
+    cpu.next().unwrap();
+    // INR B from the data bus, PC stays put
+    cpu.interrupt(0x04);
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.b, 1);
+    assert_eq!(cpu.register.pc, 0x0002);
+    assert!(!cpu.interrupt_enabled());
+}
+
+#[test]
+fn test_hlt() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.sp = 0x1000;
+    mem.borrow_mut()[0x0000] = 0xfb;
+    mem.borrow_mut()[0x0001] = 0x76;
+    cpu.next().unwrap();
+    assert_eq!(cpu.next().unwrap(), 7);
+    assert!(cpu.is_halted());
+    cpu.next().unwrap();
+    cpu.next().unwrap();
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.register.pc, 0x0002);
+    cpu.interrupt(0xff);
+    assert_eq!(cpu.next().unwrap(), 11);
+    assert!(!cpu.is_halted());
+    assert_eq!(cpu.register.pc, 0x0038);
+    assert_eq!(mem.borrow()[0x0ffe], 0x02);
+}
+
+#[test]
+fn test_hlt_interrupts_disabled() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    mem.borrow_mut()[0x0000] = 0x76;
+    cpu.next().unwrap();
+    cpu.interrupt(0xcf);
+    cpu.next().unwrap();
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.register.pc, 0x0001);
+}
+
+#[test]
+fn test_undocumented_trap() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.set_fault_policy(FaultPolicy::Trap);
+    mem.borrow_mut()[0x0000] = 0x00;
+    mem.borrow_mut()[0x0001] = 0xcb;
+    mem.borrow_mut()[0x0002] = 0x34;
+    mem.borrow_mut()[0x0003] = 0x12;
+    cpu.next().unwrap();
+    assert_eq!(cpu.next(), Err(CpuError::UndocumentedOpcode { opcode: 0xcb, addr: 0x0001 }));
+    // still executed as JMP
+    assert_eq!(cpu.register.pc, 0x1234);
+    cpu.set_fault_policy(FaultPolicy::Ignore);
+    mem.borrow_mut()[0x1234] = 0x08;
+    assert_eq!(cpu.next(), Ok(4));
+}
+
+#[test]
+fn test_dcr_ac() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    mem.borrow_mut()[0x0000] = 0x05; // DCR B
+    mem.borrow_mut()[0x0001] = 0x05;
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.b = 0x11;
+    cpu.next().unwrap();
+    assert!(cpu.register.flag_ac);
+    cpu.next().unwrap();
+    // borrow out of bit 4
+    assert_eq!(cpu.register.b, 0x0f);
+    assert!(!cpu.register.flag_ac);
+}
+
+#[test]
+fn test_ana_ac() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    mem.borrow_mut()[0x0000] = 0xa0; // ANA B
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.a = 0x08;
+    cpu.register.b = 0x00;
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.a, 0);
+    assert!(cpu.register.flag_ac);
+}
+
+#[test]
+fn test_sub_ac() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    mem.borrow_mut()[0x0000] = 0x90; // SUB B
+    mem.borrow_mut()[0x0001] = 0x98; // SBB B
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.a = 0x3e;
+    cpu.register.b = 0x3e;
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.a, 0);
+    assert!(cpu.register.flag_ac);
+    assert!(!cpu.register.flag_cy);
+    cpu.register.a = 0x10;
+    cpu.register.b = 0x00;
+    cpu.register.flag_cy = true;
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.a, 0x0f);
+    assert!(!cpu.register.flag_ac);
+}
+
+#[test]
+fn test_jp() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    mem.borrow_mut()[0x0000..0x0003].copy_from_slice(&[0xf2, 0x00, 0x10]); // JP 1000H
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, Rc::new(RefCell::new(TestIO::new())));
+    cpu.register.flag_s = false;
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.pc, 0x1000);
+    cpu.register.pc = 0;
+    cpu.register.flag_s = true;
+    cpu.next().unwrap();
+    assert_eq!(cpu.register.pc, 0x0003);
+}
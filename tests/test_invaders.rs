@@ -0,0 +1,752 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rust8080::{Cpu, TestAddressing};
+use rust8080::cpu::{CpuError, FaultPolicy};
+use rust8080::memory::{BusError, Memory, ReadOnly};
+use rust8080::util::StateError;
+use rust8080::image::{Image, write_png};
+use rust8080::game::invaders::{Artwork, Beam, Headless, HeadlessLaunch, InputScript, InvadersSound, sample_at, SAMPLE_COUNT,
+                                SoundWrite, Synth, CYCLES_PER_FRAME, ExtraShip, InvadersAddressBus, InvadersButton, InvadersDipSwitches,
+                                InvadersIO, InvadersMachine, Movie, MoviePlayer, Overlay, PlaybackError, Recorder, Rect, Region, Rewind, Scheduler,
+                                SCREEN_HEIGHT, SCREEN_WIDTH, ShiftRegister, VIDEO_RAM_SIZE, wav_path};
+
+#[test]
+fn test_shift_register() {
+    let mut shifter = ShiftRegister::new();
+    shifter.push(0xab);
+    shifter.push(0xcd);
+    assert_eq!(shifter.read(), 0xcd);
+    shifter.set_offset(3);
+    assert_eq!(shifter.read(), 0x6d);
+    shifter.set_offset(0xff);
+    assert_eq!(shifter.read(), 0xd5);
+}
+
+#[test]
+fn test_shift_register_ports() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let program = [
+        0x3e, 0xab, // MVI A,0xab
+        0xd3, 0x04, // OUT 4
+        0x3e, 0xcd, // MVI A,0xcd
+        0xd3, 0x04, // OUT 4
+        0x3e, 0x03, // MVI A,3
+        0xd3, 0x02, // OUT 2
+        0xdb, 0x03, // IN 3
+    ];
+    mem.borrow_mut()[..program.len()].copy_from_slice(&program);
+    let io = Rc::new(RefCell::new(InvadersIO::new()));
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, io);
+    for _ in 0..7 {
+        cpu.next().unwrap();
+    }
+    assert_eq!(cpu.register.a, 0x6d);
+}
+
+#[test]
+fn test_input_ports_idle() {
+    let io = InvadersIO::new();
+    assert_eq!(io.port(0), 0b0000_1110);
+    assert_eq!(io.port(1), 0b0000_1000);
+    assert_eq!(io.port(2), 0b1000_0000);
+}
+
+#[test]
+fn test_input_ports_simultaneous() {
+    let mut io = InvadersIO::new();
+    io.press(InvadersButton::P1Left);
+    io.press(InvadersButton::P1Fire);
+    io.press(InvadersButton::P2Right);
+    assert_eq!(io.port(1), 0b0011_1000);
+    assert_eq!(io.port(2), 0b1100_0000);
+    io.release(InvadersButton::P1Left);
+    assert_eq!(io.port(1), 0b0001_1000);
+    assert_eq!(io.port(0), 0b0001_1110);
+}
+
+#[test]
+fn test_input_ports_latched() {
+    let mem = Rc::new(RefCell::new([0u8; 65536].to_vec()));
+    let program = [
+        0xdb, 0x01, // IN 1
+        0x47,       // MOV B,A
+        0xdb, 0x01, // IN 1
+    ];
+    mem.borrow_mut()[..program.len()].copy_from_slice(&program);
+    let io = Rc::new(RefCell::new(InvadersIO::new()));
+    io.borrow_mut().press(InvadersButton::Coin);
+    let mut cpu = Cpu::new(Box::new(TestAddressing::new(mem.clone())), 0, io);
+    for _ in 0..3 {
+        cpu.next().unwrap();
+    }
+    assert_eq!(cpu.register.b, 0b0000_1001);
+    assert_eq!(cpu.register.a, 0b0000_1001);
+}
+
+#[test]
+fn test_dip_switches() {
+    let mut io = InvadersIO::new();
+    let mut dip = InvadersDipSwitches::default();
+    dip.set("ships", "5").unwrap();
+    dip.set("bonus", "1000").unwrap();
+    dip.set("coin_info", "on").unwrap();
+    assert_eq!(dip.extra_ship, ExtraShip::At1000);
+    io.set_dip_switches(dip);
+    io.press(InvadersButton::P2Fire);
+    assert_eq!(io.port(2), 0b0001_1010);
+    assert!(dip.set("ships", "7").is_err());
+    assert!(dip.set("lives", "3").is_err());
+}
+
+#[test]
+fn test_scheduler_interrupt_points() {
+    let mut scheduler = Scheduler::new();
+    let mut raised = Vec::new();
+    let mut cycle = 0u32;
+    while scheduler.frame() < 2 {
+        if let Some(beam) = scheduler.tick(7) {
+            raised.push((beam, cycle));
+        }
+        cycle += 7;
+    }
+    assert_eq!(raised.len(), 4);
+    assert_eq!(raised[0].0, Beam::MidScreen);
+    assert_eq!(raised[1].0, Beam::VBlank);
+    // the interrupt is raised by the instruction that reaches the line
+    assert!(raised[0].1 + 7 >= Scheduler::line_cycle(96) && raised[0].1 < Scheduler::line_cycle(96));
+    assert!(raised[1].1 + 7 >= Scheduler::line_cycle(224) && raised[1].1 < Scheduler::line_cycle(224));
+    let second = CYCLES_PER_FRAME + Scheduler::line_cycle(96);
+    assert!(raised[2].1 + 7 >= second && raised[2].1 < second);
+}
+
+/// A board whose ROM counts RST 1 in B and RST 2 in C, `main` is placed at 0x0020
+fn counting_machine(main: &[u8]) -> InvadersMachine {
+    let mut rom = Box::new([0u8; 2048]);
+    // JMP 0x0020
+    rom[..3].copy_from_slice(&[0xc3, 0x20, 0x00]);
+    rom[0x20..0x20 + main.len()].copy_from_slice(main);
+    // RST 1: INR B; EI; RET
+    rom[0x08..0x0b].copy_from_slice(&[0x04, 0xfb, 0xc9]);
+    // RST 2: INR C; EI; RET
+    rom[0x10..0x13].copy_from_slice(&[0x0c, 0xfb, 0xc9]);
+    let video_arr = Rc::new(RefCell::new(vec![0u8; VIDEO_RAM_SIZE]));
+    let bus = InvadersAddressBus::new(rom, Box::new([0u8; 2048]), Box::new([0u8; 2048]), Box::new([0u8; 2048]),
+                                      video_arr.clone());
+    InvadersMachine::new(bus, video_arr, InvadersDipSwitches::default())
+}
+
+#[test]
+fn test_machine_frame_interrupts() {
+    let mut machine = counting_machine(&[
+        0x31, 0x00, 0x24, // LXI SP,0x2400
+        0xfb,             // EI
+        0xc3, 0x24, 0x00, // JMP 0x0024
+    ]);
+    machine.run_frame().unwrap();
+    assert_eq!(machine.cpu.register.b, 1);
+    assert_eq!(machine.cpu.register.c, 1);
+    machine.run_frame().unwrap();
+    assert_eq!(machine.cpu.register.b, 2);
+    assert_eq!(machine.cpu.register.c, 2);
+}
+
+#[test]
+fn test_machine_interrupt_pending_until_ei() {
+    let mut machine = counting_machine(&[
+        0x31, 0x00, 0x24, // LXI SP,0x2400
+        0x11, 0x00, 0x08, // LXI D,0x0800
+        0x1b,             // DCX D
+        0x7a,             // MOV A,D
+        0xb3,             // ORA E
+        0xc2, 0x26, 0x00, // JNZ 0x0026
+        0xfb,             // EI
+        0xc3, 0x2d, 0x00, // JMP 0x002d
+    ]);
+    machine.run_frame().unwrap();
+    assert_eq!(machine.cpu.register.b + machine.cpu.register.c, 0);
+    machine.run_frame().unwrap();
+    // the mid-screen request raised during the delay loop is served right after EI
+    assert_eq!(machine.cpu.register.b, 1);
+    assert_eq!(machine.cpu.register.c, 1);
+}
+
+#[test]
+fn test_bus_faults_trap() {
+    let mut machine = counting_machine(&[
+        0x3e, 0x05,       // MVI A,5
+        0x32, 0x00, 0x01, // STA 0x0100
+        0x3a, 0x00, 0x50, // LDA 0x5000
+    ]);
+    machine.cpu.set_fault_policy(FaultPolicy::Trap);
+    machine.step().unwrap();
+    machine.step().unwrap();
+    let before = machine.scheduler().cycles();
+    assert_eq!(machine.step(), Err(CpuError::Bus { pc: 0x0022, error: BusError::RomWrite { addr: 0x0100, val: 5 } }));
+    assert_eq!(machine.step(), Err(CpuError::Bus { pc: 0x0025, error: BusError::Unmapped { addr: 0x5000 } }));
+    assert_eq!(machine.cpu.register.a, 0xff);
+    assert_eq!(machine.cpu.register.pc, 0x0028);
+    // 出错的 STA 和 LDA 也算时钟
+    assert_eq!(machine.scheduler().cycles() - before, 13 + 13);
+}
+
+#[test]
+fn test_rom_out_of_range() {
+    let rom = ReadOnly::init(0x0800, Box::new([0u8; 2048]));
+    assert_eq!(rom.get(0x0100), Err(BusError::Unmapped { addr: 0x0100 }));
+    assert_eq!(rom.get(0x1000), Err(BusError::Unmapped { addr: 0x1000 }));
+    assert_eq!(rom.get(0x0fff), Ok(0));
+}
+
+#[test]
+fn test_save_state_replays() {
+    let mut machine = InvadersMachine::from_rom_dir("./res", InvadersDipSwitches::default()).unwrap();
+    machine.io.borrow_mut().press(InvadersButton::Coin);
+    for _ in 0..200 {
+        machine.run_frame().unwrap();
+    }
+    // 停在帧中间, 让扫描位置和挂起的中断都进存档
+    for _ in 0..1234 {
+        machine.step().unwrap();
+    }
+    let state = machine.save_state();
+    machine.io.borrow_mut().release(InvadersButton::Coin);
+    for _ in 0..60 {
+        machine.run_frame().unwrap();
+    }
+    let after = machine.save_state();
+    let video = machine.video_arr.borrow().clone();
+
+    machine.load_state(&state).unwrap();
+    assert_eq!(machine.save_state(), state);
+    machine.io.borrow_mut().release(InvadersButton::Coin);
+    for _ in 0..60 {
+        machine.run_frame().unwrap();
+    }
+    assert_eq!(machine.save_state(), after);
+    assert_eq!(*machine.video_arr.borrow(), video);
+}
+
+#[test]
+fn test_save_state_cpu_and_io() {
+    let mut machine = counting_machine(&[0x76]); // HLT
+    machine.cpu.register.set_bc(0x1234);
+    machine.cpu.register.flag_cy = true;
+    machine.cpu.interrupt(0xd7);
+    machine.io.borrow_mut().press(InvadersButton::P1Fire);
+    machine.cpu.addring.set_mem(0x43ff, 0x99);
+    machine.step().unwrap();
+    machine.step().unwrap();
+    let state = machine.save_state();
+
+    let mut other = counting_machine(&[]);
+    other.load_state(&state).unwrap();
+    assert_eq!(other.cpu.register.get_bc(), 0x1234);
+    assert!(other.cpu.register.flag_cy);
+    assert!(other.cpu.is_halted());
+    assert_eq!(other.cpu.interrupt_pending(), Some(0xd7));
+    assert_eq!(other.io.borrow().port(1) & 0b0001_0000, 0b0001_0000);
+    assert_eq!(other.cpu.addring.get_mem(0x43ff), 0x99);
+    assert_eq!(other.scheduler().cycle(), machine.scheduler().cycle());
+}
+
+#[test]
+fn test_save_state_rejected() {
+    let mut machine = counting_machine(&[]);
+    let state = machine.save_state();
+    let mut bad = state.clone();
+    bad[0] = b'X';
+    assert!(matches!(machine.load_state(&bad), Err(StateError::BadMagic)));
+    let mut bad = state.clone();
+    bad[8] = 99;
+    assert!(matches!(machine.load_state(&bad), Err(StateError::Version(99))));
+    machine.cpu.register.pc = 0x1111;
+    let short = &state[..state.len() - 1];
+    assert!(matches!(machine.load_state(short), Err(StateError::Length { .. })));
+    // 被拒绝的存档不改变机器
+    assert_eq!(machine.cpu.register.pc, 0x1111);
+}
+
+#[test]
+fn test_rewind() {
+    let mut machine = InvadersMachine::from_rom_dir("./res", InvadersDipSwitches::default()).unwrap();
+    let mut rewind = Rewind::new(1 << 20);
+    let mut states = Vec::new();
+    for _ in 0..120 {
+        machine.run_frame().unwrap();
+        states.push(machine.save_state());
+        rewind.push(machine.save_state());
+    }
+    assert_eq!(rewind.len(), 119);
+    // 增量远小于整个存档
+    assert!(rewind.used() < 119 * states[0].len() / 4, "{}", rewind.used());
+    for expected in states.iter().rev().skip(1).take(50) {
+        assert_eq!(rewind.pop().as_ref(), Some(expected));
+    }
+    // 倒带后继续玩, 新的帧接在退回的位置之后
+    machine.load_state(&states[69]).unwrap();
+    machine.run_frame().unwrap();
+    rewind.push(machine.save_state());
+    assert_eq!(rewind.pop().as_ref(), Some(&states[69]));
+    assert_eq!(rewind.pop().as_ref(), Some(&states[68]));
+}
+
+#[test]
+fn test_rewind_budget() {
+    let mut machine = InvadersMachine::from_rom_dir("./res", InvadersDipSwitches::default()).unwrap();
+    let mut rewind = Rewind::new(512);
+    for _ in 0..60 {
+        machine.run_frame().unwrap();
+        rewind.push(machine.save_state());
+        assert!(rewind.used() <= 512);
+    }
+    assert!(rewind.len() < 59, "{} frames in {} bytes", rewind.len(), rewind.used());
+    while rewind.pop().is_some() {}
+    assert!(rewind.is_empty());
+    assert_eq!(rewind.used(), 0);
+}
+
+/// Coin, start and some shooting, then idle
+fn play_some(machine: &mut InvadersMachine, movie: &mut Movie, frames: usize) {
+    for frame in 0..frames {
+        let pressed = match frame {
+            100..=104 => Some(InvadersButton::Coin),
+            160..=164 => Some(InvadersButton::P1Start),
+            300..=599 if frame % 20 < 3 => Some(InvadersButton::P1Fire),
+            300..=599 if frame % 80 < 40 => Some(InvadersButton::P1Left),
+            _ => None,
+        };
+        machine.io.borrow_mut().release_all();
+        if let Some(button) = pressed {
+            machine.io.borrow_mut().press(button);
+        }
+        machine.run_frame().unwrap();
+        movie.record(machine);
+    }
+}
+
+#[test]
+fn test_movie_playback() {
+    let dip = InvadersDipSwitches { ships: 5, ..InvadersDipSwitches::default() };
+    let mut machine = InvadersMachine::from_rom_dir("./res", dip).unwrap();
+    let mut movie = Movie::power_on(dip);
+    play_some(&mut machine, &mut movie, 600);
+    let end = machine.save_state();
+    assert_eq!(movie.frames.iter().filter(|f| f.hash.is_some()).count(), 10);
+
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(movie.dip, dip);
+    let mut replay = InvadersMachine::from_rom_dir("./res", InvadersDipSwitches::default()).unwrap();
+    let mut player = MoviePlayer::new(movie);
+    player.start(&mut replay).unwrap();
+    while player.step(&mut replay).unwrap() {}
+    assert_eq!(player.frame(), 600);
+    assert_eq!(replay.save_state(), end);
+}
+
+#[test]
+fn test_movie_from_state_and_desync() {
+    let mut machine = InvadersMachine::from_rom_dir("./res", InvadersDipSwitches::default()).unwrap();
+    let mut warmup = Movie::power_on(InvadersDipSwitches::default());
+    play_some(&mut machine, &mut warmup, 200);
+    let mut movie = Movie::from_machine(InvadersDipSwitches::default(), &machine);
+    play_some(&mut machine, &mut movie, 400);
+    let end = machine.save_state();
+
+    // 从存档开始的录像不需要刚开机的机器
+    let mut player = MoviePlayer::new(movie.clone());
+    player.start(&mut machine).unwrap();
+    while player.step(&mut machine).unwrap() {}
+    assert_eq!(machine.save_state(), end);
+
+    // 改掉一帧的输入, 下一个检查点就发现不同步
+    let fire = movie.frames.iter().position(|f| f.buttons[1] & 0b0001_0000 != 0).unwrap();
+    movie.frames[fire].buttons = [0b0000_1110, 0b0000_1000, 0];
+    let mut player = MoviePlayer::new(movie);
+    player.start(&mut machine).unwrap();
+    let error = loop {
+        match player.step(&mut machine) {
+            Ok(true) => {}
+            Ok(false) => panic!("no desync detected"),
+            Err(e) => break e,
+        }
+    };
+    match error {
+        PlaybackError::Desync { frame, .. } => assert!(frame > fire && frame <= fire + 60, "{} {}", frame, fire),
+        e => panic!("{}", e),
+    }
+}
+
+#[test]
+fn test_movie_rejected() {
+    let movie = Movie::power_on(InvadersDipSwitches::default());
+    let mut bytes = movie.to_bytes();
+    assert_eq!(Movie::from_bytes(&bytes).unwrap(), movie);
+    bytes.push(0);
+    assert!(matches!(Movie::from_bytes(&bytes), Err(StateError::Length { .. })));
+    assert!(matches!(Movie::from_bytes(b"8080INVS"), Err(StateError::BadMagic)));
+}
+
+/// BCD credit count in work RAM
+const CREDITS: u16 = 0x20eb;
+
+#[test]
+fn test_headless_attract() {
+    let mut headless = Headless::power_on(InvadersDipSwitches::default()).unwrap();
+    headless.run(600).unwrap();
+    assert_eq!(headless.frame(), 600);
+    assert!(headless.lit_pixels() > 500, "{}", headless.lit_pixels());
+    let screen = headless.screen();
+    assert_eq!(screen.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    assert_eq!(screen.iter().filter(|p| **p != 0).count(), headless.lit_pixels());
+    assert_eq!(headless.machine.cpu.addring.get_mem(CREDITS), 0);
+
+    let path = std::env::temp_dir().join(format!("rust8080-attract-{}.pbm", std::process::id()));
+    headless.write_pbm(path.to_str().unwrap()).unwrap();
+    let pbm = std::fs::read(&path).unwrap();
+    assert!(pbm.starts_with(b"P4\n224 256\n"));
+    assert_eq!(pbm.len(), 11 + SCREEN_WIDTH / 8 * SCREEN_HEIGHT);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_headless_dumps() {
+    let mut headless = Headless::power_on(InvadersDipSwitches::default()).unwrap();
+    headless.run(300).unwrap();
+    let base = std::env::temp_dir().join(format!("rust8080-dump-{}", std::process::id()));
+    let base = base.to_str().unwrap();
+
+    let raw = format!("{}.bin", base);
+    headless.dump(&raw).unwrap();
+    assert_eq!(std::fs::read(&raw).unwrap(), *headless.machine.video_arr.borrow());
+    assert_eq!(std::fs::metadata(&raw).unwrap().len(), VIDEO_RAM_SIZE as u64);
+
+    let png = format!("{}.PNG", base);
+    headless.dump(&png).unwrap();
+    let data = std::fs::read(&png).unwrap();
+    assert!(data.starts_with(b"\x89PNG\r\n\x1a\n"));
+    // IHDR: 224x256, 黑白两色用 1 bit 调色板
+    assert_eq!(&data[16..26], &[0, 0, 0, 224, 0, 0, 1, 0, 1, 3]);
+    std::fs::remove_file(raw).unwrap();
+    std::fs::remove_file(png).unwrap();
+
+    // 第 0 帧是开机时的画面, 最后一帧之后的不能要
+    let mut launch = HeadlessLaunch::new(InvadersDipSwitches::default());
+    launch.frames = Some(10);
+    launch.dumps = vec![(0, format!("{}-0.bin", base)), (10, format!("{}-10.bin", base))];
+    launch.run().unwrap();
+    let first = std::fs::read(format!("{}-0.bin", base)).unwrap();
+    assert_eq!(first, Headless::power_on(InvadersDipSwitches::default()).unwrap().machine.video_arr.borrow().clone());
+    assert_eq!(std::fs::metadata(format!("{}-10.bin", base)).unwrap().len(), VIDEO_RAM_SIZE as u64);
+    std::fs::remove_file(format!("{}-0.bin", base)).unwrap();
+    std::fs::remove_file(format!("{}-10.bin", base)).unwrap();
+    launch.dumps = vec![(11, format!("{}-11.bin", base))];
+    assert!(launch.run().unwrap_err().contains("after the last frame"));
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rust8080"))
+        .args(["headless", "--dump", "11:late.png", "--frames", "10"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("--dump 11 is after --frames 10"));
+}
+
+#[test]
+fn test_capture_long_still() {
+    // 画面十多分钟不变, 一帧的延时放不下就拆成两帧
+    let path = std::env::temp_dir().join(format!("rust8080-still-{}.gif", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut recorder = Recorder::create(path, 1, 1).unwrap();
+    for _ in 0..40_000 {
+        recorder.frame(&[0xffffff]).unwrap();
+    }
+    recorder.finish().unwrap();
+    let gif = std::fs::read(path).unwrap();
+    let delays: Vec<u32> = gif.windows(4).enumerate().filter(|(_, w)| *w == [0x21, 0xf9, 4, 0])
+        .map(|(i, _)| u32::from(gif[i + 4]) | u32::from(gif[i + 5]) << 8).collect();
+    assert_eq!(delays.len(), 2);
+    assert_eq!(delays.iter().sum::<u32>(), 40_000 * 100 / 60);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_headless_capture() {
+    let base = std::env::temp_dir().join(format!("rust8080-capture-{}", std::process::id()));
+    let base = base.to_str().unwrap();
+    let mut launch = HeadlessLaunch::new(InvadersDipSwitches::default());
+    launch.frames = Some(120);
+
+    launch.capture = Some(format!("{}.gif", base));
+    launch.run().unwrap();
+    let gif = std::fs::read(format!("{}.gif", base)).unwrap();
+    assert!(gif.starts_with(b"GIF89a\xe0\x00\x00\x01"));
+    // 延时加起来是两秒, 按模拟的帧数算
+    let delays: u32 = gif.windows(4).enumerate().filter(|(_, w)| *w == [0x21, 0xf9, 4, 0])
+        .map(|(i, _)| u32::from(gif[i + 4]) | u32::from(gif[i + 5]) << 8).sum();
+    assert_eq!(delays, 200);
+    launch.run().unwrap();
+    assert_eq!(std::fs::read(format!("{}.gif", base)).unwrap(), gif);
+
+    launch.capture = Some(format!("{}.y4m", base));
+    launch.run().unwrap();
+    let y4m = std::fs::metadata(format!("{}.y4m", base)).unwrap().len() as usize;
+    let header = "YUV4MPEG2 W224 H256 F60:1 Ip A1:1 C444\n".len();
+    assert_eq!(y4m, header + 120 * (6 + SCREEN_WIDTH * SCREEN_HEIGHT * 3));
+    assert_eq!(wav_path(&format!("{}.y4m", base)), format!("{}.wav", base));
+    let wav = std::fs::metadata(format!("{}.wav", base)).unwrap().len();
+    assert_eq!(wav, 44 + 120 * 735 * 2);
+    for ext in ["gif", "y4m", "wav"] {
+        std::fs::remove_file(format!("{}.{}", base, ext)).unwrap();
+    }
+    launch.capture = Some(format!("{}.avi", base));
+    assert!(launch.run().is_err());
+}
+
+fn write(port: u8, value: u8, cycle: u64) -> SoundWrite {
+    SoundWrite { port, value, cycle }
+}
+
+#[test]
+fn test_sound_edges() {
+    assert_eq!(sample_at(u64::from(CYCLES_PER_FRAME)), 735);
+    let frame = u64::from(CYCLES_PER_FRAME);
+    let mut sound = InvadersSound::new();
+    sound.set_sample(0, vec![1, 2, 3]);
+    sound.set_sample(1, vec![100; 1000]);
+    sound.set_sample(4, vec![-7; 10]);
+
+    // 功放关着没有声音, 但 UFO 已经在放了
+    let out = sound.mix(&[write(3, 0b0000_0001, 0)], frame);
+    assert_eq!(out.len(), 735);
+    assert!(out.iter().all(|s| *s == 0));
+    assert_eq!(sound.voices(), 1);
+
+    // 功放打开, 在半帧时开枪; UFO 循环播放
+    let half = frame + frame / 2;
+    let out = sound.mix(&[write(3, 0b0010_0001, frame), write(3, 0b0010_0011, half)], frame * 2);
+    assert_eq!(out.len(), 735);
+    assert_eq!(&out[..6], &[1, 2, 3, 1, 2, 3]);
+    let at = (sample_at(half) - 735) as usize;
+    assert_eq!(out[at - 1], [1, 2, 3][(at - 1) % 3]);
+    assert_eq!(out[at], [1, 2, 3][at % 3] + 100);
+
+    // UFO 的位清零就停, 开枪的位清零放完为止; 端口 5 的舰队音
+    let out = sound.mix(&[write(3, 0b0010_0000, frame * 2), write(5, 0b0000_0001, frame * 2)], frame * 3);
+    assert_eq!(&out[..3], &[93, 93, 93]);
+    assert_eq!(out[10], 100);
+    let end = 1000 - (735 - at);
+    assert_eq!((out[end - 1], out[end]), (100, 0));
+    assert_eq!(sound.voices(), 0);
+
+    // 位一直是 1 不会再触发; 没有样本的位不出声
+    let out = sound.mix(&[write(5, 0b0000_0001, frame * 4), write(5, 0b0000_0011, frame * 4)], frame * 5);
+    assert!(out.iter().all(|s| *s == 0));
+    // 回到过去只会重新对齐, 不补声音
+    sound.sync(0);
+    assert_eq!(sound.mix(&[], frame).len(), 735);
+}
+
+#[test]
+fn test_synth() {
+    for effect in 1..SAMPLE_COUNT {
+        let render = || {
+            let mut synth = Synth::new(effect);
+            std::iter::from_fn(|| synth.next_sample()).collect::<Vec<i16>>()
+        };
+        let samples = render();
+        let length = Synth::length(effect).unwrap();
+        assert_eq!(samples.len(), (length * 44100.0).round() as usize, "effect {}", effect);
+        assert!(samples.iter().filter(|s| s.abs() > 1000).count() > samples.len() / 10, "effect {}", effect);
+        // 噪声是确定的, 同样的输入得到同样的声音
+        assert_eq!(samples, render());
+    }
+    assert_eq!(Synth::length(0), None);
+    let mut ufo = Synth::new(0);
+    assert!((0..200_000).all(|_| ufo.next_sample().is_some()));
+    // 舰队的四个音一个比一个低
+    let cycles = |effect: usize| {
+        let mut synth = Synth::new(effect);
+        let samples: Vec<i16> = (0..5000).map(|_| synth.next_sample().unwrap()).collect();
+        samples.windows(2).filter(|w| w[0] <= 0 && w[1] > 0).count()
+    };
+    assert!(cycles(4) > cycles(5) && cycles(5) > cycles(6) && cycles(6) > cycles(7));
+
+    let frame = u64::from(CYCLES_PER_FRAME);
+    let mut sound = InvadersSound::new();
+    let march = [write(3, 0b0010_0000, 0), write(5, 0b0000_0001, 0)];
+    assert!(sound.mix(&march, frame).iter().all(|s| *s == 0));
+    let mut sound = InvadersSound::new();
+    sound.set_synth(true);
+    let mut muted = InvadersSound::new();
+    muted.set_synth(true);
+    assert!(sound.mix(&march, frame).iter().any(|s| *s != 0));
+    assert!(muted.mix(&[write(5, 0b0000_0001, 0)], frame).iter().all(|s| *s == 0));
+    // 有样本的效果用样本
+    let mut sound = InvadersSound::new();
+    sound.set_synth(true);
+    sound.set_sample(8, vec![3; 5]);
+    assert_eq!(&sound.mix(&[write(3, 0b0010_0000, 0), write(5, 0b0001_0000, 0)], frame)[..6], &[3, 3, 3, 3, 3, 0]);
+}
+
+#[test]
+fn test_sound_from_game() {
+    let mut headless = Headless::power_on(InvadersDipSwitches::default()).unwrap();
+    for index in 0..SAMPLE_COUNT {
+        headless.sound.set_sample(index, vec![1000; 100]);
+    }
+    headless.set_script(InputScript::parse("60 tap coin\n120 tap start1\n200 tap fire").unwrap());
+    let mut loud = 0;
+    for _ in 0..600 {
+        headless.step().unwrap();
+        assert_eq!(headless.audio().len(), 735);
+        if headless.audio().iter().any(|s| *s != 0) {
+            loud += 1;
+        }
+    }
+    assert!(loud >= 5, "{}", loud);
+    // 吸引模式下没有声音
+    let mut quiet = Headless::power_on(InvadersDipSwitches::default()).unwrap();
+    quiet.sound.set_sample(0, vec![1000; 100]);
+    quiet.run(300).unwrap();
+    assert!(quiet.audio().iter().all(|s| *s == 0));
+
+    let mut machine = InvadersMachine::from_rom_dir("./res", InvadersDipSwitches::default()).unwrap();
+    let mut writes = Vec::new();
+    for _ in 0..400 {
+        machine.run_frame().unwrap();
+        writes.extend(machine.io.borrow_mut().take_sound_writes());
+    }
+    assert!(!writes.is_empty());
+    assert!(writes.iter().all(|w| (w.port == 3 || w.port == 5) && w.cycle > 0));
+    assert!(writes.windows(2).all(|w| w[0].cycle <= w[1].cycle));
+    assert!(writes.last().unwrap().cycle <= machine.scheduler().cycles());
+    assert!(machine.io.borrow_mut().take_sound_writes().is_empty());
+}
+
+#[test]
+fn test_headless_script() {
+    let script = InputScript::parse("
+        # two coins, then a one player game
+        60 tap coin
+        90 tap coin 3
+        150 press start1
+        155 release start1
+    ").unwrap();
+    let mut headless = Headless::power_on(InvadersDipSwitches::default()).unwrap();
+    headless.set_script(script);
+    headless.run(120).unwrap();
+    assert_eq!(headless.machine.cpu.addring.get_mem(CREDITS), 2);
+    headless.run(200).unwrap();
+    assert_eq!(headless.machine.cpu.addring.get_mem(CREDITS), 1);
+}
+
+#[test]
+fn test_input_script_errors() {
+    let script = InputScript::parse("10 tap fire 2\n10 press left").unwrap();
+    assert_eq!(script.events(10), &[(InvadersButton::P1Fire, true), (InvadersButton::P1Left, true)]);
+    assert_eq!(script.events(12), &[(InvadersButton::P1Fire, false)]);
+    assert_eq!(InputScript::parse("x tap coin"), Err(String::from("line 1: bad frame number")));
+    assert_eq!(InputScript::parse("\n1 tap jump"), Err(String::from("line 2: unknown button")));
+    assert_eq!(InputScript::parse("1 hold coin"), Err(String::from("line 1: expected press, release or tap")));
+}
+
+#[test]
+fn test_overlay_masks() {
+    let at = |mask: &[u32], x: usize, y: usize| mask[y * SCREEN_WIDTH + x];
+    assert!(Overlay::None.mask().iter().all(|c| *c == 0xffffff));
+    let midway = Overlay::Midway.mask();
+    assert_eq!(at(&midway, 100, 10), 0xffffff);
+    assert_eq!(at(&midway, 100, 40), 0xff2020);
+    assert_eq!(at(&midway, 0, 200), 0x20ff20);
+    assert_eq!(at(&midway, 20, 250), 0x20ff20);
+    // 投币数不上色
+    assert_eq!(at(&midway, 200, 250), 0xffffff);
+    let taito = Overlay::Taito.mask();
+    assert_ne!(at(&taito, 100, 10), 0xffffff);
+    assert_ne!(at(&taito, 100, 100), at(&taito, 100, 150));
+
+    let regions = Overlay::parse_regions("# gel\n10 20 5 5 00ff00\n\n200 250 100 100 0000FF # off the edge\n").unwrap();
+    assert_eq!(regions, vec![Region::new(10, 20, 5, 5, 0x00ff00), Region::new(200, 250, 100, 100, 0x0000ff)]);
+    let mask = Overlay::Regions(regions).mask();
+    assert_eq!(at(&mask, 12, 22), 0x00ff00);
+    assert_eq!(at(&mask, 15, 22), 0xffffff);
+    assert_eq!(at(&mask, 223, 255), 0x0000ff);
+    let huge = Overlay::parse_regions("200 250 18446744073709551615 18446744073709551615 00ff00").unwrap();
+    assert_eq!(at(&Overlay::Regions(huge).mask(), 223, 255), 0x00ff00);
+    assert_eq!(Overlay::parse_regions("1 2 3 4"), Err(String::from("line 1: expected X Y WIDTH HEIGHT RRGGBB")));
+    assert_eq!(Overlay::parse_regions("\n1 2 x 4 ffffff"), Err(String::from("line 2: bad number")));
+    assert_eq!(Overlay::parse_regions("1 2 3 4 red"), Err(String::from("line 1: bad colour, expected RRGGBB")));
+
+    let mut image = vec![0u32; SCREEN_WIDTH * SCREEN_HEIGHT];
+    image[0] = 0xff00_00ff;
+    image[1] = 0x8000_0000;
+    let mask = Overlay::Image(image).mask();
+    assert_eq!(&mask[..3], &[0x0000ff, 0x7f7f7f, 0xffffff]);
+
+    assert_eq!(Overlay::from_arg("midway"), Ok(Overlay::Midway));
+    assert!(Overlay::from_arg("/nonexistent/gel.txt").is_err());
+}
+
+#[test]
+fn test_headless_overlay() {
+    let mut headless = Headless::power_on(InvadersDipSwitches::default()).unwrap();
+    headless.set_overlay(&Overlay::Midway);
+    headless.run(300).unwrap();
+    let screen = headless.screen();
+    let mask = Overlay::Midway.mask();
+    assert_eq!(screen.iter().filter(|p| **p != 0).count(), headless.lit_pixels());
+    assert!(screen.iter().zip(&mask).all(|(p, m)| *p == 0 || p == m));
+    assert!(screen.contains(&0x20ff20), "the shields and bases are green");
+}
+
+#[test]
+fn test_artwork_compose() {
+    // 屏幕放大两倍, 背景是暗蓝色
+    let mut artwork = Artwork::new(500, 600, Rect::new(10, 20, 448, 512)).unwrap();
+    artwork.set_backdrop(&Image { width: 500, height: 600, pixels: vec![0xff00_0040; 500 * 600] });
+    let mut bezel = vec![0u32; 500 * 600];
+    bezel[0] = 0xff80_8080;
+    artwork.set_bezel(&Image { width: 500, height: 600, pixels: bezel });
+    let mut playfield = vec![0u32; SCREEN_WIDTH * SCREEN_HEIGHT];
+    playfield[0] = 0x20ff20;
+    playfield[SCREEN_WIDTH * SCREEN_HEIGHT - 1] = 0xffffff;
+    let mut buffer = vec![0u32; 500 * 600];
+    artwork.compose(&playfield, &mut buffer);
+    let at = |x: usize, y: usize| buffer[y * 500 + x];
+    assert_eq!(at(0, 0), 0x808080);
+    assert_eq!(at(5, 5), 0x000040);
+    assert_eq!(at(10, 20), 0x20ff60);
+    assert_eq!(at(11, 21), 0x20ff60);
+    assert_eq!(at(12, 20), 0x000040);
+    assert_eq!(at(457, 531), 0xffffff);
+    assert_eq!(at(458, 531), 0x000040);
+
+    assert!(Artwork::new(100, 100, Rect::new(0, 0, 224, 256)).is_err());
+    assert!(Artwork::new(300, 300, Rect::new(usize::MAX, 0, 224, 256)).is_err());
+    assert!(Artwork::new(300, 300, Rect::new(0, 1, 224, usize::MAX)).is_err());
+}
+
+#[test]
+fn test_artwork_layout() {
+    let dir = std::env::temp_dir().join(format!("rust8080-artwork-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    write_png(dir.join("moon.png").to_str().unwrap(), &vec![0x000020; 300 * 320], 300, 320).unwrap();
+    let layout = dir.join("layout.txt");
+    std::fs::write(&layout, "# upright\nbackdrop moon.png\nscreen 38 32 224 256\n").unwrap();
+    let artwork = Artwork::load(layout.to_str().unwrap()).unwrap();
+    assert_eq!((artwork.width(), artwork.height()), (300, 320));
+    assert_eq!(artwork.screen(), Rect::new(38, 32, 224, 256));
+    let mut buffer = vec![0; 300 * 320];
+    artwork.compose(&vec![0; SCREEN_WIDTH * SCREEN_HEIGHT], &mut buffer);
+    assert!(buffer.iter().all(|p| *p == 0x000020));
+
+    let parse = |text: &str| Artwork::parse(text, &dir).map(|_| ());
+    assert_eq!(parse("size 300 300\nscreen 0 0 224 256"), Ok(()));
+    assert_eq!(parse("size 300 300"), Err(String::from("the layout has no screen")));
+    assert_eq!(parse("screen 0 0 224 256"), Err(String::from("the layout needs a size without a backdrop or a bezel")));
+    assert_eq!(parse("screen 0 0 224"), Err(String::from("line 1: screen takes 4 numbers")));
+    assert_eq!(parse("\nsize 3 x"), Err(String::from("line 2: bad number")));
+    assert_eq!(parse("glass 1"), Err(String::from("line 1: expected backdrop, bezel, screen or size")));
+    assert_eq!(parse("size 100000 100000\nscreen 0 0 224 256"), Err(String::from("the artwork 100000x100000 is larger than 8192x8192")));
+    assert!(parse("size 300 300\nscreen 18446744073709551615 0 224 256").is_err());
+    assert!(parse("bezel missing.png\nscreen 0 0 224 256").unwrap_err().starts_with("line 1: "));
+    std::fs::remove_dir_all(dir).unwrap();
+}
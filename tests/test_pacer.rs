@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use rust8080::util::{Pacer, RateControl};
+
+const MS: Duration = Duration::from_millis(1);
+
+#[test]
+fn test_pacer_deadlines() {
+    let mut pacer = Pacer::new(2_000_000);
+    assert_eq!(pacer.due(2_000_000), Duration::from_secs(1));
+    // 一帧 33333 个周期, 60 帧正好一秒, 不会因为取整漂移
+    assert_eq!(pacer.due(33_333 * 60), Duration::from_micros(999_990));
+    assert_eq!(pacer.delay_at(20_000, Duration::ZERO), 10 * MS);
+    assert_eq!(pacer.delay_at(20_000, 4 * MS), 6 * MS);
+    assert_eq!(pacer.delay_at(20_000, 12 * MS), Duration::ZERO);
+    assert_eq!(pacer.due(20_000), 10 * MS);
+}
+
+#[test]
+fn test_pacer_catches_up_or_gives_up() {
+    let mut pacer = Pacer::new(1000);
+    // 落后一点要追上, 不等待
+    assert_eq!(pacer.delay_at(100, 150 * MS), Duration::ZERO);
+    assert_eq!(pacer.due(100), 100 * MS);
+    // 落后太多 (比如停在监视器里) 就从现在重新算
+    assert_eq!(pacer.delay_at(200, 400 * MS), Duration::ZERO);
+    assert_eq!(pacer.due(200), 400 * MS);
+    assert_eq!(pacer.delay_at(250, 400 * MS), 50 * MS);
+    // 时钟往回走也重新算
+    assert_eq!(pacer.delay_at(10, 500 * MS), Duration::ZERO);
+    assert_eq!(pacer.due(20), 510 * MS);
+}
+
+#[test]
+fn test_pacer_speed() {
+    let mut pacer = Pacer::new(1000);
+    pacer.set_speed(2.0, 100);
+    assert_eq!(pacer.speed(), 2.0);
+    // 改速度的那一刻截止时间不跳
+    assert_eq!(pacer.due(100), 100 * MS);
+    assert_eq!(pacer.due(300), 200 * MS);
+    pacer.set_speed(0.5, 300);
+    assert_eq!(pacer.due(300), 200 * MS);
+    assert_eq!(pacer.due(400), 400 * MS);
+}
+
+#[test]
+fn test_rate_control() {
+    let rate = RateControl::new(2000);
+    assert_eq!(rate.target(), 2000);
+    assert_eq!(rate.speed(2000), 1.0);
+    assert!((rate.speed(0) - 1.005).abs() < 1e-9);
+    assert!((rate.speed(3000) - 0.9975).abs() < 1e-9);
+    // 最多改变 0.5%
+    assert!((rate.speed(100_000) - 0.995).abs() < 1e-9);
+    assert!(rate.speed(1000) > rate.speed(1500));
+}